@@ -13,4 +13,5 @@ pub enum ClientMessage {
 pub enum ServerMessage {
     PingResponse,
     ChatMessage { user_id: Uuid, message: String },
+    UserJoined { user_id: Uuid },
 }
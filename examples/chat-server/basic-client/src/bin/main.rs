@@ -1,5 +1,8 @@
 use anyhow::Result;
-use basic_chat_client::{handler::ServerMessageHandler, input::Inputs};
+use basic_chat_client::{
+    handler::ServerMessageHandler,
+    input::{inputs, Inputs},
+};
 use chat_api::api::ServerMessage;
 use scot::Client;
 
@@ -9,6 +12,10 @@ impl Client for ChatClient {
     type ServerMessage = ServerMessage;
     type ServerMessageHandler = ServerMessageHandler;
     type InputHandler = Inputs;
+
+    fn input_handler(&self) -> Inputs {
+        inputs()
+    }
 }
 
 #[tokio::main]
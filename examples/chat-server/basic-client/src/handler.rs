@@ -18,9 +18,16 @@ impl MessageHandler for ServerMessageHandler {
             ServerMessage::ChatMessage { user_id, message } => {
                 println!("User #{}: {}", user_id, message);
             }
+            ServerMessage::UserJoined { user_id } => {
+                println!("User #{} joined", user_id);
+            }
             _ => {
                 println!("Got a message from the server that the client couldn't understand!")
             }
         }
     }
+
+    async fn on_server_close() {
+        println!("Disconnected from the server.");
+    }
 }
@@ -1,38 +1,21 @@
-use async_trait::async_trait;
-
 use chat_api::api::ClientMessage;
-use futures::SinkExt;
-use scot::{client::InputHandler, types::ValueSender};
+use scot::client::LineInputHandler;
+use tokio::io::{self, BufReader, Stdin};
+
+/// Reads chat messages and commands from stdin, one per line.
+pub type Inputs = LineInputHandler<BufReader<Stdin>, fn(String) -> Option<ClientMessage>>;
 
-pub struct Inputs;
+pub fn inputs() -> Inputs {
+    LineInputHandler::new(BufReader::new(io::stdin()), parse_line)
+}
 
-#[async_trait]
-impl InputHandler for Inputs {
-    async fn next_input(message_channel: &mut ValueSender) {
-        let mut input = String::new();
-        std::io::stdin()
-            .read_line(&mut input)
-            .expect("Error while reading user input!");
-        let trimmed = input.trim_matches(char::is_whitespace);
-        match trimmed {
-            "" => {}
-            "/ping" => {
-                message_channel
-                    .send(serde_json::to_value(&ClientMessage::Ping).unwrap())
-                    .await
-                    .unwrap();
-            }
-            _ => {
-                message_channel
-                    .send(
-                        serde_json::to_value(&ClientMessage::ChatMessage {
-                            message: trimmed.to_string(),
-                        })
-                        .unwrap(),
-                    )
-                    .await
-                    .unwrap();
-            }
-        }
+fn parse_line(input: String) -> Option<ClientMessage> {
+    let trimmed = input.trim_matches(char::is_whitespace);
+    match trimmed {
+        "" => None,
+        "/ping" => Some(ClientMessage::Ping),
+        _ => Some(ClientMessage::ChatMessage {
+            message: trimmed.to_string(),
+        }),
     }
 }
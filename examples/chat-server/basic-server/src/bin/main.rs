@@ -1,14 +1,21 @@
 use std::sync::Arc;
 
 use anyhow::Result;
+use async_trait::async_trait;
 use parking_lot::Mutex;
+use tokio::sync::broadcast;
+use tokio_util::bytes::Bytes;
 use uuid::Uuid;
 
-use scot::Server;
+use scot::{
+    server::recipients::Recipients,
+    types::{ServerMessageChannels, TryBroadcast},
+    Server,
+};
 
 use basic_chat_server::state::ServerState;
 use basic_chat_server::ClientMessageHandler;
-use chat_api::api::ClientMessage;
+use chat_api::api::{ClientMessage, ServerMessage};
 
 /// This server uses the simplest way to share data, which is to wrap
 /// the entire state in an [`Arc<Mutex<T>>`].
@@ -26,6 +33,7 @@ impl ChatServer {
     }
 }
 
+#[async_trait]
 impl Server for ChatServer {
     type ClientID = Uuid;
     type ClientMessage = ClientMessage;
@@ -35,6 +43,37 @@ impl Server for ChatServer {
     fn get_state(&self) -> Arc<Mutex<ServerState>> {
         self.state.clone()
     }
+
+    /// Catch new users up on the conversation so far.
+    async fn on_join_snapshot(
+        &self,
+        _id: &Uuid,
+        channels: &mut ServerMessageChannels<Uuid>,
+        state: &mut Arc<Mutex<ServerState>>,
+    ) {
+        let history: Vec<_> = state.lock().history.iter().cloned().collect();
+        for (user_id, message) in history {
+            let message = ServerMessage::ChatMessage { user_id, message };
+            if channels.respond(&message).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Let everyone else know someone new is here.
+    async fn after_join(
+        &self,
+        id: &Uuid,
+        broadcast_sender: &broadcast::Sender<(Bytes, Recipients<Uuid>)>,
+        state: &mut Arc<Mutex<ServerState>>,
+    ) {
+        let users: Vec<Uuid> = { state.lock().users.clone() };
+        let recipients = Recipients::everyone_but(id, users);
+        let message = ServerMessage::UserJoined { user_id: *id };
+        if let Err(e) = broadcast_sender.try_broadcast(&message, recipients) {
+            println!("Couldn't announce new user: {}", e);
+        }
+    }
 }
 
 #[tokio::main]
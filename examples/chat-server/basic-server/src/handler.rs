@@ -1,7 +1,6 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use futures::SinkExt;
 use parking_lot::Mutex;
 
 use crate::state::ServerState;
@@ -28,31 +27,28 @@ impl MessageHandler for ClientMessageHandler {
         match msg {
             ClientMessage::Ping => {
                 println!("Got a ping from user {}!", user_id);
-                message_channels
-                    .response_sender
-                    .send(serde_json::to_value(ServerMessage::PingResponse).unwrap())
-                    .await
-                    .unwrap();
+                if let Err(e) = message_channels.respond(&ServerMessage::PingResponse).await {
+                    println!("Couldn't respond to ping: {}", e);
+                }
             }
             ClientMessage::ChatMessage { message } => {
-                let message = serde_json::to_value(ServerMessage::ChatMessage {
-                    user_id: *user_id,
-                    message,
-                })
-                .unwrap();
                 let users: Vec<Uuid> = { state.lock().users.clone() };
                 let recipients = Recipients::everyone_but(user_id, users);
 
                 {
                     let mut state = state.lock();
+                    state.record(*user_id, message.clone());
                     state.message_counter += 1;
                     println!("Total messages: {}", state.message_counter);
                 }
 
-                message_channels
-                    .broadcast_sender
-                    .send((message, recipients))
-                    .unwrap();
+                let message = ServerMessage::ChatMessage {
+                    user_id: *user_id,
+                    message,
+                };
+                if let Err(e) = message_channels.try_broadcast(&message, recipients) {
+                    println!("Couldn't send chat message: {}", e);
+                }
             }
 
             _ => {
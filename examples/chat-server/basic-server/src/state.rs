@@ -1,10 +1,26 @@
+use std::collections::VecDeque;
+
 use scot::server::State;
 use uuid::Uuid;
 
+/// How many chat messages are kept to send to users who join later.
+pub const HISTORY_LENGTH: usize = 20;
+
 #[derive(Default)]
 pub struct ServerState {
     pub users: Vec<Uuid>,
     pub message_counter: usize,
+    pub history: VecDeque<(Uuid, String)>,
+}
+
+impl ServerState {
+    /// Remember a chat message, forgetting the oldest one if the history is full.
+    pub fn record(&mut self, user_id: Uuid, message: String) {
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back((user_id, message));
+    }
 }
 
 impl State for ServerState {
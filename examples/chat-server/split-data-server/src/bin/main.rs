@@ -1,9 +1,12 @@
 use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::broadcast;
+use tokio_util::bytes::Bytes;
 use uuid::Uuid;
 
-use scot::Server;
+use scot::{server::recipients::Recipients, types::TryBroadcast, Server};
 
-use chat_api::api::ClientMessage;
+use chat_api::api::{ClientMessage, ServerMessage};
 use split_data_server::state::ServerState;
 use split_data_server::ClientMessageHandler;
 
@@ -19,6 +22,7 @@ impl ChatServer {
     }
 }
 
+#[async_trait]
 impl Server for ChatServer {
     type ClientID = Uuid;
     type ClientMessage = ClientMessage;
@@ -28,6 +32,21 @@ impl Server for ChatServer {
     fn get_state(&self) -> ServerState {
         self.state.clone()
     }
+
+    /// Let everyone else know someone new is here.
+    async fn after_join(
+        &self,
+        id: &Uuid,
+        broadcast_sender: &broadcast::Sender<(Bytes, Recipients<Uuid>)>,
+        state: &mut ServerState,
+    ) {
+        let users: Vec<Uuid> = { state.users.lock().clone() };
+        let recipients = Recipients::everyone_but(id, users);
+        let message = ServerMessage::UserJoined { user_id: *id };
+        if let Err(e) = broadcast_sender.try_broadcast(&message, recipients) {
+            println!("Couldn't announce new user: {}", e);
+        }
+    }
 }
 
 #[tokio::main]
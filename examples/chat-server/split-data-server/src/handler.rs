@@ -2,7 +2,6 @@ use std::sync::atomic::Ordering;
 
 use async_trait::async_trait;
 use chat_api::api::{ClientMessage, ServerMessage};
-use futures::SinkExt;
 use scot::{server::recipients::Recipients, server::MessageHandler, types::*};
 use uuid::Uuid;
 
@@ -27,18 +26,15 @@ impl MessageHandler for ClientMessageHandler {
         match msg {
             ClientMessage::Ping => {
                 println!("Got a ping from user {}!", user_id);
-                message_channels
-                    .response_sender
-                    .send(serde_json::to_value(ServerMessage::PingResponse).unwrap())
-                    .await
-                    .unwrap();
+                if let Err(e) = message_channels.respond(&ServerMessage::PingResponse).await {
+                    println!("Couldn't respond to ping: {}", e);
+                }
             }
             ClientMessage::ChatMessage { message } => {
-                let message = serde_json::to_value(ServerMessage::ChatMessage {
+                let message = ServerMessage::ChatMessage {
                     user_id: *user_id,
                     message,
-                })
-                .unwrap();
+                };
                 let users: Vec<Uuid> = { state.users.lock().clone() };
                 let recipients = Recipients::everyone_but(user_id, users);
 
@@ -50,10 +46,9 @@ impl MessageHandler for ClientMessageHandler {
                     );
                 }
 
-                message_channels
-                    .broadcast_sender
-                    .send((message, recipients))
-                    .unwrap();
+                if let Err(e) = message_channels.try_broadcast(&message, recipients) {
+                    println!("Couldn't send chat message: {}", e);
+                }
             }
 
             _ => {
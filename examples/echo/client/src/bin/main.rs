@@ -0,0 +1,10 @@
+use anyhow::Result;
+use echo_client::{EchoClient, ROUNDS};
+use scot::Client;
+
+#[tokio::main]
+pub async fn main() -> Result<()> {
+    EchoClient.start("localhost:31195").await?;
+    println!("Got all {} echoes back", ROUNDS);
+    Ok(())
+}
@@ -0,0 +1,73 @@
+//! A client for the echo server, which checks that everything it sends comes
+//! back unchanged.
+//!
+//! The client sends numbered messages one at a time, each once the previous
+//! one has been echoed correctly, and disconnects after the last, so
+//! [`Client::start`] only returns once every echo has arrived intact.
+
+use std::ops::ControlFlow;
+
+use async_trait::async_trait;
+use futures::future;
+
+use echo_server::Echo;
+use scot::{
+    client::{InputHandler, MessageHandler},
+    types::ValueSender,
+    Client,
+};
+
+/// How many messages make a round of echoes.
+pub const ROUNDS: u32 = 10;
+
+pub struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ServerMessage = Echo;
+
+    async fn handle_server_message(_msg: Echo, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(msg: Echo, response_channel: &mut ValueSender) -> ControlFlow<()> {
+        assert_eq!(msg, Echo::numbered(msg.seq), "the echo doesn't match");
+        let next = msg.seq + 1;
+        if next == ROUNDS {
+            return ControlFlow::Break(());
+        }
+        if let Err(e) = response_channel.send_message(&Echo::numbered(next)).await {
+            println!("Couldn't send message: {}", e);
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Sends the first message, after which the echoes keep things going.
+#[derive(Default)]
+pub struct FirstMessage {
+    sent: bool,
+}
+
+#[async_trait]
+impl InputHandler for FirstMessage {
+    async fn next_input(&mut self, message_channel: &mut ValueSender) {
+        if self.sent {
+            return future::pending().await;
+        }
+        self.sent = true;
+        if let Err(e) = message_channel.send_message(&Echo::numbered(0)).await {
+            println!("Couldn't send message: {}", e);
+        }
+    }
+}
+
+pub struct EchoClient;
+
+impl Client for EchoClient {
+    type ServerMessage = Echo;
+    type ServerMessageHandler = EchoHandler;
+    type InputHandler = FirstMessage;
+
+    fn input_handler(&self) -> FirstMessage {
+        FirstMessage::default()
+    }
+}
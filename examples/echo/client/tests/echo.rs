@@ -0,0 +1,20 @@
+use std::time::Duration;
+
+use echo_client::EchoClient;
+use echo_server::EchoServer;
+use scot::{Client, Server};
+use tokio::time;
+
+#[tokio::test]
+async fn every_message_comes_back() {
+    let running = EchoServer::new().launch("localhost:0").await.unwrap();
+    let addr = running.local_addr().to_string();
+
+    // The client only disconnects once every echo has arrived intact
+    time::timeout(Duration::from_secs(10), EchoClient.start(&addr))
+        .await
+        .expect("not every message came back")
+        .unwrap();
+
+    running.shutdown().await.unwrap();
+}
@@ -0,0 +1,8 @@
+use anyhow::Result;
+use echo_server::EchoServer;
+use scot::Server;
+
+#[tokio::main]
+pub async fn main() -> Result<()> {
+    EchoServer::new().start("localhost:31195").await
+}
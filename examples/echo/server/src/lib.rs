@@ -0,0 +1,71 @@
+//! The smallest useful server: every message a client sends comes straight
+//! back to it.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+
+/// The messages exchanged with the server, in both directions.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Echo {
+    pub seq: u32,
+    pub text: String,
+}
+
+impl Echo {
+    /// The message numbered `seq`, whose text can be told from its number,
+    /// so that an echo can be checked without remembering what was sent.
+    pub fn numbered(seq: u32) -> Echo {
+        Echo {
+            seq,
+            text: format!("echo #{seq}"),
+        }
+    }
+}
+
+pub struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = Echo;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Echo,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        if let Err(e) = channels.respond(&msg).await {
+            println!("Couldn't echo message: {}", e);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EchoServer {
+    ids: SequentialIdAllocator,
+}
+
+impl EchoServer {
+    pub fn new() -> EchoServer {
+        EchoServer::default()
+    }
+}
+
+impl Server for EchoServer {
+    type ClientID = usize;
+    type ClientMessage = Echo;
+    type ClientMessageHandler = EchoHandler;
+    type State = SequentialIdAllocator;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
@@ -0,0 +1,142 @@
+//! A client for code that isn't async.
+
+use std::{io, marker::PhantomData};
+
+use anyhow::Result;
+use futures::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tokio::{
+    io::{ReadHalf, WriteHalf},
+    net::TcpStream,
+    runtime::{self, Runtime},
+};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{FramedRead, FramedWrite},
+};
+
+use crate::{
+    codec::FrameCodec,
+    envelope::{self, Control},
+};
+
+/// A client whose methods block until they're done, for programs that
+/// don't otherwise use async, e.g. small command-line tools.
+///
+/// Unlike [`Client`](super::Client), there are no handlers: messages are
+/// sent with [`BlockingClient::send`] and received one at a time with
+/// [`BlockingClient::recv`]. Each `BlockingClient` spins up its own
+/// single-threaded tokio runtime, which only runs while one of its methods
+/// is being called, so it must not be used from within another tokio
+/// runtime.
+///
+/// The connection uses the default settings of [`Client`](super::Client),
+/// with a codec of choice.
+///
+/// ```no_run
+/// use scot::client::BlockingClient;
+///
+/// let mut client = BlockingClient::<String>::connect("localhost:1234")?;
+/// client.send("Ping")?;
+/// if let Some(reply) = client.recv()? {
+///     println!("{reply}");
+/// }
+/// client.close()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// Type parameter is the type of messages received from the server.
+pub struct BlockingClient<M> {
+    runtime: Runtime,
+    reader: FramedRead<ReadHalf<TcpStream>, FrameCodec>,
+    writer: FramedWrite<WriteHalf<TcpStream>, FrameCodec>,
+    message: PhantomData<fn() -> M>,
+}
+
+impl<M: DeserializeOwned> BlockingClient<M> {
+    /// Connect to the given address, framing messages with
+    /// [`FrameCodec::length_delimited`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the runtime can't be created or connecting fails.
+    pub fn connect(addr: &str) -> Result<Self> {
+        BlockingClient::connect_with_codec(addr, FrameCodec::length_delimited)
+    }
+
+    /// Connect to the given address, framing messages with the codec
+    /// created by `codec`, which must match the server's.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the runtime can't be created or connecting fails.
+    pub fn connect_with_codec(addr: &str, codec: impl Fn() -> FrameCodec) -> Result<Self> {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let stream = runtime.block_on(TcpStream::connect(addr))?;
+        let (read_half, write_half) = tokio::io::split(stream);
+        Ok(BlockingClient {
+            reader: FramedRead::new(read_half, codec()),
+            writer: FramedWrite::new(write_half, codec()),
+            runtime,
+            message: PhantomData,
+        })
+    }
+
+    /// Serialize a message and send it to the server, blocking until it has
+    /// been written.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the message can't be serialized, or writing fails.
+    pub fn send<T: Serialize + ?Sized>(&mut self, message: &T) -> io::Result<()> {
+        let value = serde_json::to_value(message).map_err(io::Error::from)?;
+        let frame = serde_json::to_vec(&envelope::escape(value))?;
+        self.runtime.block_on(self.writer.send(Bytes::from(frame)))
+    }
+
+    /// Block until the next message from the server arrives. Returns `None`
+    /// once the server has closed the connection.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading fails, or the message can't be deserialized.
+    pub fn recv(&mut self) -> Result<Option<M>> {
+        let Some(frame) = self.runtime.block_on(self.reader.next()) else {
+            return Ok(None);
+        };
+        let frame = frame?;
+        // Messages that look like frames meant for the framework arrive
+        // escaped
+        if envelope::may_need_escape(&frame) {
+            let value = serde_json::from_slice(&frame)?;
+            return Ok(Some(serde_json::from_value(envelope::unescape(value))?));
+        }
+        Ok(Some(serde_json::from_slice(&frame)?))
+    }
+
+    /// Say goodbye to the server and wait for it to finish handling the
+    /// client leaving, like [`Client::start`](super::Client::start) does
+    /// when disconnecting. Messages still arriving in the meantime are
+    /// dropped.
+    ///
+    /// # Errors
+    ///
+    /// Fails if writing the goodbye fails.
+    pub fn close(mut self) -> io::Result<()> {
+        let goodbye = serde_json::to_vec(&Control::Goodbye.to_value())?;
+        self.runtime.block_on(async {
+            self.writer.send(Bytes::from(goodbye)).await?;
+            self.writer.close().await?;
+            while let Some(Ok(frame)) = self.reader.next().await {
+                let value = serde_json::from_slice::<Value>(&frame);
+                if value.is_ok_and(|value| Control::parse(&value) == Some(Control::Goodbye)) {
+                    break;
+                }
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,73 @@
+//! Connecting to hosts with several addresses, racing them in the style of
+//! Happy Eyeballs (RFC 8305).
+
+use std::{io, net::SocketAddr, time::Duration};
+
+use futures::{prelude::*, stream::FuturesUnordered};
+use tokio::{
+    net::{self, TcpStream},
+    time,
+};
+
+/// How long to wait for an attempt before starting the next one, as
+/// recommended by RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Resolve `addr` and connect to whichever of its addresses answers first.
+///
+/// Attempts are started one at a time, alternating between IPv6 and IPv4,
+/// and the next one starts as soon as the previous one fails or after
+/// [`CONNECTION_ATTEMPT_DELAY`], whichever comes first, so a dead address
+/// only delays connecting instead of holding it up entirely. Attempts still
+/// running when one succeeds are dropped.
+pub(crate) async fn connect(addr: &str) -> io::Result<TcpStream> {
+    let mut candidates = interleave(net::lookup_host(addr).await?.collect()).into_iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_error = None;
+
+    loop {
+        if attempts.is_empty() {
+            match candidates.next() {
+                Some(candidate) => attempts.push(TcpStream::connect(candidate)),
+                None => break,
+            }
+        }
+
+        tokio::select! {
+            Some(result) = attempts.next() => match result {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    last_error = Some(e);
+                    attempts.extend(candidates.next().map(TcpStream::connect));
+                }
+            },
+            () = time::sleep(CONNECTION_ATTEMPT_DELAY), if !candidates.as_slice().is_empty() => {
+                attempts.extend(candidates.next().map(TcpStream::connect));
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "address didn't resolve to anything",
+        )
+    }))
+}
+
+/// Order addresses alternating between families, starting with the family
+/// of the first one, and otherwise keeping the resolver's order.
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_ipv6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (preferred, other): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_ipv6);
+    let mut other = other.into_iter();
+    let mut interleaved = Vec::with_capacity(preferred.len() + other.len());
+    for addr in preferred {
+        interleaved.push(addr);
+        interleaved.extend(other.next());
+    }
+    interleaved.extend(other);
+    interleaved
+}
@@ -0,0 +1,67 @@
+//! Reading input line by line from any asynchronous source.
+
+use async_trait::async_trait;
+use futures::future;
+use serde::Serialize;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, Lines};
+
+use super::InputHandler;
+use crate::types::ValueSender;
+
+/// An [`InputHandler`] reading newline-delimited input from an
+/// [`AsyncBufRead`], e.g. stdin, a file, a pipe or a socket, and turning
+/// each line into a message for the server with a closure. Lines mapped to
+/// `None` aren't sent.
+///
+/// Once the source reaches its end or fails, no more input is read, and the
+/// client keeps running until it's disconnected.
+///
+/// ```
+/// use scot::client::LineInputHandler;
+///
+/// let input: &[u8] = b"hello\n\nworld\n";
+/// let handler = LineInputHandler::new(input, |line: String| {
+///     (!line.is_empty()).then_some(line)
+/// });
+/// ```
+pub struct LineInputHandler<R, F> {
+    lines: Lines<R>,
+    map: F,
+    done: bool,
+}
+
+impl<R: AsyncBufRead, F> LineInputHandler<R, F> {
+    /// Read lines from `reader`, turning each one into a message with `map`.
+    #[must_use]
+    pub fn new(reader: R, map: F) -> Self {
+        LineInputHandler {
+            lines: reader.lines(),
+            map,
+            done: false,
+        }
+    }
+}
+
+#[async_trait]
+impl<R, F, M> InputHandler for LineInputHandler<R, F>
+where
+    R: AsyncBufRead + Unpin + Send,
+    F: FnMut(String) -> Option<M> + Send,
+    M: Serialize + Send + Sync,
+{
+    async fn next_input(&mut self, message_channel: &mut ValueSender) {
+        if self.done {
+            return future::pending().await;
+        }
+        match self.lines.next_line().await {
+            Ok(Some(line)) => {
+                if let Some(message) = (self.map)(line) {
+                    // Failing to send means the connection is closing, which
+                    // the client finds out about on its own
+                    let _ = message_channel.send_message(&message).await;
+                }
+            }
+            Ok(None) | Err(_) => self.done = true,
+        }
+    }
+}
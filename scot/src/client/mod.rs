@@ -4,19 +4,42 @@
 //! - Importing the API that the server is using
 //! - Defining a [`MessageHandler`] to handle incoming server messages
 //! - Defining an [`InputHandler`] to receive input from the client and
-//! respond appropriately, sending messages to the server when needed
+//!   respond appropriately, sending messages to the server when needed
 //! - Defining a [`Client`] struct
 //! - Starting the client
 
-use crate::types::{MessageReceiver, ValueSender};
+mod blocking;
+mod happy_eyeballs;
+mod line_input;
+mod options;
+mod retry;
+mod sink;
+
+pub use blocking::BlockingClient;
+pub use line_input::LineInputHandler;
+pub use options::ClientOptions;
+pub use retry::{Jitter, RetryPolicy};
+pub use sink::ServerSink;
+
+use crate::{
+    codec::{self, FrameCodec},
+    compression::{self, Compression, SharedCompression},
+    envelope::{self, Control},
+    sealed::{self, Cipher},
+    types::{MessageReceiver, Transport, ValueSender, WriterOptions},
+    watchdog,
+};
+
+use std::{io, net::SocketAddr, ops::ControlFlow, sync::Arc, time::Duration};
 
 use anyhow::{Error, Result};
 use async_trait::async_trait;
-use futures::prelude::*;
+use futures::{channel::oneshot, future::BoxFuture, prelude::*};
 use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
 use tokio::net::TcpStream;
 use tokio_serde::formats::SymmetricalJson;
-use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
+use tokio_util::bytes::{Bytes, BytesMut};
 
 /// The base trait for the client half of the client-server
 ///
@@ -43,7 +66,7 @@ use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
 /// #
 /// # #[async_trait]
 /// # impl InputHandler for GUIInputHandler {
-/// #   async fn next_input(serialized: &mut ValueSender) {}
+/// #   async fn next_input(&mut self, serialized: &mut ValueSender) {}
 /// # }
 ///
 /// struct ChatClient;
@@ -53,6 +76,10 @@ use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
 ///     type ServerMessage = ChatServerMessage;
 ///     type ServerMessageHandler = ServerMessageHandler;
 ///     type InputHandler = GUIInputHandler;
+///
+///     fn input_handler(&self) -> GUIInputHandler {
+///         GUIInputHandler
+///     }
 /// }
 ///
 /// #[tokio::main]
@@ -65,69 +92,671 @@ use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
 pub trait Client {
     /// The type representing messages received from the server. Should be
     /// imported from the server's API.
+    ///
+    /// For peers that both send and receive the same messages, this can be
+    /// the same type as the server's [`crate::Server::ClientMessage`].
     type ServerMessage: 'static + Serialize + DeserializeOwned + Unpin + Send;
     /// A type implementing [`MessageHandler`] for the given [`Self::ServerMessage`] type
-    type ServerMessageHandler: MessageHandler<ServerMessage = Self::ServerMessage>;
+    type ServerMessageHandler: MessageHandler<ServerMessage = Self::ServerMessage> + Send;
     /// Implements [`InputHandler`], which accepts input from the client in
     /// some form and responds, possibly sending messages to the server.
     type InputHandler: InputHandler;
 
+    /// Create the [`Self::InputHandler`] for a new connection. It's used for
+    /// as long as the client is running, and can hold state, e.g. the
+    /// source it reads input from.
+    fn input_handler(&self) -> Self::InputHandler;
+
+    /// Get every setting for running the client at once, read whenever it
+    /// connects. Overriding this is an alternative to overriding the
+    /// individual methods, such as [`Client::keepalive_interval`], one by
+    /// one.
+    ///
+    /// Defaults to collecting the individual methods into a
+    /// [`ClientOptions`], so they're ignored when this is overridden.
+    fn options(&self) -> ClientOptions {
+        ClientOptions {
+            length_field_length: self.length_field_length(),
+            json_pretty: self.json_pretty(),
+            chunk_size: self.chunk_size(),
+            read_buffer_capacity: self.read_buffer_capacity(),
+            write_buffer_capacity: self.write_buffer_capacity(),
+            require_ack: self.require_ack(),
+            keepalive_interval: self.keepalive_interval(),
+            coalesced: self.coalesced(),
+            compression: self.compression(),
+            slow_handler_warn: self.slow_handler_warn(),
+            protocol_version: self.protocol_version(),
+        }
+    }
+
+    /// Get the codec used for framing messages. Must match the codec used by
+    /// the server.
+    ///
+    /// Defaults to [`FrameCodec::length_delimited_with`], using
+    /// [`ClientOptions::length_field_length`] from [`Client::options`].
+    fn codec(&self) -> FrameCodec {
+        FrameCodec::length_delimited_with(self.options().length_field_length)
+    }
+
+    /// The size, in bytes, of the length prefix of each frame sent with the
+    /// default [`Client::codec`]. Must match the server's setting, see
+    /// [`crate::Server::length_field_length`].
+    ///
+    /// Defaults to 4.
+    fn length_field_length(&self) -> usize {
+        4
+    }
+
+    /// Whether to pretty-print the JSON sent to the server, see
+    /// [`crate::Server::json_pretty`].
+    ///
+    /// Defaults to `false`.
+    fn json_pretty(&self) -> bool {
+        false
+    }
+
+    /// The largest message, in bytes, to send as a single frame, see
+    /// [`crate::Server::chunk_size`]. Must match the server's setting.
+    ///
+    /// Defaults to `None`, which never splits messages.
+    fn chunk_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// How many bytes to read from the socket at a time, see
+    /// [`crate::Server::read_buffer_capacity`].
+    ///
+    /// Defaults to `None`, which uses the default of
+    /// [`FramedRead`](tokio_util::codec::FramedRead) (8 KiB).
+    fn read_buffer_capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// How many bytes of outgoing frames to buffer before writing them to
+    /// the socket, see [`crate::Server::write_buffer_capacity`].
+    ///
+    /// Defaults to `None`, which uses the default of
+    /// [`FramedWrite`](tokio_util::codec::FramedWrite) (8 KiB).
+    fn write_buffer_capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether the server requires messages to be acknowledged, see
+    /// [`crate::Server::require_ack`]. When `true`, each message is
+    /// acknowledged once [`MessageHandler::handle_server_message`] returns.
+    /// Must match the server's setting.
+    ///
+    /// Defaults to `false`.
+    fn require_ack(&self) -> bool {
+        false
+    }
+
+    /// How long the client may go without sending anything before it sends
+    /// a keepalive, e.g. to stop NATs from dropping idle connections. The
+    /// server skips keepalives, so they never reach its
+    /// [`crate::server::MessageHandler`].
+    ///
+    /// Defaults to `None`, which never sends keepalives.
+    fn keepalive_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether the server coalesces messages into batches, see
+    /// [`crate::Server::coalesce_window`]. When `true`, frames containing a
+    /// JSON array are split and each element is handled as its own message,
+    /// so [`Self::ServerMessage`] must not serialize to a JSON array.
+    ///
+    /// Defaults to `false`.
+    fn coalesced(&self) -> bool {
+        false
+    }
+
+    /// The compression algorithms the client supports, in order of
+    /// preference. When not empty, they're offered to the server, which
+    /// picks the one to use, see [`crate::compression`].
+    ///
+    /// Defaults to none, which never compresses messages.
+    fn compression(&self) -> Vec<Compression> {
+        Vec::new()
+    }
+
+    /// How long a single poll of a handler call may take before it's
+    /// reported to [`MessageHandler::on_slow_handler`], see
+    /// [`crate::Server::slow_handler_warn`]. Covers [`MessageHandler`] as
+    /// well as [`InputHandler::next_input`], so an input handler reading
+    /// stdin with blocking calls, instead of e.g. [`LineInputHandler`], is
+    /// caught.
+    ///
+    /// Defaults to `None`, which doesn't measure anything.
+    fn slow_handler_warn(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The version of the protocol, i.e. of the message types, that the
+    /// client speaks, see [`crate::Server::protocol_version`]. When it's
+    /// not 0, the client declares it before sending anything else, and
+    /// checks the version the server declares before handling any of its
+    /// messages. Servers declaring another version, or none at all, are
+    /// passed to [`MessageHandler::on_version_mismatch`].
+    ///
+    /// Defaults to 0, which neither declares nor checks a version.
+    fn protocol_version(&self) -> u32 {
+        0
+    }
+
+    /// The cipher for opening [sealed](crate::sealed) message bodies. With
+    /// one, every sealed part of a message from the server is decrypted
+    /// before the message is deserialized, and messages that can't be
+    /// decrypted go to [`MessageHandler::handle_bad_message`]. To seal
+    /// outgoing bodies, e.g. in the [`InputHandler`], use
+    /// [`Sealed::seal`](crate::sealed::Sealed::seal) with the same cipher.
+    ///
+    /// Defaults to `None`, which leaves sealed parts as they are.
+    fn cipher(&self) -> Option<Arc<dyn Cipher>> {
+        None
+    }
+
+    /// Called once connected, with the client's local address (e.g. the
+    /// ephemeral port it was given) and the address of the server it
+    /// resolved to, e.g. for logging or for reporting the endpoint to a
+    /// coordinator. Called by the methods that connect by themselves, such
+    /// as [`Client::start`], before anything is sent. Callers of
+    /// [`Client::start_with_stream`] have the stream at hand already.
+    ///
+    /// Defaults to doing nothing.
+    fn on_connect(&self, _local_addr: SocketAddr, _peer_addr: SocketAddr) {}
+
+    /// Called by [`Client::connect_retrying`] whenever an attempt to
+    /// connect fails, with the attempt's number, starting at 1, and the
+    /// error, e.g. to print "retrying (3)...". Also called for the last
+    /// attempt, before giving up.
+    ///
+    /// Defaults to doing nothing.
+    fn on_connect_attempt(&self, _attempt: u32, _err: &io::Error) {}
+
     /// Start the client and connect to the given address.
     async fn start(&self, addr: &str) -> Result<()> {
         let stream = TcpStream::connect(addr).await?;
+        connected(self, &stream)?;
         self.start_with_stream(stream).await
     }
 
-    /// Start the client with a given [`TcpStream`].
-    async fn start_with_stream(&self, stream: TcpStream) -> Result<()> {
-        // Duplicate the stream: one for serializing and one for deserializing
-        let receiver_stream = stream.into_std()?;
-        let message_handler_sender_stream = receiver_stream.try_clone()?;
-        let input_handler_sender_stream = receiver_stream.try_clone()?;
-        let receiver_stream = TcpStream::from_std(receiver_stream)?;
-        let message_handler_sender_stream = TcpStream::from_std(message_handler_sender_stream)?;
-        let input_handler_sender_stream = TcpStream::from_std(input_handler_sender_stream)?;
-
-        let mut receiver: MessageReceiver<Self::ServerMessage> =
-            tokio_serde::SymmetricallyFramed::new(
-                FramedRead::new(receiver_stream, LengthDelimitedCodec::new()),
-                SymmetricalJson::<Self::ServerMessage>::default(),
-            );
-
-        let mut message_handler_sender: ValueSender = tokio_serde::SymmetricallyFramed::new(
-            FramedWrite::new(message_handler_sender_stream, LengthDelimitedCodec::new()),
-            SymmetricalJson::default(),
-        );
+    /// Start the client like [`Client::start`], but retry connecting with
+    /// backoff as `policy` says, e.g. when the server may still be starting
+    /// up. [`Client::on_connect_attempt`] is called for every attempt that
+    /// fails. Once connected, this behaves like [`Client::start`]; failures
+    /// after that aren't retried.
+    ///
+    /// Fails with the last attempt's error once every attempt has failed.
+    async fn connect_retrying(&self, addr: &str, policy: RetryPolicy) -> Result<()> {
+        let stream = retry::connect(addr, &policy, |attempt, err| {
+            self.on_connect_attempt(attempt, err);
+        })
+        .await?;
+        connected(self, &stream)?;
+        self.start_with_stream(stream).await
+    }
 
-        let mut input_handler_sender: ValueSender = tokio_serde::SymmetricallyFramed::new(
-            FramedWrite::new(input_handler_sender_stream, LengthDelimitedCodec::new()),
-            SymmetricalJson::default(),
-        );
+    /// Start the client like [`Client::connect_retrying`], and reconnect the
+    /// same way whenever the server closes the connection, e.g. because it
+    /// restarted. Input carries on with the same [`Self::InputHandler`]
+    /// once reconnected, after [`Client::on_reconnected`] has restored the
+    /// client's session.
+    ///
+    /// Returns once the [`MessageHandler`] asks to disconnect. Fails with
+    /// the last attempt's error once every attempt to connect, or to
+    /// reconnect, has failed.
+    async fn start_reconnecting(&self, addr: &str, policy: RetryPolicy) -> Result<()> {
+        let mut input_handler = self.input_handler();
+        let mut reconnected = false;
+        let options = self.options();
+        loop {
+            let stream = retry::connect(addr, &policy, |attempt, err| {
+                self.on_connect_attempt(attempt, err);
+            })
+            .await?;
+            connected(self, &stream)?;
+            let (mut sender, disconnect_receiver) = connect(self, &options, stream);
+            if reconnected {
+                self.on_reconnected(&mut sender).await;
+            }
+            let disconnected = run_input::<Self::ServerMessageHandler, _>(
+                &mut input_handler,
+                &mut sender,
+                disconnect_receiver,
+                options.slow_handler_warn,
+            )
+            .await;
+            if disconnected == Disconnected::ByHandler {
+                return Ok(());
+            }
+            reconnected = true;
+        }
+    }
+
+    /// Called by [`Client::start_reconnecting`] once it has reconnected,
+    /// before any input is read, with the channel for sending to the
+    /// server. The server sees a new connection, which doesn't have the
+    /// session the old one had, so this is where to restore it, e.g. by
+    /// authenticating again and rejoining rooms. The server can't tell on
+    /// its own that this is the same client: to pick up where it left off,
+    /// send it something identifying the old session, such as a token it
+    /// handed out, and have its handler restore the session's state.
+    ///
+    /// Messages from the server may be handled while this runs, as they
+    /// are from the moment the client is connected.
+    ///
+    /// Defaults to doing nothing.
+    async fn on_reconnected(&self, _sender: &mut ValueSender) {}
+
+    /// Start the client like [`Client::start`], but for host names that
+    /// resolve to several addresses, e.g. both IPv6 and IPv4 ones, race
+    /// connection attempts instead of trying one address after the other,
+    /// in the style of Happy Eyeballs (RFC 8305). Attempts alternate between
+    /// IPv6 and IPv4, and each one starts 250 ms after the previous one or
+    /// as soon as it fails, so an unreachable address doesn't hold up
+    /// connecting through the others. The first connection to succeed is
+    /// used.
+    async fn start_happy_eyeballs(&self, addr: &str) -> Result<()> {
+        let stream = happy_eyeballs::connect(addr).await?;
+        connected(self, &stream)?;
+        self.start_with_stream(stream).await
+    }
+
+    /// Start the client with a given stream, usually a [`TcpStream`].
+    ///
+    /// Returns once the [`MessageHandler`] asks to disconnect, or the server
+    /// closes the connection. When disconnecting, the client says goodbye
+    /// and waits for the server to acknowledge it (see [`crate::envelope`]),
+    /// so the server has already handled the client leaving by the time
+    /// this returns.
+    async fn start_with_stream<S: Transport>(&self, stream: S) -> Result<()> {
+        let options = self.options();
+        let (mut sender, disconnect_receiver) = connect(self, &options, stream);
+        run_input::<Self::ServerMessageHandler, _>(
+            &mut self.input_handler(),
+            &mut sender,
+            disconnect_receiver,
+            options.slow_handler_warn,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Start the client and connect to the WebSocket server at the given
+    /// URL, e.g. `ws://localhost:1234`, see [`crate::Server::start_ws`].
+    /// Each WebSocket message carries a single message, so
+    /// [`Client::codec`], [`Client::length_field_length`] and
+    /// [`Client::chunk_size`] don't apply. Otherwise this behaves like
+    /// [`Client::start`].
+    ///
+    /// Only available with the `websocket` feature.
+    #[cfg(feature = "websocket")]
+    async fn start_ws(&self, url: &str) -> Result<()> {
+        let (ws, _response) = tokio_tungstenite::connect_async(url).await?;
+        if let tokio_tungstenite::MaybeTlsStream::Plain(stream) = ws.get_ref() {
+            connected(self, stream)?;
+        }
+        let (frames, frame_sink) = crate::websocket::split(ws);
+        let options = self.options();
+        let (mut sender, disconnect_receiver) = connect_frames(self, &options, frames, frame_sink);
+        run_input::<Self::ServerMessageHandler, _>(
+            &mut self.input_handler(),
+            &mut sender,
+            disconnect_receiver,
+            options.slow_handler_warn,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Connect to the given address, returning a [`ServerSink`] for sending
+    /// messages to the server from anywhere, along with a future that runs
+    /// the client. See [`Client::start_stream_with_sink`].
+    async fn start_with_sink(&self, addr: &str) -> Result<(ServerSink, BoxFuture<'static, ()>)>
+    where
+        Self::InputHandler: 'static,
+    {
+        let stream = TcpStream::connect(addr).await?;
+        connected(self, &stream)?;
+        Ok(self.start_stream_with_sink(stream))
+    }
+
+    /// Start the client with a given stream like
+    /// [`Client::start_with_stream`], but return a [`ServerSink`] for
+    /// sending messages to the server from outside the [`InputHandler`],
+    /// e.g. from GUI callbacks or timers, along with a future that runs the
+    /// client.
+    ///
+    /// Server messages are handled in the background as soon as this
+    /// returns, but input is only read while the returned future is polled.
+    /// The future completes once the client is disconnected, as
+    /// [`Client::start_with_stream`] would return.
+    fn start_stream_with_sink<S: Transport>(
+        &self,
+        stream: S,
+    ) -> (ServerSink, BoxFuture<'static, ()>)
+    where
+        Self::InputHandler: 'static,
+    {
+        let options = self.options();
+        let (sender, disconnect_receiver) = connect(self, &options, stream);
+        let sink = ServerSink::new(sender.clone());
+        let mut input_handler = self.input_handler();
+        let slow_handler_warn = options.slow_handler_warn;
+        let run = async move {
+            let mut sender = sender;
+            run_input::<Self::ServerMessageHandler, _>(
+                &mut input_handler,
+                &mut sender,
+                disconnect_receiver,
+                slow_handler_warn,
+            )
+            .await;
+        };
+        (sink, run.boxed())
+    }
+}
+
+/// Tell the client where it connected from and to.
+fn connected<C: Client + ?Sized>(client: &C, stream: &TcpStream) -> io::Result<()> {
+    client.on_connect(stream.local_addr()?, stream.peer_addr()?);
+    Ok(())
+}
+
+/// Spawn the writer and the task handling server messages for a new
+/// connection, returning the channel for sending to the server and a
+/// receiver firing once the client is disconnected.
+fn connect<C, S>(
+    client: &C,
+    options: &ClientOptions,
+    stream: S,
+) -> (ValueSender, oneshot::Receiver<Disconnected>)
+where
+    C: Client + ?Sized,
+    S: Transport,
+{
+    // Split the stream: reading happens in the receiver task, while all
+    // writes go through a single writer task
+    let (receiver_stream, sender_stream) = tokio::io::split(stream);
+    let new_codec = || client.codec().chunked(options.chunk_size);
+    connect_frames(
+        client,
+        options,
+        codec::framed_read(receiver_stream, new_codec(), options.read_buffer_capacity),
+        codec::framed_write(sender_stream, new_codec(), options.write_buffer_capacity),
+    )
+}
+
+/// Like [`connect`], given the stream of frames read from the server and
+/// the sink for frames written to it.
+fn connect_frames<C, R, W>(
+    client: &C,
+    options: &ClientOptions,
+    frames: R,
+    frame_sink: W,
+) -> (ValueSender, oneshot::Receiver<Disconnected>)
+where
+    C: Client + ?Sized,
+    R: Stream<Item = io::Result<BytesMut>> + Send + Unpin + 'static,
+    W: Sink<Bytes, Error = io::Error> + Send + Unpin + 'static,
+{
+    let frames = frames.and_then(|frame| future::ready(compression::decompress(frame)));
+    let mut receiver: MessageReceiver<_> =
+        tokio_serde::SymmetricallyFramed::new(frames, SymmetricalJson::<Value>::default());
+
+    let compression = SharedCompression::default();
+    let input_handler_sender = ValueSender::spawn(
+        frame_sink,
+        WriterOptions {
+            pretty: options.json_pretty,
+            keepalive: options.keepalive_interval,
+            compression: compression.clone(),
+            ..WriterOptions::default()
+        },
+    );
+    let mut message_handler_sender = input_handler_sender.clone();
+
+    let coalesced = options.coalesced;
+    let require_ack = options.require_ack;
+    let slow_handler_warn = options.slow_handler_warn;
+    let protocol_version = options.protocol_version;
+    let greeting = greeting(protocol_version, &options.compression);
+    let cipher = client.cipher();
+
+    // Fires when the message handler asks to disconnect, or the server
+    // closes the connection
+    let (disconnect_sender, disconnect_receiver) = oneshot::channel();
+
+    // Handle incoming messages from the server
+    tokio::spawn(async move {
+        for frame in greeting {
+            let _ = message_handler_sender.send_control_frame(frame).await;
+        }
+        // Whether the server's protocol version has been checked, or
+        // doesn't have to be
+        let mut version_checked = protocol_version == 0;
 
-        // Handle incoming messages from the server
-        tokio::spawn(async move {
-            while let Some(next) = receiver.next().await {
-                match next {
-                    Ok(msg) => {
-                        Self::ServerMessageHandler::handle_server_message(
-                            msg,
-                            &mut message_handler_sender,
-                        )
-                        .await;
-                    }
-                    Err(e) => Self::ServerMessageHandler::handle_bad_message(e.into()).await,
+        let disconnected = loop {
+            let Some(next) = receiver.next().await else {
+                C::ServerMessageHandler::on_server_close().await;
+                break Disconnected::ByServer;
+            };
+            // The server answered our offer, so compress from now on
+            let control = next.as_ref().ok().and_then(Control::parse);
+            if let Some(Control::Choice(chosen)) = control {
+                compression.set(chosen);
+                continue;
+            }
+            if ping_pong::<C::ServerMessageHandler>(control.as_ref(), &message_handler_sender).await
+            {
+                continue;
+            }
+            // Check the server's protocol version once it has declared one,
+            // or sent a message without declaring any
+            let declared = match control {
+                Some(Control::Version(declared)) => Some(declared),
+                _ => None,
+            };
+            if !version_checked && next.is_ok() {
+                version_checked = true;
+                if !accepts_version::<C::ServerMessageHandler>(declared, protocol_version).await {
+                    say_goodbye(&mut message_handler_sender, &mut receiver).await;
+                    break Disconnected::ByHandler;
                 }
             }
-        });
+            if declared.is_some() {
+                continue;
+            }
+            let (next, seq) = open(next, require_ack, cipher.as_deref());
+            let flow = handle::<C::ServerMessageHandler>(
+                next,
+                coalesced,
+                slow_handler_warn,
+                &mut message_handler_sender,
+            )
+            .await;
 
-        // Continuously read user input and send appropriate messages to the server
-        loop {
-            Self::InputHandler::next_input(&mut input_handler_sender).await;
+            if let Some(seq) = seq {
+                let _ = message_handler_sender
+                    .send_control_frame(Control::Ack(seq).to_value())
+                    .await;
+            }
+
+            if flow.is_break() {
+                say_goodbye(&mut message_handler_sender, &mut receiver).await;
+                break Disconnected::ByHandler;
+            }
+        };
+
+        let _ = message_handler_sender.close().await;
+        let _ = disconnect_sender.send(disconnected);
+    });
+
+    (input_handler_sender, disconnect_receiver)
+}
+
+/// Pass a message from the server, or each of a batch of coalesced ones, to
+/// the handler, reporting calls that block the executor.
+async fn handle<H>(
+    next: io::Result<Value>,
+    coalesced: bool,
+    slow_handler_warn: Option<Duration>,
+    sender: &mut ValueSender,
+) -> ControlFlow<()>
+where
+    H: MessageHandler + Send,
+    H::ServerMessage: DeserializeOwned + Send,
+{
+    match next {
+        // Split frames containing several coalesced messages
+        Ok(Value::Array(batch)) if coalesced => {
+            for value in batch {
+                let handling = dispatch::<H>(value, sender);
+                watchdog::watch(handling, slow_handler_warn, H::on_slow_handler).await?;
+            }
+            ControlFlow::Continue(())
+        }
+        Ok(value) => {
+            let handling = dispatch::<H>(value, sender);
+            watchdog::watch(handling, slow_handler_warn, H::on_slow_handler).await
+        }
+        Err(e) => {
+            let handling = H::handle_bad_message(e.into());
+            watchdog::watch(handling, slow_handler_warn, H::on_slow_handler).await;
+            ControlFlow::Continue(())
+        }
+    }
+}
+
+/// Answer a ping from the server, or report the answer to one of ours,
+/// returning whether `control` was either. Neither reaches the handler.
+async fn ping_pong<H: MessageHandler>(control: Option<&Control>, sender: &ValueSender) -> bool {
+    match control {
+        Some(Control::Ping) => {
+            let _ = sender.send_control(Control::Pong);
+            true
+        }
+        Some(Control::Pong) => {
+            H::on_pong().await;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Take a message from the server out of its escaping, if it's escaped, and
+/// out of its numbered envelope, if messages are acknowledged, and open its
+/// sealed parts, if there's a cipher, returning it along with its number.
+fn open(
+    next: io::Result<Value>,
+    require_ack: bool,
+    cipher: Option<&dyn Cipher>,
+) -> (io::Result<Value>, Option<u64>) {
+    let (next, seq) = match next.map(envelope::unescape) {
+        Ok(value) if require_ack => {
+            let (value, seq) = envelope::open_sequenced(value);
+            (Ok(value), seq)
+        }
+        next => (next, None),
+    };
+    let next = match (next, cipher) {
+        (Ok(mut value), Some(cipher)) => sealed::open_all(&mut value, cipher).map(|()| value),
+        (next, _) => next,
+    };
+    (next, seq)
+}
+
+/// Whether to stay connected to a server that declared `declared` as its
+/// protocol version, given the client's own `version`.
+async fn accepts_version<H: MessageHandler>(declared: Option<u32>, version: u32) -> bool {
+    declared == Some(version) || H::on_version_mismatch(declared).await.is_continue()
+}
+
+/// The frames a client sends first: its protocol version and its offer to
+/// compress, unless there's no version or nothing to offer.
+fn greeting(protocol_version: u32, offered: &[Compression]) -> Vec<Value> {
+    let mut greeting = Vec::new();
+    if protocol_version != 0 {
+        greeting.push(Control::Version(protocol_version).to_value());
+    }
+    if !offered.is_empty() {
+        greeting.push(Control::Offer(offered.to_vec()).to_value());
+    }
+    greeting
+}
+
+/// Say goodbye, then wait for the server to finish handling us leaving
+/// before returning. Closing makes the writer shut down our write half, so
+/// that servers that don't understand goodbyes still see us leave.
+async fn say_goodbye<S, E>(sender: &mut ValueSender, receiver: &mut S)
+where
+    S: Stream<Item = Result<Value, E>> + Unpin,
+{
+    let _ = sender.feed_control(Control::Goodbye.to_value()).await;
+    let _ = sender.close().await;
+    while let Some(next) = receiver.next().await {
+        if next.is_ok_and(|value| Control::parse(&value) == Some(Control::Goodbye)) {
+            break;
+        }
+    }
+}
+
+/// Why a connection to the server ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Disconnected {
+    /// The message handler asked to disconnect.
+    ByHandler,
+    /// The server closed the connection.
+    ByServer,
+}
+
+/// Continuously read user input and send appropriate messages to the
+/// server, until the client is disconnected, returning why. Input that
+/// blocks the executor is reported to `H`.
+async fn run_input<H: MessageHandler, I: InputHandler>(
+    input_handler: &mut I,
+    sender: &mut ValueSender,
+    mut disconnect_receiver: oneshot::Receiver<Disconnected>,
+    slow_handler_warn: Option<Duration>,
+) -> Disconnected {
+    loop {
+        let next_input = watchdog::watch(
+            input_handler.next_input(sender),
+            slow_handler_warn,
+            H::on_slow_handler,
+        );
+        tokio::select! {
+            // A dropped sender means the receiving task is gone, which is
+            // as good as the server closing the connection.
+            result = &mut disconnect_receiver => {
+                break result.unwrap_or(Disconnected::ByServer);
+            }
+            () = next_input => {}
+        }
+    }
+}
+
+/// Deserialize a message from the server and pass it to the handler.
+async fn dispatch<H>(value: Value, response_channel: &mut ValueSender) -> ControlFlow<()>
+where
+    H: MessageHandler + Send,
+    H::ServerMessage: DeserializeOwned + Send,
+{
+    match serde_json::from_value(value) {
+        Ok(msg) => H::on_server_message(msg, response_channel).await,
+        Err(e) => {
+            H::handle_bad_message(e.into()).await;
+            ControlFlow::Continue(())
         }
     }
 }
 
 /// Trait representing a handler for incoming server messages.
+// `async_trait` turns provided methods into functions returning boxed
+// futures, which `clippy::pedantic` flags as `must_use` candidates.
+#[allow(clippy::must_use_candidate)]
 #[async_trait]
 pub trait MessageHandler {
     /// Type representing messages received from the server. Should be
@@ -135,17 +764,73 @@ pub trait MessageHandler {
     type ServerMessage;
 
     /// Function to be called when a message is received from the server. A channel is provided
-    /// for sending responses back.
+    /// for sending responses back, e.g. with [`ValueSender::send_message`],
+    /// which serializes a typed response and fails instead of panicking if
+    /// it can't be serialized.
     async fn handle_server_message(msg: Self::ServerMessage, response_channel: &mut ValueSender);
 
+    /// Function to be called when a message is received from the server,
+    /// in place of [`Self::handle_server_message`], deciding whether to
+    /// stay connected. Return [`ControlFlow::Break`] to disconnect from the
+    /// server (e.g. after being kicked), which makes [`Client::start`]
+    /// return.
+    ///
+    /// Defaults to calling [`Self::handle_server_message`] and staying
+    /// connected.
+    async fn on_server_message(
+        msg: Self::ServerMessage,
+        response_channel: &mut ValueSender,
+    ) -> ControlFlow<()>
+    where
+        Self::ServerMessage: Send + 'async_trait,
+    {
+        Self::handle_server_message(msg, response_channel).await;
+        ControlFlow::Continue(())
+    }
+
     /// Function to be called when deserializing a message from the server fails. Does nothing by default.
     async fn handle_bad_message(_err: Error) {}
+
+    /// Function to be called when the server's protocol version, if it
+    /// declared one, doesn't match [`Client::protocol_version`], before any
+    /// of its messages are handled. Return [`ControlFlow::Continue`] to
+    /// carry on anyway, e.g. when the server is known to still understand
+    /// this client, or [`ControlFlow::Break`] to disconnect.
+    ///
+    /// Disconnects by default.
+    async fn on_version_mismatch(_server_version: Option<u32>) -> ControlFlow<()> {
+        ControlFlow::Break(())
+    }
+
+    /// Function to be called once when the server closes the connection,
+    /// after which [`Client::start`] returns, or
+    /// [`Client::start_reconnecting`] reconnects. Not called when
+    /// [`Self::handle_server_message`] disconnects. Does nothing by default.
+    async fn on_server_close() {}
+
+    /// Function to be called when the server answers a ping sent with
+    /// [`ValueSender::ping`], e.g. to measure the round trip. Does nothing
+    /// by default.
+    async fn on_pong() {}
+
+    /// Function to be called whenever a single poll of a handler call took
+    /// `elapsed`, longer than [`Client::slow_handler_warn`], meaning the
+    /// handler blocked the executor, e.g. to log a warning. Covers this
+    /// handler as well as [`InputHandler::next_input`]. Called while the
+    /// handler is being polled, so it should return quickly. Does nothing
+    /// by default.
+    fn on_slow_handler(_elapsed: Duration) {}
 }
 
 /// A trait for accepting user input.
+///
+/// [`Self::next_input`] is called in a loop for as long as the client is
+/// running, alongside the task handling server messages, so it should wait
+/// for input asynchronously rather than block, e.g. with
+/// [`LineInputHandler`].
 #[async_trait]
-pub trait InputHandler {
+pub trait InputHandler: Send {
     /// Get input from the client and optionally send a message to the server
     /// using the given channel.
-    async fn next_input(message_channel: &mut ValueSender);
+    async fn next_input(&mut self, message_channel: &mut ValueSender);
 }
@@ -0,0 +1,145 @@
+//! Tuning a client in one place.
+
+use std::time::Duration;
+
+use crate::compression::Compression;
+
+/// Settings for running a [`Client`](super::Client), returned by
+/// [`Client::options`](super::Client::options) and read once per
+/// connection.
+///
+/// Start from [`ClientOptions::default`], which matches the defaults of
+/// the individual [`Client`](super::Client) methods, and change what's
+/// needed, either through the builder methods or the fields:
+///
+/// ```
+/// # use std::time::Duration;
+/// # use scot::client::ClientOptions;
+/// let options = ClientOptions::default()
+///     .keepalive_interval(Duration::from_secs(30))
+///     .protocol_version(2);
+/// assert_eq!(options.protocol_version, 2);
+/// ```
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ClientOptions {
+    /// See [`Client::length_field_length`](super::Client::length_field_length).
+    pub length_field_length: usize,
+    /// See [`Client::json_pretty`](super::Client::json_pretty).
+    pub json_pretty: bool,
+    /// See [`Client::chunk_size`](super::Client::chunk_size).
+    pub chunk_size: Option<usize>,
+    /// See [`Client::read_buffer_capacity`](super::Client::read_buffer_capacity).
+    pub read_buffer_capacity: Option<usize>,
+    /// See [`Client::write_buffer_capacity`](super::Client::write_buffer_capacity).
+    pub write_buffer_capacity: Option<usize>,
+    /// See [`Client::require_ack`](super::Client::require_ack).
+    pub require_ack: bool,
+    /// See [`Client::keepalive_interval`](super::Client::keepalive_interval).
+    pub keepalive_interval: Option<Duration>,
+    /// See [`Client::coalesced`](super::Client::coalesced).
+    pub coalesced: bool,
+    /// See [`Client::compression`](super::Client::compression).
+    pub compression: Vec<Compression>,
+    /// See [`Client::slow_handler_warn`](super::Client::slow_handler_warn).
+    pub slow_handler_warn: Option<Duration>,
+    /// See [`Client::protocol_version`](super::Client::protocol_version).
+    pub protocol_version: u32,
+}
+
+impl Default for ClientOptions {
+    fn default() -> ClientOptions {
+        ClientOptions {
+            length_field_length: 4,
+            json_pretty: false,
+            chunk_size: None,
+            read_buffer_capacity: None,
+            write_buffer_capacity: None,
+            require_ack: false,
+            keepalive_interval: None,
+            coalesced: false,
+            compression: Vec::new(),
+            slow_handler_warn: None,
+            protocol_version: 0,
+        }
+    }
+}
+
+impl ClientOptions {
+    /// Set [`ClientOptions::length_field_length`].
+    #[must_use]
+    pub fn length_field_length(mut self, length: usize) -> ClientOptions {
+        self.length_field_length = length;
+        self
+    }
+
+    /// Set [`ClientOptions::json_pretty`].
+    #[must_use]
+    pub fn json_pretty(mut self, pretty: bool) -> ClientOptions {
+        self.json_pretty = pretty;
+        self
+    }
+
+    /// Set [`ClientOptions::chunk_size`].
+    #[must_use]
+    pub fn chunk_size(mut self, chunk_size: usize) -> ClientOptions {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Set [`ClientOptions::read_buffer_capacity`].
+    #[must_use]
+    pub fn read_buffer_capacity(mut self, capacity: usize) -> ClientOptions {
+        self.read_buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Set [`ClientOptions::write_buffer_capacity`].
+    #[must_use]
+    pub fn write_buffer_capacity(mut self, capacity: usize) -> ClientOptions {
+        self.write_buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Set [`ClientOptions::require_ack`].
+    #[must_use]
+    pub fn require_ack(mut self, require_ack: bool) -> ClientOptions {
+        self.require_ack = require_ack;
+        self
+    }
+
+    /// Set [`ClientOptions::keepalive_interval`].
+    #[must_use]
+    pub fn keepalive_interval(mut self, interval: Duration) -> ClientOptions {
+        self.keepalive_interval = Some(interval);
+        self
+    }
+
+    /// Set [`ClientOptions::coalesced`].
+    #[must_use]
+    pub fn coalesced(mut self, coalesced: bool) -> ClientOptions {
+        self.coalesced = coalesced;
+        self
+    }
+
+    /// Set [`ClientOptions::compression`].
+    #[must_use]
+    pub fn compression(mut self, compression: Vec<Compression>) -> ClientOptions {
+        self.compression = compression;
+        self
+    }
+
+    /// Set [`ClientOptions::slow_handler_warn`].
+    #[must_use]
+    pub fn slow_handler_warn(mut self, threshold: Duration) -> ClientOptions {
+        self.slow_handler_warn = Some(threshold);
+        self
+    }
+
+    /// Set [`ClientOptions::protocol_version`].
+    #[must_use]
+    pub fn protocol_version(mut self, version: u32) -> ClientOptions {
+        self.protocol_version = version;
+        self
+    }
+}
@@ -0,0 +1,158 @@
+//! Retrying the initial connection, e.g. while the server is still
+//! starting up, and reconnecting once it has gone away.
+
+use std::{io, time::Duration};
+
+use tokio::{net::TcpStream, time};
+
+use crate::rand::{self, SplitMix64};
+
+/// How [`Client::connect_retrying`](super::Client::connect_retrying) and
+/// [`Client::start_reconnecting`](super::Client::start_reconnecting) retry
+/// connecting: the delay before each retry starts at
+/// [`RetryPolicy::initial_delay`] and doubles every time, up to
+/// [`RetryPolicy::max_delay`], until [`RetryPolicy::max_attempts`]
+/// attempts have failed. With [`RetryPolicy::jitter`], the time actually
+/// waited is randomized, see [`Jitter`].
+///
+/// ```
+/// # use std::time::Duration;
+/// # use scot::client::RetryPolicy;
+/// let policy = RetryPolicy::default()
+///     .max_attempts(20)
+///     .initial_delay(Duration::from_millis(50));
+/// assert_eq!(policy.max_attempts, 20);
+/// ```
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct RetryPolicy {
+    /// How many times to try connecting, including the first attempt.
+    /// Defaults to 10.
+    pub max_attempts: u32,
+    /// How long to wait after the first failed attempt. Defaults to 100 ms.
+    pub initial_delay: Duration,
+    /// The longest to wait between attempts. Defaults to 5 s.
+    pub max_delay: Duration,
+    /// How to randomize the delays. Defaults to [`Jitter::None`].
+    pub jitter: Jitter,
+    /// The seed for randomizing the delays, so that they're the same every
+    /// time, e.g. in tests. Defaults to `None`, which picks a different
+    /// seed every time.
+    pub seed: Option<u64>,
+}
+
+/// How to randomize the delays between attempts to connect, so that
+/// clients that lost their connection at the same time, e.g. because the
+/// server restarted, spread out their attempts instead of all reconnecting
+/// at once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Jitter {
+    /// Wait exactly the delay.
+    #[default]
+    None,
+    /// Wait a random time between zero and the delay.
+    Full,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 10,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: Jitter::None,
+            seed: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Set [`RetryPolicy::max_attempts`].
+    #[must_use]
+    pub fn max_attempts(mut self, attempts: u32) -> RetryPolicy {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Set [`RetryPolicy::initial_delay`].
+    #[must_use]
+    pub fn initial_delay(mut self, delay: Duration) -> RetryPolicy {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Set [`RetryPolicy::max_delay`].
+    #[must_use]
+    pub fn max_delay(mut self, delay: Duration) -> RetryPolicy {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Set [`RetryPolicy::jitter`].
+    #[must_use]
+    pub fn jitter(mut self, jitter: Jitter) -> RetryPolicy {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set [`RetryPolicy::seed`].
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> RetryPolicy {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Returns how long to wait before each retry, in order, i.e. one delay
+    /// fewer than [`RetryPolicy::max_attempts`]. Randomized delays are the
+    /// same for every call with the same [`RetryPolicy::seed`].
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use scot::client::{Jitter, RetryPolicy};
+    /// let policy = RetryPolicy::default().max_attempts(4);
+    /// let delays: Vec<_> = policy.delays().collect();
+    /// assert_eq!(delays, [100, 200, 400].map(Duration::from_millis));
+    ///
+    /// let jittered = policy.jitter(Jitter::Full).seed(7);
+    /// assert!(jittered.delays().zip(&delays).all(|(jittered, delay)| jittered <= *delay));
+    /// ```
+    pub fn delays(&self) -> impl Iterator<Item = Duration> {
+        let mut rng = SplitMix64::new(self.seed.unwrap_or_else(rand::random_seed));
+        let (jitter, max_delay) = (self.jitter, self.max_delay);
+        let mut delay = self.initial_delay;
+        (1..self.max_attempts).map(move |_| {
+            let base = delay;
+            delay = (delay * 2).min(max_delay);
+            match jitter {
+                Jitter::None => base,
+                Jitter::Full => base.mul_f64(rng.next_f64()),
+            }
+        })
+    }
+}
+
+/// Connect to `addr`, retrying as `policy` says and calling `on_failure`
+/// with the number of every failed attempt, starting at 1, and its error.
+/// Fails with the last attempt's error once every attempt has failed.
+pub(crate) async fn connect(
+    addr: &str,
+    policy: &RetryPolicy,
+    mut on_failure: impl FnMut(u32, &io::Error),
+) -> io::Result<TcpStream> {
+    let mut delays = policy.delays();
+    let mut attempt = 1;
+    loop {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => {
+                on_failure(attempt, &e);
+                let Some(delay) = delays.next() else {
+                    return Err(e);
+                };
+                time::sleep(delay).await;
+            }
+        }
+        attempt += 1;
+    }
+}
@@ -0,0 +1,47 @@
+//! Sending messages to the server from outside the input loop.
+
+use std::io;
+
+use serde::Serialize;
+
+use crate::types::ValueSender;
+
+/// A handle for sending messages to the server from any task, e.g. from GUI
+/// callbacks or timers, returned by [`Client::start_with_sink`]. Cloning
+/// gives another handle to the same connection.
+///
+/// Messages go through the connection's single writer, in the order they're
+/// sent, interleaved with those sent by the [`InputHandler`] and
+/// [`MessageHandler`]. Once the client is disconnected, sending fails.
+///
+/// [`Client::start_with_sink`]: super::Client::start_with_sink
+/// [`InputHandler`]: super::InputHandler
+/// [`MessageHandler`]: super::MessageHandler
+#[derive(Clone, Debug)]
+pub struct ServerSink {
+    sender: ValueSender,
+}
+
+impl ServerSink {
+    pub(crate) fn new(sender: ValueSender) -> Self {
+        ServerSink { sender }
+    }
+
+    /// Serialize a message and send it to the server, see
+    /// [`ValueSender::send_message`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the message can't be serialized, or the client has been
+    /// disconnected.
+    pub async fn send<M: Serialize + ?Sized>(&self, message: &M) -> io::Result<()> {
+        self.sender.clone().send_message(message).await
+    }
+
+    /// Returns whether the client has been disconnected, after which
+    /// sending fails.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
@@ -0,0 +1,315 @@
+//! Framing codecs used to split the byte stream into individual messages.
+//!
+//! By default, every message is sent as a length-delimited frame. For
+//! interoperating with other protocols, a newline-delimited (NDJSON) codec
+//! is also provided, and any other codec producing whole frames can be used
+//! through [`FrameCodec::custom`]. The server and the client must use the
+//! same codec.
+//!
+//! # Chunking
+//!
+//! Messages larger than a codec's maximum frame length (8 MiB by default
+//! for [`FrameCodec::length_delimited`]) can't be sent as a single frame.
+//! With chunking enabled (see [`crate::Server::chunk_size`]), such messages
+//! are split into several frames, each starting with a small header, and
+//! put back together by the receiving end before being deserialized. A
+//! chunk's header is a zero byte followed by the message's ID, the index of
+//! the chunk and the total number of chunks, each as a 4-byte big-endian
+//! integer. As JSON never starts with a zero byte, chunks can't be mistaken
+//! for whole messages. The header isn't valid UTF-8 text, so chunking can't
+//! be used with [`FrameCodec::lines`].
+
+use std::{collections::HashMap, io};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::{
+    bytes::{Buf, BufMut, Bytes, BytesMut},
+    codec::{
+        Decoder, Encoder, FramedRead, FramedWrite, LengthDelimitedCodec, LinesCodec,
+        LinesCodecError,
+    },
+};
+
+/// A codec that splits a byte stream into frames and writes frames back out.
+///
+/// Any type implementing both [`Decoder`] and [`Encoder`] over raw frames
+/// automatically implements this trait, and can be used with
+/// [`FrameCodec::custom`].
+pub trait Codec:
+    Decoder<Item = BytesMut, Error = io::Error> + Encoder<Bytes, Error = io::Error> + Send
+{
+}
+
+impl<T> Codec for T where
+    T: Decoder<Item = BytesMut, Error = io::Error> + Encoder<Bytes, Error = io::Error> + Send
+{
+}
+
+/// The codec used for framing messages on the wire.
+///
+/// The default is [`FrameCodec::length_delimited`].
+pub struct FrameCodec {
+    inner: Inner,
+    chunking: Option<Chunking>,
+}
+
+/// Marks a frame as a chunk of a larger message.
+const CHUNK_MARKER: u8 = 0;
+/// The marker, then the message ID, chunk index and chunk count.
+const CHUNK_HEADER_LEN: usize = 1 + 3 * 4;
+
+/// State for splitting messages into chunks and putting them back together.
+struct Chunking {
+    chunk_size: usize,
+    next_id: u32,
+    // Messages whose chunks are still arriving, by ID
+    partial: HashMap<u32, BytesMut>,
+}
+
+enum Inner {
+    LengthDelimited(LengthDelimitedCodec),
+    Lines(LinesCodec),
+    Custom(Box<dyn Codec>),
+}
+
+impl FrameCodec {
+    /// Frames are prefixed with their length as a 4-byte big-endian integer.
+    pub fn length_delimited() -> FrameCodec {
+        LengthDelimitedCodec::new().into()
+    }
+
+    /// Frames are prefixed with their length as a big-endian integer of
+    /// `length_field_length` bytes. A short prefix saves space when all
+    /// messages are small, e.g. 2 bytes allow frames of up to 64 KiB, while
+    /// 8 bytes allow frames larger than 4 GiB.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `length_field_length` isn't between 1 and 8.
+    pub fn length_delimited_with(length_field_length: usize) -> FrameCodec {
+        LengthDelimitedCodec::builder()
+            .length_field_length(length_field_length)
+            .new_codec()
+            .into()
+    }
+
+    /// Frames are separated by newlines, i.e. newline-delimited JSON.
+    pub fn lines() -> FrameCodec {
+        FrameCodec {
+            inner: Inner::Lines(LinesCodec::new()),
+            chunking: None,
+        }
+    }
+
+    /// Use a custom codec for framing.
+    pub fn custom(codec: impl Codec + 'static) -> FrameCodec {
+        FrameCodec {
+            inner: Inner::Custom(Box::new(codec)),
+            chunking: None,
+        }
+    }
+
+    /// A fresh codec framing the same way, e.g. for another connection, or
+    /// `None` for a custom codec, which can't be copied.
+    pub(crate) fn fresh_copy(&self) -> Option<FrameCodec> {
+        let inner = match &self.inner {
+            Inner::LengthDelimited(codec) => Inner::LengthDelimited(codec.clone()),
+            Inner::Lines(codec) => Inner::Lines(codec.clone()),
+            Inner::Custom(_) => return None,
+        };
+        Some(FrameCodec {
+            inner,
+            chunking: None,
+        })
+    }
+
+    /// Split messages longer than `chunk_size` bytes into chunks, and put
+    /// chunks that are received back together. See the
+    /// [module documentation](self#chunking).
+    pub(crate) fn chunked(mut self, chunk_size: Option<usize>) -> FrameCodec {
+        self.chunking = chunk_size.map(|chunk_size| Chunking {
+            chunk_size: chunk_size.max(1),
+            next_id: 0,
+            partial: HashMap::new(),
+        });
+        self
+    }
+
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        match &mut self.inner {
+            Inner::LengthDelimited(codec) => codec.decode(src),
+            Inner::Lines(codec) => codec
+                .decode(src)
+                .map(|line| line.map(|line| line.as_bytes().into()))
+                .map_err(lines_error),
+            Inner::Custom(codec) => codec.decode(src),
+        }
+    }
+
+    fn decode_frame_eof(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        match &mut self.inner {
+            Inner::LengthDelimited(codec) => codec.decode_eof(src),
+            Inner::Lines(codec) => codec
+                .decode_eof(src)
+                .map(|line| line.map(|line| line.as_bytes().into()))
+                .map_err(lines_error),
+            Inner::Custom(codec) => codec.decode_eof(src),
+        }
+    }
+
+    fn encode_frame(&mut self, item: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+        match &mut self.inner {
+            Inner::LengthDelimited(codec) => codec.encode(item, dst),
+            Inner::Lines(codec) => {
+                let line = std::str::from_utf8(&item)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                codec.encode(line, dst).map_err(lines_error)
+            }
+            Inner::Custom(codec) => codec.encode(item, dst),
+        }
+    }
+
+    /// Handle a received frame, returning a whole message if there is one.
+    fn reassemble(&mut self, frame: BytesMut) -> io::Result<Option<BytesMut>> {
+        let Some(chunking) = &mut self.chunking else {
+            return Ok(Some(frame));
+        };
+        if frame.first() != Some(&CHUNK_MARKER) {
+            return Ok(Some(frame));
+        }
+        if frame.len() < CHUNK_HEADER_LEN {
+            return Err(bad_chunk("chunk header is too short"));
+        }
+
+        let mut header = &frame[1..CHUNK_HEADER_LEN];
+        let id = header.get_u32();
+        let index = header.get_u32();
+        let count = header.get_u32();
+        let payload = &frame[CHUNK_HEADER_LEN..];
+
+        // Chunks are sent in order, so any other order means the stream is
+        // broken
+        let message = if index == 0 {
+            chunking.partial.entry(id).or_default()
+        } else {
+            chunking
+                .partial
+                .get_mut(&id)
+                .ok_or_else(|| bad_chunk("chunk of an unknown message"))?
+        };
+        if index >= count {
+            return Err(bad_chunk("chunk index out of range"));
+        }
+        message.extend_from_slice(payload);
+
+        if index + 1 == count {
+            Ok(chunking.partial.remove(&id))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Default for FrameCodec {
+    fn default() -> FrameCodec {
+        FrameCodec::length_delimited()
+    }
+}
+
+impl From<LengthDelimitedCodec> for FrameCodec {
+    fn from(codec: LengthDelimitedCodec) -> FrameCodec {
+        FrameCodec {
+            inner: Inner::LengthDelimited(codec),
+            chunking: None,
+        }
+    }
+}
+
+impl Decoder for FrameCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        while let Some(frame) = self.decode_frame(src)? {
+            if let Some(message) = self.reassemble(frame)? {
+                return Ok(Some(message));
+            }
+        }
+        Ok(None)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        while let Some(frame) = self.decode_frame_eof(src)? {
+            if let Some(message) = self.reassemble(frame)? {
+                return Ok(Some(message));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Encoder<Bytes> for FrameCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+        let (chunk_size, id) = match &mut self.chunking {
+            Some(chunking) if item.len() > chunking.chunk_size => {
+                let id = chunking.next_id;
+                chunking.next_id = chunking.next_id.wrapping_add(1);
+                (chunking.chunk_size, id)
+            }
+            _ => return self.encode_frame(item, dst),
+        };
+
+        let count = u32::try_from(item.len().div_ceil(chunk_size))
+            .map_err(|_| bad_chunk("message has too many chunks"))?;
+        for (index, payload) in (0..count).zip(item.chunks(chunk_size)) {
+            let mut chunk = BytesMut::with_capacity(CHUNK_HEADER_LEN + payload.len());
+            chunk.put_u8(CHUNK_MARKER);
+            chunk.put_u32(id);
+            chunk.put_u32(index);
+            chunk.put_u32(count);
+            chunk.put_slice(payload);
+            self.encode_frame(chunk.freeze(), dst)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read frames from `io`, with a read buffer of the given initial
+/// capacity, or the default one.
+pub(crate) fn framed_read<R: AsyncRead>(
+    io: R,
+    codec: FrameCodec,
+    capacity: Option<usize>,
+) -> FramedRead<R, FrameCodec> {
+    match capacity {
+        Some(capacity) => FramedRead::with_capacity(io, codec, capacity),
+        None => FramedRead::new(io, codec),
+    }
+}
+
+/// Write frames to `io`, buffering up to the given number of bytes before
+/// writing, or the default number.
+pub(crate) fn framed_write<W: AsyncWrite>(
+    io: W,
+    codec: FrameCodec,
+    capacity: Option<usize>,
+) -> FramedWrite<W, FrameCodec> {
+    let mut writer = FramedWrite::new(io, codec);
+    if let Some(capacity) = capacity {
+        writer.set_backpressure_boundary(capacity);
+    }
+    writer
+}
+
+fn bad_chunk(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+fn lines_error(err: LinesCodecError) -> io::Error {
+    match err {
+        LinesCodecError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    }
+}
@@ -0,0 +1,166 @@
+//! Compressing messages, negotiated between the client and the server.
+//!
+//! Compression is negotiated per connection, so that clients and servers
+//! supporting different algorithms can still talk to each other, e.g.
+//! during a rolling upgrade. A client offering compression (see
+//! [`crate::Client::compression`]) starts by sending a control frame
+//! listing the algorithms it supports, in order of preference:
+//!
+//! ```json
+//! { "scot": "hello", "compression": ["zstd", "gzip"] }
+//! ```
+//!
+//! The server picks the first algorithm in its own list (see
+//! [`crate::Server::compression`]) that the client offered, or `"none"` if
+//! there's no overlap, and replies with its choice:
+//!
+//! ```json
+//! { "scot": "hello", "compression": "zstd" }
+//! ```
+//!
+//! From then on, both ends compress the messages they send with the chosen
+//! algorithm. Messages sent before the choice is known go out
+//! uncompressed, as do frames meant for the framework itself, such as the
+//! hello frames. Clients that don't offer anything never send the hello,
+//! and every message stays uncompressed.
+//!
+//! A compressed message starts with a byte identifying the algorithm (1 for
+//! gzip, 2 for zstd), followed by the compressed JSON. As JSON never starts
+//! with such a byte, compressed and uncompressed messages can be told
+//! apart, and either end accepts both at any time. Compression is applied
+//! to whole messages, before [chunking](crate::codec#chunking). Compressed
+//! messages aren't valid UTF-8 text, so compression can't be used with
+//! [`FrameCodec::lines`](crate::codec::FrameCodec::lines).
+//!
+//! Each algorithm is only available with the feature of the same name.
+
+use std::{io, sync::Arc};
+
+use tokio_util::bytes::BytesMut;
+
+use crate::sync::Mutex;
+
+/// An algorithm used to compress messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Messages are sent as-is.
+    #[default]
+    None,
+    /// Messages are compressed with gzip. Requires the `gzip` feature.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Messages are compressed with zstd. Requires the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+/// Marks a message compressed with gzip.
+#[cfg(feature = "gzip")]
+const GZIP_MARKER: u8 = 1;
+/// Marks a message compressed with zstd.
+#[cfg(feature = "zstd")]
+const ZSTD_MARKER: u8 = 2;
+
+impl Compression {
+    /// Every compression algorithm enabled in this build, in the order
+    /// they're preferred.
+    #[must_use]
+    pub fn supported() -> Vec<Compression> {
+        vec![
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+            #[cfg(feature = "gzip")]
+            Compression::Gzip,
+        ]
+    }
+
+    /// The name of the algorithm in the handshake.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// The algorithm with the given name, if it's enabled in this build.
+    pub(crate) fn from_name(name: &str) -> Option<Compression> {
+        std::iter::once(Compression::None)
+            .chain(Compression::supported())
+            .find(|compression| compression.name() == name)
+    }
+
+    /// Pick the first of `ours` that the other end offered, falling back to
+    /// no compression.
+    pub(crate) fn negotiate(ours: &[Compression], offered: &[Compression]) -> Compression {
+        ours.iter()
+            .copied()
+            .find(|compression| offered.contains(compression))
+            .unwrap_or_default()
+    }
+
+    /// Compress a serialized message, prefixing it with the marker for the
+    /// algorithm.
+    pub(crate) fn compress(self, json: Vec<u8>) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(json),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Write;
+
+                let mut encoder = flate2::write::GzEncoder::new(
+                    vec![GZIP_MARKER],
+                    flate2::Compression::default(),
+                );
+                encoder.write_all(&json)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                let mut compressed = vec![ZSTD_MARKER];
+                zstd::stream::copy_encode(&json[..], &mut compressed, 0)?;
+                Ok(compressed)
+            }
+        }
+    }
+}
+
+/// Decompress a received message if it's compressed, whatever the
+/// negotiated algorithm.
+pub(crate) fn decompress(frame: BytesMut) -> io::Result<BytesMut> {
+    match frame.first() {
+        #[cfg(feature = "gzip")]
+        Some(&GZIP_MARKER) => {
+            use std::io::Read;
+
+            let mut json = Vec::new();
+            flate2::read::GzDecoder::new(&frame[1..]).read_to_end(&mut json)?;
+            Ok(BytesMut::from(&json[..]))
+        }
+        #[cfg(feature = "zstd")]
+        Some(&ZSTD_MARKER) => {
+            let json = zstd::stream::decode_all(&frame[1..])?;
+            Ok(BytesMut::from(&json[..]))
+        }
+        _ => Ok(frame),
+    }
+}
+
+/// The algorithm a connection's writer compresses messages with, switched
+/// once it has been negotiated. Cloning gives another handle to the same
+/// setting.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SharedCompression(Arc<Mutex<Compression>>);
+
+impl SharedCompression {
+    pub(crate) fn get(&self) -> Compression {
+        *self.0.lock()
+    }
+
+    pub(crate) fn set(&self, compression: Compression) {
+        *self.0.lock() = compression;
+    }
+}
@@ -0,0 +1,518 @@
+//! Optional metadata attached to messages sent to the server.
+//!
+//! A client message can be sent as-is, or with extra information for the
+//! framework. The server strips it before the message reaches the
+//! [`MessageHandler`](crate::server::MessageHandler), so handlers only ever
+//! see the message itself, and message types don't need to make room for
+//! it.
+//!
+//! Metadata goes in a reserved `"__scot"` field, next to the message's own
+//! fields (see [`with_metadata`]):
+//!
+//! ```json
+//! { "__scot": { "deadline_ms": 1700000000000 }, "text": "..." }
+//! ```
+//!
+//! Messages that don't serialize to an object, such as unit enum variants,
+//! are carried inside the metadata instead:
+//!
+//! ```json
+//! { "__scot": { "deadline_ms": 1700000000000, "message": "Ping" } }
+//! ```
+//!
+//! where `deadline_ms` is the time, in milliseconds since the UNIX epoch,
+//! after which the server shouldn't bother handling the message, see
+//! [`Metadata`]. Client message types must not have a field named
+//! `"__scot"`, as it would be taken for metadata.
+//!
+//! A deadline can also be sent with an envelope, a JSON object with exactly
+//! two fields (see [`with_deadline`]):
+//!
+//! ```json
+//! { "deadline_ms": 1700000000000, "message": "..." }
+//! ```
+//!
+//! Client message types that serialize to an object with exactly these
+//! fields would be mistaken for an envelope, and must not be used.
+//!
+//! Similarly, objects with a single `"scot"` field are used for control
+//! frames exchanged by the framework itself, which never reach handlers:
+//!
+//! - `{ "scot": "keepalive" }` is sent by idle clients (see
+//!   [`crate::Client::keepalive_interval`]), and skipped by the server.
+//! - `{ "scot": "goodbye" }` is sent by a client that's leaving, after which
+//!   it sends nothing else. The server then stops reading, handles the
+//!   client leaving, and sends the same frame back as an acknowledgement
+//!   before closing the connection.
+//! - `{ "scot": "ping" }` can be sent by either end (see
+//!   [`ValueSender::ping`](crate::types::ValueSender::ping)) to check that
+//!   the other end is still there, and is answered with
+//!   `{ "scot": "pong" }`, so that liveness checks don't need messages of
+//!   their own in the application's message types.
+//!
+//! Messages of either end that would be mistaken for one of the frames
+//! meant for the framework described here are escaped by putting them
+//! inside metadata without any fields, and taken back out by the other end
+//! before they reach its handler:
+//!
+//! ```json
+//! { "__scot": { "message": { "scot": "ping" } } }
+//! ```
+//!
+//! so message types are free to look like control frames. Messages that
+//! look escaped already are escaped again.
+//!
+//! Clients offering compression start with a hello frame, which the server
+//! answers with one of its own, see [`crate::compression`].
+//!
+//! Ends that have a [protocol version](crate::Server::protocol_version)
+//! declare it before anything else, the client with the first frame it
+//! sends and the server with the first frame it sends once the client has
+//! joined:
+//!
+//! ```json
+//! { "scot": "version", "version": 2 }
+//! ```
+//!
+//! When delivery has to be acknowledged (see
+//! [`crate::Server::require_ack`]), every message sent by the server is
+//! wrapped in an envelope numbering it:
+//!
+//! ```json
+//! { "seq": 0, "message": "..." }
+//! ```
+//!
+//! and the client acknowledges it once it has been handled with
+//! `{ "scot": "ack", "seq": 0 }`, which also acknowledges every message
+//! numbered before it.
+//!
+//! With [ordered broadcasts](crate::Server::ordered_broadcasts), broadcasts
+//! sent through
+//! [`ServerMessageChannels::broadcast`](crate::types::ServerMessageChannels::broadcast)
+//! are passed between connections in an envelope recording which
+//! connection sent them. These never leave the server, and rather than
+//! JSON, they're a NUL byte and the connection's number in front of the
+//! serialized message, which can't be mistaken for a message sent straight
+//! on the broadcast channel, e.g. with
+//! [`TryBroadcast`](crate::types::TryBroadcast).
+//!
+//! Likewise, broadcasts sent with
+//! [`ServerMessageChannels::broadcast_priority`](crate::types::ServerMessageChannels::broadcast_priority)
+//! have a byte in front marking them as high priority, outside of any
+//! origin envelope, and broadcasts that arrived from another instance of
+//! the server (see [`crate::Server::cluster`]) have another one in front of
+//! that.
+
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::de::{Deserializer as _, IgnoredAny, MapAccess, Visitor};
+use serde_json::{Map, Value};
+use tokio_util::bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::compression::Compression;
+
+const METADATA: &str = "__scot";
+const DEADLINE: &str = "deadline_ms";
+const MESSAGE: &str = "message";
+const SEQ: &str = "seq";
+const CONTROL: &str = "scot";
+const ACK: &str = "ack";
+const HELLO: &str = "hello";
+const COMPRESSION: &str = "compression";
+const VERSION: &str = "version";
+
+/// Starts an origin envelope. JSON text never starts with a NUL byte, so
+/// this can't be mistaken for the start of a message.
+const ORIGIN_MARKER: u8 = 0;
+/// The marker followed by the connection number.
+const ORIGIN_LEN: usize = 1 + 8;
+
+/// Marks a high priority broadcast. Like the origin marker, JSON text never
+/// starts with it.
+const PRIORITY_MARKER: u8 = 1;
+
+/// Marks a broadcast that arrived from another instance of the server, so
+/// that it isn't published back.
+const REMOTE_MARKER: u8 = 2;
+
+/// Wrap a message in an envelope telling the server to skip it if it's
+/// still waiting to be handled at `deadline`. Expired messages are passed
+/// to [`MessageHandler::handle_expired`](crate::server::MessageHandler::handle_expired)
+/// instead.
+///
+/// ```
+/// # use std::time::{Duration, SystemTime};
+/// # use serde_json::json;
+/// let deadline = SystemTime::now() + Duration::from_secs(5);
+/// let value = scot::envelope::with_deadline(json!("Ping"), deadline);
+/// assert_eq!(value["message"], json!("Ping"));
+/// ```
+pub fn with_deadline(message: Value, deadline: SystemTime) -> Value {
+    let mut envelope = Map::new();
+    envelope.insert(DEADLINE.to_string(), Value::from(to_millis(deadline)));
+    envelope.insert(MESSAGE.to_string(), message);
+    Value::Object(envelope)
+}
+
+/// Information for the framework sent along with a client message, see
+/// [`with_metadata`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Metadata {
+    /// When the server should stop bothering to handle the message, see
+    /// [`with_deadline`].
+    pub deadline: Option<SystemTime>,
+}
+
+impl Metadata {
+    /// Set [`Metadata::deadline`].
+    #[must_use]
+    pub fn deadline(mut self, deadline: SystemTime) -> Metadata {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    fn to_fields(&self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        if let Some(deadline) = self.deadline {
+            fields.insert(DEADLINE.to_string(), Value::from(to_millis(deadline)));
+        }
+        fields
+    }
+
+    fn from_fields(fields: &Map<String, Value>) -> Metadata {
+        Metadata {
+            deadline: fields
+                .get(DEADLINE)
+                .and_then(Value::as_u64)
+                .map(from_millis),
+        }
+    }
+}
+
+/// Attach metadata to a message in the reserved `"__scot"` field, leaving
+/// the message's own fields where they are.
+///
+/// ```
+/// # use std::time::{Duration, SystemTime};
+/// # use serde_json::json;
+/// # use scot::envelope::{self, Metadata};
+/// let metadata = Metadata::default().deadline(SystemTime::now() + Duration::from_secs(5));
+/// let value = envelope::with_metadata(json!({ "text": "hi" }), &metadata);
+/// assert_eq!(value["text"], json!("hi"));
+/// assert!(value["__scot"]["deadline_ms"].is_u64());
+/// ```
+pub fn with_metadata(message: Value, metadata: &Metadata) -> Value {
+    let mut fields = metadata.to_fields();
+    let mut object = match message {
+        Value::Object(object) => object,
+        message => {
+            fields.insert(MESSAGE.to_string(), message);
+            Map::new()
+        }
+    };
+    object.insert(METADATA.to_string(), Value::Object(fields));
+    Value::Object(object)
+}
+
+/// Take a message out of its envelope, or strip its metadata, returning it
+/// along with the metadata.
+pub(crate) fn open(value: Value) -> (Value, Metadata) {
+    match value {
+        Value::Object(mut object) if object.contains_key(METADATA) => {
+            let mut fields = match object.remove(METADATA) {
+                Some(Value::Object(fields)) => fields,
+                _ => Map::new(),
+            };
+            let metadata = Metadata::from_fields(&fields);
+            let message = fields.remove(MESSAGE).unwrap_or(Value::Object(object));
+            (message, metadata)
+        }
+        Value::Object(mut envelope)
+            if envelope.len() == 2
+                && envelope.get(DEADLINE).is_some_and(Value::is_u64)
+                && envelope.contains_key(MESSAGE) =>
+        {
+            let metadata = Metadata::from_fields(&envelope);
+            let message = envelope.remove(MESSAGE).unwrap_or_default();
+            (message, metadata)
+        }
+        value => (value, Metadata::default()),
+    }
+}
+
+/// Escape a message that would be mistaken for a frame meant for the
+/// framework, or for an escaped message, leaving any other message as is.
+pub(crate) fn escape(message: Value) -> Value {
+    if !is_control(&message) && !is_escaped(&message) {
+        return message;
+    }
+    let mut fields = Map::new();
+    fields.insert(MESSAGE.to_string(), message);
+    let mut object = Map::new();
+    object.insert(METADATA.to_string(), Value::Object(fields));
+    Value::Object(object)
+}
+
+/// Take an escaped message back out, see [`escape`], leaving any other
+/// message as is.
+pub(crate) fn unescape(mut value: Value) -> Value {
+    if !is_escaped(&value) {
+        return value;
+    }
+    value[METADATA][MESSAGE].take()
+}
+
+fn is_escaped(value: &Value) -> bool {
+    let Some(object) = value.as_object().filter(|object| object.len() == 1) else {
+        return false;
+    };
+    object
+        .get(METADATA)
+        .and_then(Value::as_object)
+        .is_some_and(|fields| fields.len() == 1 && fields.contains_key(MESSAGE))
+}
+
+/// Returns whether a serialized message may have to be escaped, see
+/// [`escape`], so that only those are parsed to find out. Only objects with
+/// fields reserved for the framework may have to be.
+pub(crate) fn may_need_escape(json: &[u8]) -> bool {
+    let start = json.iter().find(|byte| !byte.is_ascii_whitespace());
+    start == Some(&b'{') && !is_plain(json)
+}
+
+/// Returns whether a frame from a client can only be a message, so that it
+/// can be deserialized straight into the message type, without decoding
+/// it into a [`Value`] to look inside first. That's the case for JSON
+/// objects without any of the fields reserved for control frames and
+/// metadata. Anything else, including frames that aren't valid JSON, has
+/// to be looked inside.
+pub(crate) fn is_plain(frame: &[u8]) -> bool {
+    let mut deserializer = serde_json::Deserializer::from_slice(frame);
+    let reserved = deserializer.deserialize_map(HasReserved);
+    matches!(reserved, Ok(false)) && deserializer.end().is_ok()
+}
+
+/// Looks for reserved fields in a JSON object, skipping over the values
+/// without decoding them.
+struct HasReserved;
+
+impl<'de> Visitor<'de> for HasReserved {
+    type Value = bool;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<bool, A::Error> {
+        let mut reserved = false;
+        // Keys with escapes can't be borrowed, which fails, so that the
+        // frame is looked inside instead
+        while let Some(key) = map.next_key::<&'de str>()? {
+            reserved |= [CONTROL, METADATA, DEADLINE].contains(&key);
+            map.next_value::<IgnoredAny>()?;
+        }
+        Ok(reserved)
+    }
+}
+
+/// Milliseconds since the UNIX epoch, as sent on the wire.
+fn to_millis(time: SystemTime) -> u64 {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    u64::try_from(millis).unwrap_or(u64::MAX)
+}
+
+fn from_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+/// Wrap a message sent by the server in an envelope numbering it.
+pub(crate) fn sequenced(seq: u64, message: Value) -> Value {
+    let mut envelope = Map::new();
+    envelope.insert(SEQ.to_string(), Value::from(seq));
+    envelope.insert(MESSAGE.to_string(), message);
+    Value::Object(envelope)
+}
+
+/// Take a message out of its numbered envelope, if it's in one, returning
+/// it along with its number.
+pub(crate) fn open_sequenced(value: Value) -> (Value, Option<u64>) {
+    match value {
+        Value::Object(mut envelope)
+            if envelope.len() == 2
+                && envelope.get(SEQ).is_some_and(Value::is_u64)
+                && envelope.contains_key(MESSAGE) =>
+        {
+            let seq = envelope[SEQ].as_u64();
+            let message = envelope.remove(MESSAGE).unwrap_or_default();
+            (message, seq)
+        }
+        value => (value, None),
+    }
+}
+
+/// Wrap a serialized broadcast in an envelope recording the connection it
+/// was sent from.
+pub(crate) fn with_origin(connection: u64, message: &[u8]) -> Bytes {
+    let mut envelope = BytesMut::with_capacity(ORIGIN_LEN + message.len());
+    envelope.put_u8(ORIGIN_MARKER);
+    envelope.put_u64(connection);
+    envelope.extend_from_slice(message);
+    envelope.freeze()
+}
+
+/// Take a serialized broadcast out of its origin envelope, if it's in one,
+/// returning it along with the connection it was sent from.
+pub(crate) fn open_origin(mut message: Bytes) -> (Bytes, Option<u64>) {
+    if message.len() < ORIGIN_LEN || message[0] != ORIGIN_MARKER {
+        return (message, None);
+    }
+    let mut header = message.split_to(ORIGIN_LEN);
+    header.advance(1);
+    (message, Some(header.get_u64()))
+}
+
+/// Mark a serialized broadcast as high priority.
+pub(crate) fn with_priority(message: &[u8]) -> Bytes {
+    with_marker(PRIORITY_MARKER, message)
+}
+
+/// Take the high priority marker off a serialized broadcast, returning it
+/// along with whether it was marked.
+pub(crate) fn open_priority(message: Bytes) -> (Bytes, bool) {
+    open_marker(PRIORITY_MARKER, message)
+}
+
+/// Mark a serialized broadcast as having arrived from another instance.
+pub(crate) fn with_remote(message: &[u8]) -> Bytes {
+    with_marker(REMOTE_MARKER, message)
+}
+
+/// Take the remote marker off a serialized broadcast, returning it along
+/// with whether it was marked.
+pub(crate) fn open_remote(message: Bytes) -> (Bytes, bool) {
+    open_marker(REMOTE_MARKER, message)
+}
+
+fn with_marker(marker: u8, message: &[u8]) -> Bytes {
+    let mut envelope = BytesMut::with_capacity(1 + message.len());
+    envelope.put_u8(marker);
+    envelope.extend_from_slice(message);
+    envelope.freeze()
+}
+
+fn open_marker(marker: u8, mut message: Bytes) -> (Bytes, bool) {
+    if message.first() != Some(&marker) {
+        return (message, false);
+    }
+    message.advance(1);
+    (message, true)
+}
+
+/// Returns whether a frame is meant for the framework, see [`Control`].
+pub(crate) fn is_control(value: &Value) -> bool {
+    Control::parse(value).is_some()
+}
+
+/// A control frame, handled by the framework rather than by handlers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Control {
+    /// Sent by an idle client to keep its connection alive.
+    Keepalive,
+    /// Sent by a client that's leaving, and sent back by the server once
+    /// it's done handling the client leaving.
+    Goodbye,
+    /// Sent by either end to check that the other one is still there.
+    Ping,
+    /// Sent back in answer to a ping.
+    Pong,
+    /// Sent by a client to acknowledge every message numbered up to and
+    /// including this one.
+    Ack(u64),
+    /// Sent by a client to offer the compression algorithms it supports.
+    /// Algorithms that aren't enabled are left out.
+    Offer(Vec<Compression>),
+    /// Sent back by the server with the algorithm chosen from an offer.
+    Choice(Compression),
+    /// Sent by either end to declare the protocol version it speaks.
+    Version(u32),
+}
+
+impl Control {
+    /// The control frames without any fields besides their name.
+    const SIGNALS: [Control; 4] = [
+        Control::Keepalive,
+        Control::Goodbye,
+        Control::Ping,
+        Control::Pong,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Control::Keepalive => "keepalive",
+            Control::Goodbye => "goodbye",
+            Control::Ping => "ping",
+            Control::Pong => "pong",
+            Control::Ack(_) => ACK,
+            Control::Offer(_) | Control::Choice(_) => HELLO,
+            Control::Version(_) => VERSION,
+        }
+    }
+
+    /// The frame to send.
+    pub(crate) fn to_value(&self) -> Value {
+        let mut control = Map::new();
+        control.insert(CONTROL.to_string(), Value::from(self.name()));
+        match self {
+            Control::Ack(seq) => {
+                control.insert(SEQ.to_string(), Value::from(*seq));
+            }
+            Control::Offer(offered) => {
+                let names = offered.iter().map(|compression| compression.name());
+                control.insert(COMPRESSION.to_string(), names.collect());
+            }
+            Control::Choice(chosen) => {
+                control.insert(COMPRESSION.to_string(), Value::from(chosen.name()));
+            }
+            Control::Version(version) => {
+                control.insert(VERSION.to_string(), Value::from(*version));
+            }
+            _ => {}
+        }
+        Value::Object(control)
+    }
+
+    /// Returns which control frame a message is, if it is one.
+    pub(crate) fn parse(value: &Value) -> Option<Control> {
+        let object = value.as_object()?;
+        let name = object.get(CONTROL)?.as_str()?;
+        match (name, object.len()) {
+            (ACK, 2) => Some(Control::Ack(object.get(SEQ)?.as_u64()?)),
+            (HELLO, 2) => match object.get(COMPRESSION)? {
+                Value::Array(names) => {
+                    let offered = names
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .filter_map(Compression::from_name);
+                    Some(Control::Offer(offered.collect()))
+                }
+                Value::String(name) => Compression::from_name(name).map(Control::Choice),
+                _ => None,
+            },
+            (VERSION, 2) => {
+                let version = object.get(VERSION)?.as_u64()?;
+                u32::try_from(version).ok().map(Control::Version)
+            }
+            (name, 1) => Control::SIGNALS
+                .into_iter()
+                .find(|control| control.name() == name),
+            _ => None,
+        }
+    }
+}
@@ -4,8 +4,19 @@
 #[warn(clippy::pedantic)]
 #[warn(missing_docs)]
 pub mod client;
+pub mod codec;
+pub mod compression;
+pub mod envelope;
+mod rand;
+pub mod sealed;
 pub mod server;
+mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod types;
+mod watchdog;
+#[cfg(feature = "websocket")]
+mod websocket;
 
 pub use client::Client;
 pub use server::Server;
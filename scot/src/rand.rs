@@ -0,0 +1,49 @@
+//! Pseudorandom numbers that are good enough for spreading things out and
+//! reproducible from a seed, but not for anything that has to be
+//! unpredictable.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// A small, fast pseudorandom number generator.
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> SplitMix64 {
+        SplitMix64(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(GAMMA);
+        mix(self.0)
+    }
+
+    /// Returns a number between 0 (inclusive) and 1 (exclusive).
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        // Random mantissa bits with the exponent of 1 give a number in [1, 2)
+        f64::from_bits(0x3ff0_0000_0000_0000 | (self.next_u64() >> 12)) - 1.0
+    }
+}
+
+/// Returns the `n`th number a generator seeded with `seed` would, starting
+/// from 1, without going through the ones before it.
+#[cfg_attr(not(feature = "uuid"), allow(dead_code))]
+pub(crate) fn nth(seed: u64, n: u64) -> u64 {
+    mix(seed.wrapping_add(n.wrapping_mul(GAMMA)))
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A seed that differs between calls and between runs, taken from the keys
+/// the standard library picks for hash maps.
+pub(crate) fn random_seed() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
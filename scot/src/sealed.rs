@@ -0,0 +1,143 @@
+//! Encrypting message bodies end to end, so that the server can relay
+//! them without being able to read them.
+//!
+//! A client seals the private part of a message with [`Sealed::seal`],
+//! using a [`Cipher`] with a key shared by the clients but not the server.
+//! The rest of the message, such as who it's for, stays readable, so the
+//! server can route it as usual, e.g. with
+//! [`Recipients`](crate::server::Recipients), while passing the
+//! [`Sealed`] part on untouched:
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! # use scot::sealed::Sealed;
+//! /// What the server sees of a direct message.
+//! #[derive(Serialize, Deserialize)]
+//! struct Direct {
+//!     to: usize,
+//!     body: Sealed,
+//! }
+//! ```
+//!
+//! Clients with a [`Client::cipher`](crate::Client::cipher) have every
+//! sealed part of the messages they receive opened before the messages
+//! are deserialized, so their own message types can hold the plain body
+//! where the server's hold a [`Sealed`].
+//!
+//! Sealed parts are JSON objects with a single `"__sealed"` field, holding
+//! the hex encoded ciphertext:
+//!
+//! ```json
+//! { "to": 2, "body": { "__sealed": "8e0f5a..." } }
+//! ```
+//!
+//! This is independent of encrypting the connection itself, which protects
+//! messages on the way to the server, but not from it.
+
+use std::{fmt::Write as _, io};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+const SEALED: &str = "__sealed";
+
+/// Encrypts and decrypts sealed message bodies, see [`Sealed`]. Scot only
+/// passes the bytes through, and leaves choosing an algorithm and
+/// agreeing on keys to the implementation.
+pub trait Cipher: Send + Sync {
+    /// Encrypt a serialized message body.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the body can't be encrypted, e.g. because there's no key.
+    fn encrypt(&self, plaintext: &[u8]) -> io::Result<Vec<u8>>;
+
+    /// Decrypt a message body encrypted with [`Cipher::encrypt`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the body can't be decrypted, e.g. because it was encrypted
+    /// with another key or tampered with.
+    fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// An encrypted message body, which only those with the [`Cipher`] it was
+/// sealed with can read. See the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sealed {
+    #[serde(rename = "__sealed")]
+    ciphertext: String,
+}
+
+impl Sealed {
+    /// Serialize a message body and encrypt it with `cipher`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the body can't be serialized, with kind
+    /// [`io::ErrorKind::InvalidData`], or can't be encrypted.
+    pub fn seal<M: Serialize + ?Sized>(body: &M, cipher: &dyn Cipher) -> io::Result<Sealed> {
+        let plaintext = serde_json::to_vec(body)?;
+        let ciphertext = cipher.encrypt(&plaintext)?;
+        Ok(Sealed {
+            ciphertext: to_hex(&ciphertext),
+        })
+    }
+
+    /// Decrypt the body with `cipher` and deserialize it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the body can't be decrypted, or doesn't deserialize to `M`,
+    /// with kind [`io::ErrorKind::InvalidData`].
+    pub fn open<M: DeserializeOwned>(&self, cipher: &dyn Cipher) -> io::Result<M> {
+        let ciphertext = from_hex(&self.ciphertext)?;
+        let plaintext = cipher.decrypt(&ciphertext)?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+}
+
+/// Replace every sealed part of `value` with its decrypted body.
+pub(crate) fn open_all(value: &mut Value, cipher: &dyn Cipher) -> io::Result<()> {
+    match value {
+        Value::Object(object) if object.len() == 1 && object.contains_key(SEALED) => {
+            let sealed = Sealed::deserialize(&*value)?;
+            *value = sealed.open(cipher)?;
+        }
+        Value::Object(object) => {
+            for field in object.values_mut() {
+                open_all(field, cipher)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                open_all(item, cipher)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+fn from_hex(hex: &str) -> io::Result<Vec<u8>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "sealed body isn't valid hex");
+    if !hex.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
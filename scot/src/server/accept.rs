@@ -0,0 +1,146 @@
+//! Accepting connections, over raw TCP or WebSockets.
+//!
+//! Either way, connections are accepted from [`TcpListener`]s and then
+//! opened into the frames read from them and the sink for frames written
+//! to them, so that the rest of the server doesn't have to tell them apart.
+
+use std::{io, net::SocketAddr, pin::Pin};
+
+use anyhow::Result;
+use futures::{
+    future::{self, BoxFuture},
+    stream::BoxStream,
+    Sink,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::bytes::{Bytes, BytesMut};
+
+use super::{framed, PauseHandle, Server, ServerOptions};
+use crate::codec::FrameCodec;
+
+/// The frames read from a connection.
+pub(crate) type Frames = BoxStream<'static, io::Result<BytesMut>>;
+
+/// The sink for frames written to a connection.
+pub(crate) type FrameSink = Pin<Box<dyn Sink<Bytes, Error = io::Error> + Send>>;
+
+/// Opening an accepted connection, which may take a while, e.g. for a
+/// handshake, and so runs alongside accepting others.
+pub(crate) type Opening = BoxFuture<'static, Result<(Frames, FrameSink)>>;
+
+/// Where a server gets its connections from.
+pub(crate) trait Acceptor {
+    /// The listeners to accept connections from.
+    fn listeners(&self) -> &[TcpListener];
+
+    /// Open a connection accepted from one of the listeners.
+    fn open<S: Server + ?Sized>(
+        &self,
+        server: &S,
+        stream: TcpStream,
+        options: &ServerOptions,
+    ) -> Opening;
+}
+
+/// Accepts raw TCP connections, split into frames by the server's codec.
+pub(crate) struct TcpAcceptor<'a> {
+    listeners: &'a [TcpListener],
+    /// Read once when the server starts, and copied for every connection,
+    /// unless it's a custom one that can't be.
+    codec: FrameCodec,
+}
+
+impl<'a> TcpAcceptor<'a> {
+    pub(crate) fn new<S: Server + ?Sized>(server: &S, listeners: &'a [TcpListener]) -> Self {
+        TcpAcceptor {
+            listeners,
+            codec: server.codec(),
+        }
+    }
+
+    /// A fresh copy of the codec, or a new one from [`Server::codec`] if it
+    /// can't be copied.
+    fn copy_codec<S: Server + ?Sized>(&self, server: &S) -> FrameCodec {
+        self.codec.fresh_copy().unwrap_or_else(|| server.codec())
+    }
+}
+
+impl Acceptor for TcpAcceptor<'_> {
+    fn listeners(&self) -> &[TcpListener] {
+        self.listeners
+    }
+
+    fn open<S: Server + ?Sized>(
+        &self,
+        server: &S,
+        stream: TcpStream,
+        options: &ServerOptions,
+    ) -> Opening {
+        let opened = stream.set_nodelay(options.nodelay).map(|()| {
+            let codecs = (self.copy_codec(server), self.copy_codec(server));
+            let (frames, frame_sink) = framed(stream, codecs, options);
+            (
+                Box::pin(frames) as Frames,
+                Box::pin(frame_sink) as FrameSink,
+            )
+        });
+        Box::pin(future::ready(opened.map_err(Into::into)))
+    }
+}
+
+/// Accepts WebSocket connections, each message carrying a single frame.
+#[cfg(feature = "websocket")]
+pub(crate) struct WsAcceptor<'a> {
+    pub(crate) listeners: &'a [TcpListener],
+}
+
+#[cfg(feature = "websocket")]
+impl Acceptor for WsAcceptor<'_> {
+    fn listeners(&self) -> &[TcpListener] {
+        self.listeners
+    }
+
+    fn open<S: Server + ?Sized>(
+        &self,
+        _server: &S,
+        stream: TcpStream,
+        options: &ServerOptions,
+    ) -> Opening {
+        let nodelay = options.nodelay;
+        Box::pin(async move {
+            stream.set_nodelay(nodelay)?;
+            let ws = tokio_tungstenite::accept_async(stream).await?;
+            let (frames, frame_sink) = crate::websocket::split(ws);
+            Ok((
+                Box::pin(frames) as Frames,
+                Box::pin(frame_sink) as FrameSink,
+            ))
+        })
+    }
+}
+
+/// Accept a connection from any of `listeners`, holding off while `pause`
+/// is paused. Fails with the address of the listener that failed.
+pub(crate) async fn accept(
+    listeners: &[TcpListener],
+    pause: &PauseHandle,
+) -> Result<(TcpStream, SocketAddr), (io::Error, SocketAddr)> {
+    loop {
+        pause.until(false).await;
+        let accepts = listeners.iter().map(|listener| Box::pin(listener.accept()));
+        tokio::select! {
+            () = pause.until(true) => {}
+            (result, index, _remaining) = future::select_all(accepts) => {
+                return result.map_err(|e| (e, local_addr(&listeners[index])));
+            }
+        }
+    }
+}
+
+/// The address `listener` is bound to, or the unspecified address if even
+/// that can't be told.
+fn local_addr(listener: &TcpListener) -> SocketAddr {
+    listener
+        .local_addr()
+        .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0)))
+}
@@ -0,0 +1,95 @@
+//! Keeping messages around until clients acknowledge them, see
+//! [`Server::require_ack`](crate::Server::require_ack).
+//!
+//! Every client ID has an outbox holding the messages written to it that
+//! haven't been acknowledged yet. Outboxes outlive connections, so that a
+//! client reconnecting with the same ID gets whatever it missed.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use serde_json::Value;
+
+use crate::{envelope, sync::Mutex};
+
+/// Messages written to a client that it hasn't acknowledged yet.
+#[derive(Debug, Default)]
+pub(crate) struct Outbox {
+    next_seq: u64,
+    pending: VecDeque<(u64, Value)>,
+}
+
+/// An outbox shared by a client's connections and their writers.
+pub(crate) type SharedOutbox = Arc<Mutex<Outbox>>;
+
+impl Outbox {
+    /// Number a message and keep it until it's acknowledged, returning the
+    /// envelope to write.
+    pub(crate) fn push(&mut self, message: Value) -> Value {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let sequenced = envelope::sequenced(seq, message.clone());
+        self.pending.push_back((seq, message));
+        sequenced
+    }
+
+    /// Drop every message up to and including `seq`.
+    pub(crate) fn acknowledge(&mut self, seq: u64) {
+        while self
+            .pending
+            .front()
+            .is_some_and(|(pending, _)| *pending <= seq)
+        {
+            self.pending.pop_front();
+        }
+    }
+
+    /// Envelopes for every message that hasn't been acknowledged yet, to
+    /// write again.
+    pub(crate) fn unacknowledged(&self) -> Vec<Value> {
+        self.pending
+            .iter()
+            .map(|(seq, message)| envelope::sequenced(*seq, message.clone()))
+            .collect()
+    }
+}
+
+/// The outboxes of every client, shared by all connections of a server.
+#[derive(Debug)]
+pub(crate) struct Outboxes<T> {
+    outboxes: Arc<Mutex<Vec<(T, SharedOutbox)>>>,
+}
+
+impl<T> Clone for Outboxes<T> {
+    fn clone(&self) -> Self {
+        Outboxes {
+            outboxes: self.outboxes.clone(),
+        }
+    }
+}
+
+impl<T> Default for Outboxes<T> {
+    fn default() -> Self {
+        Outboxes {
+            outboxes: Arc::default(),
+        }
+    }
+}
+
+impl<T: PartialEq> Outboxes<T> {
+    /// Get the outbox of the client with the given ID, creating it if it
+    /// doesn't exist yet.
+    pub(crate) fn open(&self, id: T) -> SharedOutbox {
+        let mut outboxes = self.outboxes.lock();
+        // Outboxes nobody is using anymore are only worth keeping if there's
+        // something left in them
+        outboxes.retain(|(_, outbox)| {
+            Arc::strong_count(outbox) > 1 || !outbox.lock().pending.is_empty()
+        });
+        if let Some((_, outbox)) = outboxes.iter().find(|(x, _)| *x == id) {
+            return outbox.clone();
+        }
+        let outbox = SharedOutbox::default();
+        outboxes.push((id, outbox.clone()));
+        outbox
+    }
+}
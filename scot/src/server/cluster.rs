@@ -0,0 +1,169 @@
+//! Fanning broadcasts out to every instance of a server running behind a
+//! load balancer, through a message bus such as Redis pub/sub or NATS.
+
+use std::io;
+
+use anyhow::{anyhow, Error};
+use async_trait::async_trait;
+use futures::{
+    future,
+    stream::{self, BoxStream, FusedStream},
+    StreamExt,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+use tokio_util::bytes::Bytes;
+
+use super::Recipients;
+use crate::{envelope, rand, types::BroadcastSender};
+
+/// A message bus connecting the instances of a server, attached with
+/// [`Server::cluster`](crate::Server::cluster).
+///
+/// Every instance subscribes when it starts accepting connections, then
+/// publishes every broadcast sent on it, whether through
+/// [`ServerMessageChannels`](crate::types::ServerMessageChannels) or
+/// straight on the broadcast channel. Broadcasts published by other
+/// instances are delivered to local clients as if they had been sent
+/// locally, with the same [`Recipients`], so client IDs have to mean the
+/// same on every instance for anything but [`Recipients::Everyone`] and
+/// [`Recipients::Tagged`] to be useful across instances.
+///
+/// Broadcasts to a [`Recipients::RecipientSet`] reach the other instances
+/// as [`Recipients::SharedRecipients`], with the same IDs.
+///
+/// Broadcasts to [`Recipients::Matching`] can't be serialized, so they only
+/// reach local clients, and publishing them fails with an error passed to
+/// [`Server::handle_cluster_err`](crate::Server::handle_cluster_err).
+///
+/// Scot only hands the backend opaque payloads, and skips its own when
+/// they come back, so the backend only has to pass every payload published
+/// by any instance on to every subscribed one.
+#[async_trait]
+pub trait ClusterBackend: Send + Sync {
+    /// Publish a payload to every instance, including this one.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the payload can't be handed to the bus. It's then lost for
+    /// the other instances, but local clients still receive the broadcast.
+    async fn publish(&self, payload: Bytes) -> io::Result<()>;
+
+    /// Subscribe to the payloads published by every instance. Called once,
+    /// when the server starts.
+    ///
+    /// # Errors
+    ///
+    /// Fails if subscribing fails, in which case the instance still
+    /// publishes, but receives nothing from the others.
+    async fn subscribe(&self) -> io::Result<BoxStream<'static, io::Result<Bytes>>>;
+}
+
+/// A broadcast as published to the other instances.
+#[derive(Serialize, Deserialize)]
+struct Published<T> {
+    /// The instance that published it, so it can skip its own.
+    instance: u64,
+    recipients: Recipients<T>,
+    #[serde(default)]
+    priority: bool,
+    message: Value,
+}
+
+/// Publish every broadcast sent on `sender` through `backend`, and send
+/// the ones published by other instances on `sender`, passing failures to
+/// `on_err`. Never returns.
+pub(crate) async fn bridge<T>(
+    backend: &dyn ClusterBackend,
+    sender: &BroadcastSender<T>,
+    on_err: impl Fn(Error),
+) where
+    T: Clone + Serialize + DeserializeOwned,
+{
+    let instance = rand::random_seed();
+    let mut local = sender.subscribe();
+    let mut remote = match backend.subscribe().await {
+        Ok(remote) => remote.fuse(),
+        Err(e) => {
+            on_err(e.into());
+            stream::empty().boxed().fuse()
+        }
+    };
+
+    loop {
+        tokio::select! {
+            result = local.recv() => match result {
+                Ok((json, recipients)) => {
+                    let (json, from_remote) = envelope::open_remote(json);
+                    if from_remote {
+                        continue;
+                    }
+                    let result = match publish(instance, json, recipients) {
+                        Ok(payload) => backend.publish(payload).await.map_err(Error::from),
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = result {
+                        on_err(e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    on_err(anyhow!("{missed} broadcasts weren't published to the cluster"));
+                }
+                // Never happens, as `sender` is borrowed for as long as this
+                // runs
+                Err(broadcast::error::RecvError::Closed) => future::pending().await,
+            },
+
+            result = remote.next(), if !remote.is_terminated() => {
+                let Some(result) = result else {
+                    on_err(anyhow!("cluster subscription ended"));
+                    continue;
+                };
+                let result = result
+                    .map_err(Error::from)
+                    .and_then(|payload| Ok(serde_json::from_slice::<Published<T>>(&payload)?));
+                match result {
+                    Ok(published) if published.instance == instance => {}
+                    Ok(published) => {
+                        let json = serde_json::to_vec(&published.message)
+                            .expect("serializing a JSON value can't fail");
+                        let json = if published.priority {
+                            envelope::with_priority(&json)
+                        } else {
+                            Bytes::from(json)
+                        };
+                        // Nobody may be connected to this instance
+                        let _ = sender.send((envelope::with_remote(&json), published.recipients));
+                    }
+                    Err(e) => on_err(e),
+                }
+            }
+        }
+    }
+}
+
+/// Serialize a broadcast sent on this instance to be published.
+fn publish<T: Clone + Serialize>(
+    instance: u64,
+    json: Bytes,
+    recipients: Recipients<T>,
+) -> Result<Bytes, Error> {
+    let (json, priority) = envelope::open_priority(json);
+    let (json, _origin) = envelope::open_origin(json);
+    // Sets can't be deserialized, as looking IDs up in them needs more of
+    // `T` than other instances can count on, so they're sent as lists
+    let recipients = match recipients {
+        Recipients::RecipientSet(set) => {
+            Recipients::shared(set.iter().cloned().collect::<Vec<_>>())
+        }
+        recipients => recipients,
+    };
+    let published = Published {
+        instance,
+        recipients,
+        priority,
+        message: serde_json::from_slice(&json)?,
+    };
+    Ok(Bytes::from(serde_json::to_vec(&published)?))
+}
@@ -0,0 +1,240 @@
+//! Keeping track of which clients are currently connected.
+//!
+//! The server registers every client once it has joined, and removes it
+//! once it has left. Handlers can query the registry through
+//! [`ServerMessageChannels::connections`](crate::types::ServerMessageChannels::connections),
+//! e.g. to skip composing a message for a client that is already gone.
+//!
+//! The registry also holds the channel feeding each client's connection, so
+//! a message meant for a single client can go straight to it, instead of
+//! going through the broadcast channel and being skipped by every other
+//! connection. Sending this way also tells whether anyone received the
+//! message, which a broadcast can't.
+//!
+//! Shutting the server down goes through the registry as well, as it's
+//! shared by every connection: it signals each connection to stop, and
+//! keeps track of the tasks serving them so that they can be waited for.
+
+use std::{collections::HashSet, io, sync::Arc};
+
+use futures::{
+    future::{self, BoxFuture},
+    Future, SinkExt,
+};
+use serde::Serialize;
+use tokio_util::{
+    sync::CancellationToken,
+    task::{task_tracker::TrackedFuture, TaskTracker},
+};
+
+use super::{
+    ack::{Outboxes, SharedOutbox},
+    ConnectionId, ConnectionStats, Recipients,
+};
+use crate::{sync::Mutex, types::ValueSender};
+
+/// The set of currently connected clients, shared by all connections of a
+/// server. Cloning gives another handle to the same set.
+///
+/// Type parameter is the type used for client IDs.
+#[derive(Debug)]
+pub struct Connections<T> {
+    clients: Arc<Mutex<Vec<(T, ConnectionId, ValueSender)>>>,
+    // Kept with the registry as they're shared the same way, but outlive
+    // connections
+    outboxes: Outboxes<T>,
+    // Cancelled when the server shuts down, ending every connection
+    shutdown: CancellationToken,
+    // Every task serving a connection, to wait for when shutting down
+    tasks: TaskTracker,
+    // Bytes read and written by every connection so far
+    stats: ConnectionStats,
+}
+
+impl<T> Clone for Connections<T> {
+    fn clone(&self) -> Self {
+        Connections {
+            clients: self.clients.clone(),
+            outboxes: self.outboxes.clone(),
+            shutdown: self.shutdown.clone(),
+            tasks: self.tasks.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+impl<T> Default for Connections<T> {
+    fn default() -> Self {
+        Connections {
+            clients: Arc::default(),
+            outboxes: Outboxes::default(),
+            shutdown: CancellationToken::new(),
+            tasks: TaskTracker::new(),
+            stats: ConnectionStats::default(),
+        }
+    }
+}
+
+impl<T: PartialEq> Connections<T> {
+    /// Returns whether the client with the given ID is connected.
+    pub fn is_connected(&self, id: &T) -> bool {
+        self.clients.lock().iter().any(|(x, _, _)| x == id)
+    }
+
+    /// Returns the number of connected clients.
+    pub fn count(&self) -> usize {
+        self.clients.lock().len()
+    }
+
+    /// Returns the IDs of the connected clients, in the order they joined,
+    /// e.g. for listing everyone or disconnecting them one by one.
+    ///
+    /// This is a snapshot: clients may join or leave while, or right
+    /// after, it's taken, so the IDs may already be stale by the time
+    /// they're used, and sending to one of them can still fail with
+    /// [`io::ErrorKind::NotConnected`]. A client that reconnects with the
+    /// same ID before its previous connection is gone shows up twice.
+    pub fn ids(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.clients
+            .lock()
+            .iter()
+            .map(|(id, _, _)| id.clone())
+            .collect()
+    }
+
+    /// Serialize a message and send it to the client with the given ID,
+    /// without going through the broadcast channel. The message is queued
+    /// for the client like a response from its own handler would be, see
+    /// [`ValueSender::send_message`].
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotConnected`] if no
+    /// client with that ID is connected.
+    pub async fn send_to<M: Serialize + ?Sized>(&self, id: &T, message: &M) -> io::Result<()> {
+        let value = serde_json::to_value(message).map_err(io::Error::from)?;
+        let sender = self
+            .clients
+            .lock()
+            .iter()
+            .rev()
+            .find(|(x, _, _)| x == id)
+            .map(|(_, _, sender)| sender.clone());
+        match sender {
+            Some(mut sender) => sender.send(value).await,
+            None => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    /// Serialize a message and send it to every connected client among
+    /// `recipients`, without going through the broadcast channel, returning
+    /// how many connections it was queued for, like
+    /// [`Connections::send_to`]. Unlike a broadcast, which connections skip
+    /// without anyone finding out, this tells whether anyone is going to
+    /// receive the message, e.g. zero once every recipient has left. A
+    /// client connected more than once receives it on every connection.
+    ///
+    /// The count is of connections that queued the message for their
+    /// writer, not of clients that have read it: a client may still leave
+    /// before its queue has been written out. The message is sent to every
+    /// connection at once, and this returns once it's queued everywhere, so
+    /// a client whose queue is full holds up returning, but not the other
+    /// clients, see [`crate::Server::write_timeout`].
+    ///
+    /// Messages sent this way aren't ordered with respect to broadcasts.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] for
+    /// [`Recipients::Tagged`], as tags are only known to each connection's
+    /// own task, and of kind [`io::ErrorKind::InvalidData`] if the message
+    /// can't be serialized.
+    pub async fn send_to_recipients<M: Serialize + ?Sized>(
+        &self,
+        recipients: &Recipients<T>,
+        message: &M,
+    ) -> io::Result<usize> {
+        if let Recipients::Tagged { .. } = recipients {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "tagged recipients can only be broadcast to",
+            ));
+        }
+        let value = serde_json::to_value(message).map_err(io::Error::from)?;
+        let no_tags = HashSet::new();
+        let senders: Vec<ValueSender> = self
+            .clients
+            .lock()
+            .iter()
+            .filter(|(id, _, _)| recipients.contains(id, &no_tags))
+            .map(|(_, _, sender)| sender.clone())
+            .collect();
+        // Sent to every connection at once, so a client whose queue is full
+        // doesn't hold up the ones after it
+        let sends = senders.into_iter().map(|mut sender| {
+            let value = value.clone();
+            async move { sender.send(value).await.is_ok() }
+        });
+        // The client may have left since
+        let queued = future::join_all(sends).await;
+        Ok(queued.into_iter().filter(|&queued| queued).count())
+    }
+
+    pub(crate) fn insert(&self, id: T, connection: ConnectionId, sender: ValueSender) {
+        self.clients.lock().push((id, connection, sender));
+    }
+
+    /// Forget the client on the given connection, returning whether no
+    /// clients are left connected. Other connections with the same client
+    /// ID stay registered.
+    pub(crate) fn remove(&self, connection: ConnectionId) -> bool {
+        let mut clients = self.clients.lock();
+        clients.retain(|(_, x, _)| *x != connection);
+        clients.is_empty()
+    }
+
+    /// The outbox of messages the client with the given ID hasn't
+    /// acknowledged yet.
+    pub(crate) fn outbox(&self, id: T) -> SharedOutbox {
+        self.outboxes.open(id)
+    }
+}
+
+impl<T> Connections<T> {
+    /// Returns the number of bytes read and written by every connection
+    /// the server has had, including the ones that are gone. The counters
+    /// are shared, so the returned stats keep counting.
+    pub fn stats(&self) -> ConnectionStats {
+        self.stats.clone()
+    }
+
+    /// Wrap a task serving a connection, so that shutting down waits for it.
+    pub(crate) fn track(&self, task: BoxFuture<'static, ()>) -> BoxFuture<'static, ()> {
+        Box::pin(self.tasks.track_future(task))
+    }
+
+    /// Wrap a connection that's still being set up, before it has any tasks
+    /// of its own, so that shutting down waits for it as well.
+    pub(crate) fn track_setup<F: Future>(&self, setup: F) -> TrackedFuture<F> {
+        self.tasks.track_future(setup)
+    }
+
+    /// The token cancelled once the server starts shutting down.
+    pub(crate) fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Wait until every connection has finished by itself. No more tasks
+    /// can be tracked afterwards.
+    pub(crate) async fn drained(&self) {
+        self.tasks.close();
+        self.tasks.wait().await;
+    }
+
+    /// Tell every connection to stop, then wait until all of their tasks
+    /// have finished.
+    pub(crate) async fn shut_down(&self) {
+        self.shutdown.cancel();
+        self.tasks.close();
+        self.tasks.wait().await;
+    }
+}
@@ -0,0 +1,163 @@
+//! Allocating IDs for clients that join, and numbering connections.
+//!
+//! Generating a new ID is usually the bulk of what [`State::on_join`] needs
+//! to do. An [`IdAllocator`] takes care of that part, so it can either be
+//! called from a custom `on_join`, or, for the simplest servers that don't
+//! need to keep track of their clients, be used as the [`State`] directly.
+//!
+//! For tests that check which client got what, [`SequentialIdAllocator`]
+//! gives the same IDs on every run, and so does a [`SeededUuidAllocator`]
+//! for servers using UUIDs, with the `uuid` feature.
+//!
+//! Connections are told apart by a [`ConnectionId`] of their own, which
+//! doesn't depend on the client ID.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use serde::Serialize;
+
+use super::State;
+
+/// A source of unique client IDs.
+pub trait IdAllocator {
+    /// The type of IDs being generated.
+    type ClientID;
+
+    /// Generate a new ID, different from all IDs previously returned.
+    fn next_id(&self) -> Self::ClientID;
+}
+
+/// Allocates monotonically increasing [`usize`] IDs, starting from 0.
+///
+/// Clones share the same counter, so this can be handed out by
+/// [`crate::Server::get_state`] and used as the server [`State`] directly:
+///
+/// ```
+/// # use scot::server::{IdAllocator, SequentialIdAllocator, State};
+/// let ids = SequentialIdAllocator::new();
+/// let mut state = ids.clone();
+/// assert_eq!(state.on_join(), 0);
+/// assert_eq!(ids.next_id(), 1);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SequentialIdAllocator {
+    next: Arc<AtomicUsize>,
+}
+
+impl SequentialIdAllocator {
+    /// Create an allocator whose first ID is 0.
+    pub fn new() -> SequentialIdAllocator {
+        SequentialIdAllocator::default()
+    }
+}
+
+impl IdAllocator for SequentialIdAllocator {
+    type ClientID = usize;
+
+    fn next_id(&self) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+impl State for SequentialIdAllocator {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id()
+    }
+}
+
+/// Allocates UUIDs that are the same on every run with the same seed, e.g.
+/// for tests of servers that normally give clients random UUIDs with
+/// `Uuid::new_v4`. The UUIDs look like random (version 4) ones, but are
+/// predictable, so they shouldn't be used outside of tests.
+///
+/// Clones share the same sequence, so like [`SequentialIdAllocator`], this
+/// can be used as the server [`State`] directly.
+///
+/// Only available with the `uuid` feature.
+///
+/// ```
+/// # use scot::server::{IdAllocator, SeededUuidAllocator};
+/// let first = SeededUuidAllocator::new(7);
+/// let second = SeededUuidAllocator::new(7);
+/// assert_eq!(first.next_id(), second.next_id());
+/// assert_ne!(first.next_id(), first.next_id());
+/// ```
+#[cfg(feature = "uuid")]
+#[derive(Clone, Debug)]
+pub struct SeededUuidAllocator {
+    seed: u64,
+    next: Arc<AtomicU64>,
+}
+
+#[cfg(feature = "uuid")]
+impl SeededUuidAllocator {
+    /// Create an allocator whose sequence is determined by `seed`.
+    pub fn new(seed: u64) -> SeededUuidAllocator {
+        SeededUuidAllocator {
+            seed,
+            next: Arc::default(),
+        }
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl IdAllocator for SeededUuidAllocator {
+    type ClientID = uuid::Uuid;
+
+    fn next_id(&self) -> uuid::Uuid {
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        let high = crate::rand::nth(self.seed, 2 * n + 1);
+        let low = crate::rand::nth(self.seed, 2 * n + 2);
+        let bytes = (u128::from(high) << 64 | u128::from(low)).to_be_bytes();
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl State for SeededUuidAllocator {
+    type ClientID = uuid::Uuid;
+
+    fn on_join(&mut self) -> uuid::Uuid {
+        self.next_id()
+    }
+}
+
+/// Numbers connections in the order they're accepted.
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(0);
+
+/// Identifies a single connection, e.g. in logs, see
+/// [`ServerMessageChannels::connection_id`](crate::types::ServerMessageChannels::connection_id).
+/// Assigned when the connection is accepted, before the client joins.
+///
+/// Unlike client IDs, which come from [`State::on_join`] and may be shared
+/// by several connections, e.g. the same user on two devices, or reused
+/// once a client reconnects, every connection accepted by the process gets
+/// a different one. Later connections get greater IDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct ConnectionId(u64);
+
+impl ConnectionId {
+    /// The ID for a newly accepted connection.
+    pub(crate) fn next() -> ConnectionId {
+        ConnectionId(NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Returns the ID as a number.
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
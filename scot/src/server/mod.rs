@@ -2,32 +2,100 @@
 //!
 //! Creating a server consists of the following steps:
 //! - Defining an API consisting of a server message type and a client message type
-//! - Defining a type to use for client IDs
+//! - Defining a type to use for client IDs, or using a provided
+//!   [`IdAllocator`]
 //! - Defining a [`State`] type
 //! - Defining a [`MessageHandler`]
 //! - Defining a [`Server`] struct
 //! - Starting the server
+//!
+//! # Connections
+//!
+//! Every accepted connection is served by two tasks. The connection task
+//! reads messages from the client and passes them to the
+//! [`MessageHandler`], and in between, forwards broadcasts meant for the
+//! client. The writer task is the only thing that ever writes to the
+//! socket: responses sent by the handler and forwarded broadcasts are both
+//! queued on the connection's `response_sender`, and the writer writes them
+//! out in that order. Since length-delimited (or newline-delimited) frames
+//! would be corrupted by concurrent writers, having exactly one writer per
+//! connection is required, and the socket is never handed out to user code.
+//! To send from elsewhere, clone the [`ValueSender`]
+//! instead.
 
+mod accept;
+mod ack;
+mod cluster;
+mod connections;
+mod id;
+mod noop;
+mod observer;
+mod options;
+mod pause;
+mod runner;
+mod running;
 mod state;
+mod stats;
 
 pub mod recipients;
 
-pub use recipients::Recipients;
-pub use state::State;
+use accept::{Acceptor, Opening, TcpAcceptor};
+pub(crate) use ack::SharedOutbox;
+pub use cluster::ClusterBackend;
+pub use connections::Connections;
+#[cfg(feature = "uuid")]
+pub use id::SeededUuidAllocator;
+pub use id::{ConnectionId, IdAllocator, SequentialIdAllocator};
+pub use noop::NoOpHandler;
+pub use observer::ConnectionObserver;
+pub use options::ServerOptions;
+pub use pause::PauseHandle;
+pub use recipients::{RecipientFilter, RecipientSet, Recipients};
+pub use runner::ServerRunner;
+pub use running::RunningServer;
+pub use state::{RejectReason, State};
+pub use stats::ConnectionStats;
+
+use std::{
+    any::Any,
+    collections::HashSet,
+    io, mem,
+    net::SocketAddr,
+    ops::ControlFlow,
+    panic::AssertUnwindSafe,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-use anyhow::{Error, Result};
+use anyhow::{anyhow, Error, Result};
 use async_trait::async_trait;
-use futures::prelude::*;
+use futures::{future::BoxFuture, prelude::*};
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use tokio::{
-    net::{TcpListener, TcpStream},
+    io::{ReadHalf, WriteHalf},
+    net::{TcpListener, TcpSocket},
     sync::broadcast,
+    time::{self, Instant},
+};
+use tokio_util::{
+    bytes::{BufMut, Bytes, BytesMut},
+    sync::CancellationToken,
+};
+
+use crate::{
+    codec::{self, FrameCodec},
+    compression::{self, Compression, SharedCompression},
+    envelope::{self, Control, Metadata},
+    sync::Mutex,
+    types::*,
+    watchdog,
 };
-use tokio_serde::formats::SymmetricalJson;
-use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
 
-use crate::types::*;
+/// The default number of broadcasts that can be waiting for the slowest
+/// connection before it starts missing them, rounded up to the next power of
+/// two by the channel (i.e. 16). See [`ServerOptions::broadcast_capacity`].
+pub const BROADCAST_CAPACITY: usize = 10;
 
 /// Trait representing a server object.
 ///
@@ -101,9 +169,21 @@ pub trait Server: 'static {
     type ClientID: 'static + Clone + Serialize + DeserializeOwned + PartialEq + Send + Sync;
     /// The messages to be received from the client. Should be defined in your server API.
     /// Will often be an enum.
+    ///
+    /// For peers that both send and receive the same messages, this can be
+    /// the same type as the client's [`crate::Client::ServerMessage`].
+    ///
+    /// Messages that serialize to a JSON object are deserialized straight
+    /// from the frame they arrive in, without building a [`Value`] first,
+    /// unless they carry [metadata](crate::envelope). Messages that only
+    /// implement `Deserialize<'de>` for a borrowed `'de` aren't supported:
+    /// handlers are given the owned message, which outlives the frame, so
+    /// fields that would borrow from it, such as `&str`, have to be owned
+    /// types, and `Cow<'_, str>` fields always end up owned.
     type ClientMessage: 'static + Serialize + DeserializeOwned + Unpin + Send;
     /// A type that implements [`MessageHandler`] for the given client message
-    /// and ID types.
+    /// and ID types. Servers that ignore what their clients send can use a
+    /// [`NoOpHandler`].
     type ClientMessageHandler: MessageHandler<
             ClientMessage = Self::ClientMessage,
             ClientID = Self::ClientID,
@@ -113,121 +193,1163 @@ pub trait Server: 'static {
     /// Get a copy of the [`State`].
     fn get_state(&self) -> Self::State;
 
-    /// Start the server on the given address.
-    async fn start(&self, addr: &str) -> Result<()> {
+    /// Get a copy of the [`State`] for a new connection, for states that
+    /// need asynchronous setup (e.g. opening a database connection).
+    ///
+    /// Called once per accepted connection, by the default
+    /// [`Server::get_state_for`]. The same instance is used for
+    /// [`State::on_join`] and is then handed to the connection's message
+    /// loop, so any changes made by `on_join` are visible to the
+    /// [`MessageHandler`]. Connections that may run several handler calls
+    /// at once get one more copy for each, see [`Server::max_in_flight`].
+    /// Connections are set up alongside accepting others, so a slow setup
+    /// only holds up its own client.
+    ///
+    /// Defaults to calling [`Server::get_state`].
+    async fn get_state_async(&self) -> Self::State {
+        self.get_state()
+    }
+
+    /// Get a copy of the [`State`] for a new connection from `addr`, for
+    /// states that depend on the connection, e.g. to record the peer from
+    /// the start instead of looking it up in [`State::on_join`].
+    ///
+    /// Called in place of [`Server::get_state_async`], as often as it
+    /// would be, and may be asynchronous like it.
+    ///
+    /// Defaults to calling [`Server::get_state_async`], and so
+    /// [`Server::get_state`].
+    async fn get_state_for(&self, _addr: SocketAddr) -> Self::State {
+        self.get_state_async().await
+    }
+
+    /// Get every setting for running the server at once, read when the
+    /// server starts. Overriding this is an alternative to overriding the
+    /// individual methods, such as [`Server::write_timeout`], one by one.
+    ///
+    /// Defaults to collecting the individual methods into a
+    /// [`ServerOptions`], so they're ignored when this is overridden.
+    fn options(&self) -> ServerOptions {
+        ServerOptions {
+            length_field_length: self.length_field_length(),
+            json_pretty: self.json_pretty(),
+            chunk_size: self.chunk_size(),
+            read_buffer_capacity: self.read_buffer_capacity(),
+            write_buffer_capacity: self.write_buffer_capacity(),
+            require_ack: self.require_ack(),
+            ordered_broadcasts: self.ordered_broadcasts(),
+            coalesce_window: self.coalesce_window(),
+            write_timeout: self.write_timeout(),
+            max_queued_messages: self.max_queued_messages(),
+            max_in_flight: self.max_in_flight(),
+            slow_handler_warn: self.slow_handler_warn(),
+            shutdown_timeout: self.shutdown_timeout(),
+            drain_timeout: self.drain_timeout(),
+            protocol_version: self.protocol_version(),
+            compression: self.compression(),
+            ..ServerOptions::default()
+        }
+    }
+
+    /// Get the codec used for framing messages. Clients must use the same
+    /// codec as the server.
+    ///
+    /// Called when the server starts, and copied for every connection. A
+    /// [custom](FrameCodec::custom) codec can't be copied, so it's asked
+    /// for again twice per connection, once for each direction.
+    ///
+    /// Defaults to length-delimited frames, using
+    /// [`ServerOptions::length_field_length`] and
+    /// [`ServerOptions::max_frame_length`] from [`Server::options`].
+    fn codec(&self) -> FrameCodec {
+        self.options().length_delimited_codec()
+    }
+
+    /// The size, in bytes, of the length prefix of each frame sent with the
+    /// default [`Server::codec`]. Clients must use the same size, through
+    /// [`crate::Client::length_field_length`]: with mismatched sizes, frames
+    /// are split in the wrong places and messages fail to deserialize.
+    /// Ignored when [`Server::codec`] is overridden.
+    ///
+    /// Defaults to 4, and must be between 1 and 8.
+    fn length_field_length(&self) -> usize {
+        4
+    }
+
+    /// Whether to pretty-print the JSON sent to clients, which can be
+    /// useful for debugging. Pretty-printed messages span several lines, so
+    /// this can't be used with [`FrameCodec::lines`]. Broadcasts sent
+    /// through [`ServerMessageChannels`] are pretty-printed too, but ones
+    /// sent straight on the broadcast channel, e.g. in
+    /// [`Server::after_join`], are always compact.
+    ///
+    /// Defaults to `false`, which sends compact JSON.
+    fn json_pretty(&self) -> bool {
+        false
+    }
+
+    /// The largest message, in bytes, to send as a single frame. Longer
+    /// messages are split into chunks of at most this many bytes, and
+    /// chunked messages from clients are put back together, see
+    /// [`crate::codec`]. Clients must use the same setting, through
+    /// [`crate::Client::chunk_size`]. Can't be used with
+    /// [`FrameCodec::lines`].
+    ///
+    /// Defaults to `None`, which never splits messages.
+    fn chunk_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// How many bytes to read from a client's socket at a time, i.e. the
+    /// initial capacity of the read buffer. Bigger buffers mean fewer reads
+    /// when clients send a lot of data, at the cost of memory per
+    /// connection.
+    ///
+    /// Defaults to `None`, which uses the default of
+    /// [`FramedRead`](tokio_util::codec::FramedRead) (8 KiB).
+    fn read_buffer_capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// How many bytes of outgoing frames to buffer before writing them to a
+    /// client's socket, see
+    /// [`FramedWrite::set_backpressure_boundary`](tokio_util::codec::FramedWrite::set_backpressure_boundary).
+    /// Bigger buffers mean fewer writes when sending a lot of data, at the
+    /// cost of memory per connection.
+    ///
+    /// Defaults to `None`, which uses the default of
+    /// [`FramedWrite`](tokio_util::codec::FramedWrite) (8 KiB).
+    fn write_buffer_capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether clients must acknowledge every message sent to them, for
+    /// at-least-once delivery. Unacknowledged messages (see
+    /// [`crate::envelope`]) are resent first when a client reconnects with
+    /// the same ID from [`State::on_join`], so may arrive twice, and are
+    /// kept for as long as the server runs. Clients must match it with
+    /// [`crate::Client::require_ack`].
+    ///
+    /// Defaults to `false`.
+    fn require_ack(&self) -> bool {
+        false
+    }
+
+    /// Bind a [`TcpListener`] to the given address, returning it along with
+    /// the address it was actually bound to.
+    ///
+    /// This is useful when binding to port 0 to let the OS pick a free port,
+    /// e.g. in tests. Pass the listener to [`Server::start_with_listener`]
+    /// once the address has been recorded.
+    ///
+    /// ```no_run
+    /// # use scot::Server;
+    /// # async fn run<S: Server + Sync>(server: S) -> anyhow::Result<()> {
+    /// let (listener, addr) = S::bind("localhost:0").await?;
+    /// println!("Listening on port {}", addr.port());
+    /// server.start_with_listener(&listener).await
+    /// # }
+    /// ```
+    async fn bind(addr: &str) -> Result<(TcpListener, SocketAddr)> {
         let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        Ok((listener, addr))
+    }
+
+    /// Bind a [`TcpListener`] to the given address like [`Server::bind`],
+    /// but with the given listen backlog, i.e. how many connections may be
+    /// waiting to be accepted before the OS starts refusing them.
+    ///
+    /// Raising it helps when many clients connect at once, e.g. when they
+    /// all reconnect after a restart. The OS may cap the value, e.g. at
+    /// `net.core.somaxconn` on Linux.
+    ///
+    /// If the address resolves to several socket addresses, each is tried in
+    /// turn, and the error from the last one is returned if none succeeds.
+    async fn bind_with_backlog(addr: &str, backlog: u32) -> Result<(TcpListener, SocketAddr)> {
+        let mut last_error = None;
+        for addr in tokio::net::lookup_host(addr).await? {
+            match listen_with_backlog(addr, backlog) {
+                Ok(listener) => {
+                    let addr = listener.local_addr()?;
+                    return Ok((listener, addr));
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "could not resolve to any address",
+                )
+            })
+            .into())
+    }
+
+    /// Get the window within which broadcasts to a client are coalesced
+    /// into a single frame holding a JSON array of the messages. Clients
+    /// must opt in with [`crate::Client::coalesced`], and message types
+    /// that serialize to JSON arrays can't be coalesced.
+    ///
+    /// Defaults to `None`, which sends every message in its own frame.
+    fn coalesce_window(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether a client's own copy of a broadcast sent with
+    /// [`ServerMessageChannels::broadcast`] while handling its message is
+    /// delivered in order with the responses sent during the same call,
+    /// instead of after all of them (see [`ServerMessageChannels`]). Other
+    /// clients aren't affected.
+    ///
+    /// Defaults to `false`.
+    fn ordered_broadcasts(&self) -> bool {
+        false
+    }
+
+    /// Get how long writing to a client may stall before the client is
+    /// considered stuck and disconnected, e.g. because it stopped reading
+    /// and its socket buffer is full.
+    ///
+    /// This covers both the connection's writer and sends on
+    /// `response_sender` waiting for room in the queue, so a stuck client
+    /// can't hold up its connection's task indefinitely. A send that times
+    /// out fails with [`std::io::ErrorKind::TimedOut`].
+    ///
+    /// Defaults to `None`, which waits indefinitely.
+    fn write_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Get how many messages may be waiting to be written to a client
+    /// before the client is considered stuck and disconnected, as a send
+    /// that finds the queue full fails with
+    /// [`std::io::ErrorKind::WouldBlock`]. Each clone of `response_sender`
+    /// may queue one more message on top of the limit.
+    ///
+    /// Defaults to `None`, which waits for room in a queue of 32 messages.
+    fn max_queued_messages(&self) -> Option<usize> {
+        None
+    }
+
+    /// Get how many [`MessageHandler`] calls a connection may run at once,
+    /// e.g. so that requests waiting on IO overlap. Once that many are
+    /// running, the connection stops reading until one of them returns.
+    ///
+    /// With more than 1, each call that may run at once gets its own state
+    /// from [`Server::get_state_for`], which never goes through
+    /// [`State::on_join`], and its own [`ServerMessageChannels`].
+    ///
+    /// Calls may finish, and respond, out of order, so keep the default if
+    /// handling a message depends on the ones before it.
+    ///
+    /// Defaults to 1, which handles one message at a time, in order.
+    fn max_in_flight(&self) -> usize {
+        1
+    }
+
+    /// Get how long a single poll of a [`MessageHandler`] call may take
+    /// before it's reported as blocking the executor, e.g. because it does
+    /// blocking IO instead of awaiting, which stalls every other task on
+    /// the same thread. Time spent awaiting doesn't count. Each time it
+    /// happens, [`ConnectionObserver::on_slow_handler`] is called, e.g. to
+    /// log a warning. Meant for development, to catch blocking calls early.
+    ///
+    /// Defaults to `None`, which doesn't measure anything.
+    fn slow_handler_warn(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Get how long [`Server::start_with_shutdown`] waits for connections
+    /// to finish once shutdown has been signalled, e.g. for writers stuck
+    /// on clients that stopped reading. Tasks still running afterwards are
+    /// left behind.
+    ///
+    /// Defaults to `None`, which waits indefinitely.
+    fn shutdown_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Get how long [`Server::start_with_drain`] waits for clients to leave
+    /// by themselves once draining has been signalled. Clients still
+    /// connected afterwards are disconnected as if the server were shutting
+    /// down, see [`Server::start_with_shutdown`].
+    ///
+    /// Defaults to `None`, which waits for every client to leave.
+    fn drain_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Get the version of the protocol, i.e. of the message types, that the
+    /// server speaks, so that clients built against another version are
+    /// caught instead of failing to deserialize messages one by one. When
+    /// it's not 0, the server declares it to every client as soon as the
+    /// client joins, and checks the version the client declares (see
+    /// [`crate::Client::protocol_version`]) before handling any of its
+    /// messages. Clients declaring another version, or none at all, are
+    /// passed to [`Server::on_version_mismatch`].
+    ///
+    /// Defaults to 0, which neither declares nor checks a version.
+    fn protocol_version(&self) -> u32 {
+        0
+    }
+
+    /// Get the compression algorithms the server is willing to use, in
+    /// order of preference. Each connection uses the first one its client
+    /// also supports, see [`crate::compression`].
+    ///
+    /// Defaults to none, which never compresses messages.
+    fn compression(&self) -> Vec<Compression> {
+        Vec::new()
+    }
+
+    /// Get the [`ConnectionObserver`] to notify of connection lifecycle
+    /// events. Called once per accepted connection.
+    ///
+    /// The observer is notified in addition to the `handle_*` hooks, so
+    /// either can be used.
+    ///
+    /// Defaults to `None`.
+    fn observer(&self) -> Option<Arc<dyn ConnectionObserver<Self::ClientID>>> {
+        None
+    }
+
+    /// Get the [`ClusterBackend`] to share broadcasts with other instances
+    /// of the server through. Called once when the server starts.
+    ///
+    /// With a backend, the server keeps listening for broadcasts even when
+    /// no client is connected, so broadcasting never fails with
+    /// [`BroadcastError::NoReceivers`].
+    ///
+    /// Defaults to `None`.
+    fn cluster(&self) -> Option<Arc<dyn ClusterBackend>> {
+        None
+    }
+
+    /// Send a newly joined client whatever it needs to catch up, e.g. the
+    /// current contents of a shared document or recent chat history.
+    ///
+    /// Called once per connection, after [`State::on_join`] and before any
+    /// messages from the client are handled. Anything sent on
+    /// `channels.response_sender` here reaches the client before any
+    /// broadcast, including broadcasts sent by other clients while this
+    /// runs.
+    ///
+    /// Runs alongside accepting and serving other connections, so a client
+    /// that doesn't read its snapshot only holds up its own connection,
+    /// until [`Server::write_timeout`] passes or the server shuts down.
+    ///
+    /// Default implementation does nothing.
+    async fn on_join_snapshot(
+        &self,
+        _id: &Self::ClientID,
+        _channels: &mut ServerMessageChannels<Self::ClientID>,
+        _state: &mut Self::State,
+    ) {
+    }
+
+    /// React to a client having joined, e.g. by broadcasting a "user
+    /// joined" message to everyone else, keeping [`State::on_join`] free of
+    /// side effects.
+    ///
+    /// Called once per connection, after [`Server::on_join_snapshot`] and
+    /// before any messages from the client are handled. The client is
+    /// already subscribed to broadcasts, so it receives anything sent here
+    /// that it's a recipient of.
+    ///
+    /// Default implementation does nothing.
+    async fn after_join(
+        &self,
+        _id: &Self::ClientID,
+        _broadcast_sender: &BroadcastSender<Self::ClientID>,
+        _state: &mut Self::State,
+    ) {
+    }
+
+    /// Spawn one of the tasks serving a connection. Each connection has two
+    /// tasks, see [the module documentation](self#connections).
+    ///
+    /// Override this to control where connections run, e.g. to use
+    /// [`tokio::task::spawn_local`] when running the server on a
+    /// [`LocalSet`](tokio::task::LocalSet), or to keep track of the tasks.
+    ///
+    /// Defaults to [`tokio::spawn`].
+    fn spawn_connection(&self, task: BoxFuture<'static, ()>) {
+        tokio::spawn(task);
+    }
+
+    /// Start the server on the given address, with the listen backlog from
+    /// [`ServerOptions::backlog`].
+    async fn start(&self, addr: &str) -> Result<()> {
+        let options = self.options();
+        let listener = bind_with_options::<Self>(addr, &options).await?;
+        let listeners = std::slice::from_ref(&listener);
+        serve_forever(self, options, TcpAcceptor::new(self, listeners)).await
+    }
+
+    /// Start the server on the given address with the given listen backlog,
+    /// in place of [`ServerOptions::backlog`]. See
+    /// [`Server::bind_with_backlog`].
+    async fn start_with_backlog(&self, addr: &str, backlog: u32) -> Result<()> {
+        let (listener, _addr) = Self::bind_with_backlog(addr, backlog).await?;
         self.start_with_listener(&listener).await
     }
 
+    /// Start the server on several addresses at once, e.g. both an IPv4 and
+    /// an IPv6 address. Clients connected through any of the addresses share
+    /// the same state and can broadcast to each other.
+    async fn start_many(&self, addrs: &[&str]) -> Result<()> {
+        let options = self.options();
+        let mut listeners = Vec::with_capacity(addrs.len());
+        for addr in addrs {
+            listeners.push(bind_with_options::<Self>(addr, &options).await?);
+        }
+        serve_forever(self, options, TcpAcceptor::new(self, &listeners)).await
+    }
+
     /// Start the server with a [`TcpListener`].
+    ///
+    /// Each accepted connection is served until the client closes its write
+    /// half. At that point no more messages are read, but any broadcasts
+    /// already queued for the client are still delivered and all buffered
+    /// frames are flushed before the connection is closed. A client that
+    /// only half-closes its socket will therefore receive everything sent to
+    /// it up to that point, followed by EOF.
     async fn start_with_listener(&self, listener: &TcpListener) -> Result<()> {
-        let (broadcast_sender, _rx) = broadcast::channel::<(Value, Recipients<Self::ClientID>)>(10);
+        self.start_with_listeners(std::slice::from_ref(listener))
+            .await
+    }
 
-        loop {
-            self.__next_client::<crate::private::InternalFlag>(
-                listener,
-                &broadcast_sender,
-                self.get_state(),
-            )
-            .await?;
+    /// Start the server with an already bound [`std::net::TcpListener`],
+    /// e.g. one inherited through systemd socket activation. The listener
+    /// is switched to non-blocking mode, as required by tokio. See
+    /// [`Server::start_with_listener`].
+    async fn start_with_std_listener(&self, listener: std::net::TcpListener) -> Result<()> {
+        listener.set_nonblocking(true)?;
+        let listener = TcpListener::from_std(listener)?;
+        self.start_with_listener(&listener).await
+    }
+
+    /// Start the server with a [`TcpListener`] like
+    /// [`Server::start_with_listener`], until `shutdown` is cancelled.
+    ///
+    /// Once cancelled, no more connections are accepted, and every
+    /// connection stops reading from its client as if the client had left:
+    /// broadcasts already queued for it are still delivered, and
+    /// [`State::on_leave`] is called. This returns once every task serving
+    /// a connection has finished, or [`Server::shutdown_timeout`] has
+    /// passed, whichever comes first.
+    async fn start_with_shutdown(
+        &self,
+        listener: &TcpListener,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        let drain = CancellationToken::new();
+        serve(
+            self,
+            self.options(),
+            TcpAcceptor::new(self, std::slice::from_ref(listener)),
+            &shutdown,
+            &drain,
+            &PauseHandle::new(),
+            &Connections::default(),
+        )
+        .await
+    }
+
+    /// Start the server with a [`TcpListener`] like
+    /// [`Server::start_with_listener`], until `drain` is cancelled and
+    /// every client has left.
+    ///
+    /// Once cancelled, no more connections are accepted, but connected
+    /// clients are served as usual until they leave, e.g. so a new server
+    /// process can take over new connections during a deploy. This returns
+    /// once the last client has left, or after [`Server::drain_timeout`],
+    /// when the remaining clients are disconnected like with
+    /// [`Server::start_with_shutdown`].
+    async fn start_with_drain(
+        &self,
+        listener: &TcpListener,
+        drain: CancellationToken,
+    ) -> Result<()> {
+        let shutdown = CancellationToken::new();
+        serve(
+            self,
+            self.options(),
+            TcpAcceptor::new(self, std::slice::from_ref(listener)),
+            &shutdown,
+            &drain,
+            &PauseHandle::new(),
+            &Connections::default(),
+        )
+        .await
+    }
+
+    /// Start the server with a [`TcpListener`] like
+    /// [`Server::start_with_listener`], pausing and resuming accepting new
+    /// connections whenever `pause` says so. See [`PauseHandle`].
+    async fn start_with_pause(&self, listener: &TcpListener, pause: PauseHandle) -> Result<()> {
+        let never = CancellationToken::new();
+        let connections = Connections::default();
+        serve(
+            self,
+            self.options(),
+            TcpAcceptor::new(self, std::slice::from_ref(listener)),
+            &never,
+            &never,
+            &pause,
+            &connections,
+        )
+        .await
+    }
+
+    /// Start the server on the given address in a background task, returning
+    /// a [`RunningServer`] handle to manage it, e.g. to shut it down, rather
+    /// than never returning like [`Server::start`].
+    ///
+    /// This takes the server by value, as the task outlives the call.
+    /// It's not called `run`, so that it can't be mixed up with
+    /// [`ServerRunner::run`].
+    async fn launch(self, addr: &str) -> Result<RunningServer<Self::ClientID>>
+    where
+        Self: Sized + Send + Sync + 'static,
+    {
+        let options = self.options();
+        let listener = bind_with_options::<Self>(addr, &options).await?;
+        let local_addr = listener.local_addr()?;
+        let connections = Connections::default();
+        let (shutdown, drain) = (CancellationToken::new(), CancellationToken::new());
+        let task = tokio::spawn({
+            let (connections, shutdown, drain) =
+                (connections.clone(), shutdown.clone(), drain.clone());
+            async move {
+                let listeners = [listener];
+                let pause = PauseHandle::new();
+                serve(
+                    &self,
+                    options,
+                    TcpAcceptor::new(&self, &listeners),
+                    &shutdown,
+                    &drain,
+                    &pause,
+                    &connections,
+                )
+                .await
+            }
+        });
+        Ok(RunningServer::new(
+            local_addr,
+            connections,
+            shutdown,
+            drain,
+            task,
+        ))
+    }
+
+    /// Start the server with several [`TcpListener`]s, accepting connections
+    /// from all of them. See [`Server::start_with_listener`].
+    ///
+    /// Returns an error if `listeners` is empty.
+    async fn start_with_listeners(&self, listeners: &[TcpListener]) -> Result<()> {
+        if listeners.is_empty() {
+            return Err(anyhow!("no listeners to accept connections from"));
         }
+
+        serve_forever(self, self.options(), TcpAcceptor::new(self, listeners)).await
+    }
+
+    /// Start the server on the given address, accepting WebSocket
+    /// connections instead of raw TCP ones, e.g. from browsers. See
+    /// [`Server::start_ws_with_listener`].
+    ///
+    /// Only available with the `websocket` feature.
+    #[cfg(feature = "websocket")]
+    async fn start_ws(&self, addr: &str) -> Result<()> {
+        let (listener, _addr) = Self::bind(addr).await?;
+        self.start_ws_with_listener(&listener).await
+    }
+
+    /// Start the server with a [`TcpListener`], accepting WebSocket
+    /// connections. Each WebSocket message carries a single message, so
+    /// [`Server::codec`], [`Server::length_field_length`] and
+    /// [`Server::chunk_size`] don't apply. Otherwise connections are served
+    /// like those accepted by [`Server::start_with_listener`].
+    ///
+    /// Connections that don't complete the WebSocket handshake are dropped
+    /// without ever joining, and reported to [`Server::handle_setup_err`].
+    ///
+    /// Only available with the `websocket` feature.
+    #[cfg(feature = "websocket")]
+    async fn start_ws_with_listener(&self, listener: &TcpListener) -> Result<()> {
+        let listeners = std::slice::from_ref(listener);
+        serve_forever(self, self.options(), accept::WsAcceptor { listeners }).await
     }
 
     #[doc(hidden)]
-    /// Accept the next connection and set up channels.
-    async fn __next_client<T: crate::private::Internal>(
+    /// Set up channels for a newly accepted connection, framed with the
+    /// given codecs for reading and writing.
+    async fn __next_client<T: crate::private::Internal, S: Transport>(
         &self,
-        listener: &TcpListener,
+        stream: S,
+        addr: SocketAddr,
+        codecs: (FrameCodec, FrameCodec),
+        options: &ServerOptions,
         broadcast_sender: &BroadcastSender<Self::ClientID>,
-        mut state: Self::State,
+        connections: &Connections<Self::ClientID>,
     ) -> Result<()> {
-        let (stream, _addr) = listener.accept().await?;
+        let (frames, frame_sink) = framed(stream, codecs, options);
+        self.__next_connection::<T, _, _>(
+            frames,
+            frame_sink,
+            addr,
+            options,
+            broadcast_sender,
+            connections,
+        )
+        .await
+    }
+
+    #[doc(hidden)]
+    /// Set up channels for a newly accepted connection, given the stream of
+    /// frames read from it and the sink for frames written to it.
+    async fn __next_connection<T, R, W>(
+        &self,
+        frames: R,
+        frame_sink: W,
+        addr: SocketAddr,
+        options: &ServerOptions,
+        broadcast_sender: &BroadcastSender<Self::ClientID>,
+        connections: &Connections<Self::ClientID>,
+    ) -> Result<()>
+    where
+        T: crate::private::Internal,
+        R: Stream<Item = io::Result<BytesMut>> + Send + Unpin + 'static,
+        W: Sink<Bytes, Error = io::Error> + Send + Unpin + 'static,
+    {
+        let connection_id = ConnectionId::next();
+        let observer = self.observer();
+        if let Some(observer) = &observer {
+            observer.on_accept(addr);
+        }
+        let stats = ConnectionStats::default();
+        let (frames, frame_sink) = stats::count(frames, frame_sink, &stats, &connections.stats());
+
+        let mut state = self.get_state_for(addr).await;
 
         let broadcast_sender = broadcast_sender.clone();
         let mut broadcast_receiver: BroadcastReceiver<Self::ClientID> =
             broadcast_sender.subscribe();
 
-        let id: Self::ClientID = state.on_join();
-
-        // Duplicate the socket: one for serializing and one for deserializing
-        let de_stream = stream.into_std()?;
-        let ser_stream = de_stream.try_clone()?;
-        let de_stream = TcpStream::from_std(de_stream)?;
-        let ser_stream = TcpStream::from_std(ser_stream)?;
+        let id: Self::ClientID = match state.try_on_join() {
+            Ok(id) => id,
+            Err(reason) => {
+                // The client never joined, so there's no connection task,
+                // just a writer for the rejection message if there is one
+                if let Some(message) = reason.message {
+                    let (mut sender, writer) =
+                        ValueSender::with_writer(frame_sink, writer_options(options));
+                    self.spawn_connection(connections.track(writer));
+                    let _ = sender.feed(message).await;
+                    let _ = sender.close().await;
+                }
+                return Ok(());
+            }
+        };
+        if let Some(observer) = &observer {
+            observer.on_join(&id);
+        }
+        let outbox = options.require_ack.then(|| connections.outbox(id.clone()));
 
-        let mut client_message_receiver: MessageReceiver<Self::ClientMessage> =
-            tokio_serde::SymmetricallyFramed::new(
-                FramedRead::new(de_stream, LengthDelimitedCodec::new()),
-                SymmetricalJson::<Self::ClientMessage>::default(),
-            );
+        // Frames are only decoded into JSON values here if they may be
+        // control frames or carry metadata, as they have to be looked inside
+        let mut client_message_receiver = frames
+            .and_then(|frame| future::ready(compression::decompress(frame)))
+            .and_then(|frame| future::ready(Inbound::decode(frame)));
+        let compression = SharedCompression::default();
+        let supported_compression = options.compression.clone();
 
-        let response_sender: ValueSender = tokio_serde::SymmetricallyFramed::new(
-            FramedWrite::new(ser_stream, LengthDelimitedCodec::new()),
-            SymmetricalJson::default(),
+        let (response_sender, writer) = ValueSender::with_writer(
+            frame_sink,
+            WriterOptions {
+                outbox: outbox.clone(),
+                compression: compression.clone(),
+                ..writer_options(options)
+            },
         );
+        // The connection is closed once the writer has finished and the
+        // connection's task has stopped reading, in whichever order
+        let reading = CancellationToken::new();
+        let stopped_reading = reading.clone().drop_guard();
+        let writer = {
+            let (observer, id, stats) = (observer.clone(), id.clone(), stats.clone());
+            async move {
+                writer.await;
+                reading.cancelled().await;
+                if let Some(observer) = &observer {
+                    observer.on_close(&id, &stats);
+                }
+            }
+        };
+        self.spawn_connection(connections.track(Box::pin(writer)));
+        connections.insert(id.clone(), connection_id, response_sender.clone());
 
         // Collect message channels into struct
         let mut message_channels = ServerMessageChannels {
             response_sender,
             broadcast_sender,
+            tags: HashSet::new(),
+            connections: connections.clone(),
+            cancellation: CancellationToken::new(),
+            connection_id,
+            origin: None,
+            pretty: options.json_pretty,
+            stats,
+            tasks: Mutex::default(),
         };
+        let ordered_broadcasts = options.ordered_broadcasts;
+        message_channels.origin = ordered_broadcasts.then(|| Origin { id: id.clone() });
+
+        let protocol_version = options.protocol_version;
+        if protocol_version != 0 {
+            let _ = message_channels
+                .response_sender
+                .send_control_frame(Control::Version(protocol_version).to_value())
+                .await;
+        }
+
+        self.on_join_snapshot(&id, &mut message_channels, &mut state)
+            .await;
+        self.after_join(&id, &message_channels.broadcast_sender, &mut state)
+            .await;
+
+        let coalesce_window = options.coalesce_window;
+        let slow_handler_warn = options.slow_handler_warn;
+        let max_in_flight = options.max_in_flight.max(1);
+        let shutdown = connections.shutdown_token();
+
+        // Handler calls running alongside others each need a state and
+        // channels of their own, which they're lent while they run
+        let mut slots = Vec::new();
+        if max_in_flight > 1 {
+            for _ in 0..max_in_flight {
+                slots.push((message_channels.fork(), self.get_state_for(addr).await));
+            }
+        }
 
-        let mut state = self.get_state();
+        self.spawn_connection(connections.track(Box::pin(async move {
+            // Broadcasts waiting to be sent together, when coalescing
+            let mut batch: Vec<Bytes> = Vec::new();
+            let mut batch_deadline: Option<Instant> = None;
+            let mut said_goodbye = false;
+            // Whether the client's protocol version has been checked, or
+            // doesn't have to be
+            let mut version_checked = protocol_version == 0;
+            // Handler calls running alongside others, polled by this task,
+            // giving back the state and channels they were lent, and the
+            // tags they started with
+            let mut in_flight: stream::FuturesUnordered<BoxFuture<'static, InFlight<Self>>> = stream::FuturesUnordered::new();
+            // Tasks spawned on the client's behalf, taken out of the
+            // channels they were spawned on
+            let mut tasks = mem::take(message_channels.tasks.get_mut());
+            let on_slow = |elapsed: Duration| {
+                if let Some(observer) = &observer {
+                    observer.on_slow_handler(&id, elapsed);
+                }
+            };
+            // Take broadcasts out of their envelopes, telling whether they're
+            // high priority, and whether this connection sent them and so has
+            // already delivered them
+            let open = |json: Bytes| {
+                let (json, _remote) = envelope::open_remote(json);
+                let (json, priority) = envelope::open_priority(json);
+                if !ordered_broadcasts {
+                    return (json, priority, false);
+                }
+                let (json, origin) = envelope::open_origin(json);
+                (json, priority, origin == Some(connection_id.get()))
+            };
 
-        tokio::spawn(async move {
             loop {
+                // Whether as many handler calls are running as may be, so
+                // that reading has to wait for one of them to return
+                let busy = in_flight.len() >= max_in_flight;
+
                 tokio::select! {
+                    // The server is shutting down, so treat the client as
+                    // having left
+                    () = shutdown.cancelled() => break,
+
+                    // A handler call running alongside others returned,
+                    // making room for the next message
+                    Some((mut slot, tags, handled)) = in_flight.next(), if !in_flight.is_empty() => {
+                        let (channels, _) = &mut slot;
+                        update_tags(&mut message_channels.tags, &tags, &channels.tags);
+                        tasks.extend(mem::take(channels.tasks.get_mut()));
+                        slots.push(slot);
+                        if let Err(payload) = handled {
+                            let e = panic_error(payload);
+                            notify_error(&observer, &id, &e);
+                            Self::handle_connection_panic(e, &mut state);
+                            break;
+                        }
+                    }
+
+                    // Tasks spawned on the client's behalf
+                    Some(()) = tasks.next(), if !tasks.is_empty() => {}
+
                     // Handle messages received from the broadcaster and pass them on
                     result = broadcast_receiver.recv() => {
                         match result {
-                            Ok((value, recipients)) => {
-                                let should_send = match recipients {
-                                    Recipients::Everyone => true,
-                                    Recipients::SingleRecipient { recipient } => recipient == id,
-                                    Recipients::MultipleRecipients { recipients } => {
-                                        recipients.contains(&id)
-                                    }
-                                };
-
-                                if should_send {
-                                    let result = message_channels.response_sender.send(value).await;
-                                    if let Err(e) = result {
-                                        Self::handle_broadcast_send_err(e.into(), &mut state);
+                            Ok((json, recipients)) => {
+                                if let Some(observer) = &observer {
+                                    observer.on_broadcast(&id, broadcast_receiver.len());
+                                }
+                                let (json, priority, delivered) = open(json);
+                                if !delivered && recipients.contains(&id, &message_channels.tags) {
+                                    if priority {
+                                        let result = message_channels.response_sender.send_serialized_priority(json);
+                                        if let Err(e) = result {
+                                            let e = e.into();
+                                            notify_error(&observer, &id, &e);
+                                            Self::handle_broadcast_send_err(e, &mut state);
+                                        }
+                                    } else if let Some(window) = coalesce_window {
+                                        batch_deadline.get_or_insert_with(|| Instant::now() + window);
+                                        batch.push(json);
+                                    } else {
+                                        let result = message_channels.response_sender.send_serialized(json).await;
+                                        if let Err(e) = result {
+                                            let e = e.into();
+                                            notify_error(&observer, &id, &e);
+                                            Self::handle_broadcast_send_err(e, &mut state);
+                                        }
                                     }
                                 }
                             }
                             Err(e) => {
-                                Self::handle_broadcast_recv_err(e.into(), &mut state);
+                                let closed = matches!(e, broadcast::error::RecvError::Closed);
+                                if let (Some(observer), broadcast::error::RecvError::Lagged(missed)) = (&observer, &e) {
+                                    observer.on_broadcast_lag(&id, *missed);
+                                }
+                                let e = e.into();
+                                notify_error(&observer, &id, &e);
+                                Self::handle_broadcast_recv_err(e, &mut state);
+                                // Every sender is gone, so receiving would fail
+                                // straight away again, forever
+                                if closed {
+                                    break;
+                                }
                             }
                         }
                     }
 
+                    // Send coalesced broadcasts once the window has passed
+                    () = time::sleep_until(batch_deadline.unwrap_or_else(Instant::now)), if batch_deadline.is_some() => {
+                        batch_deadline = None;
+                        let result = message_channels.response_sender.send_serialized(coalesce(&mut batch)).await;
+                        if let Err(e) = result {
+                            let e = e.into();
+                            notify_error(&observer, &id, &e);
+                            Self::handle_broadcast_send_err(e, &mut state);
+                        }
+                    }
+
                     // Messages received from the client
-                    result = client_message_receiver.try_next() => {
+                    result = client_message_receiver.try_next(), if !busy => {
                         match result {
-                            Ok(msg) => {
-                                if let Some(msg) = msg {
-                                    Self::ClientMessageHandler::handle_client_message(msg, &id, &mut message_channels, &mut state).await;
+                            // Keepalives only need to arrive
+                            Ok(Some(Inbound::Control(Control::Keepalive))) => {}
+                            // Pings are answered by the framework, and the
+                            // answers to ours only reported
+                            Ok(Some(Inbound::Control(Control::Ping))) => {
+                                let _ = message_channels.response_sender.send_control(Control::Pong);
+                            }
+                            Ok(Some(Inbound::Control(Control::Pong))) => {
+                                if let Some(observer) = &observer {
+                                    observer.on_pong(&id);
+                                }
+                            }
+                            Ok(Some(Inbound::Control(Control::Ack(seq)))) => {
+                                if let Some(outbox) = &outbox {
+                                    outbox.lock().acknowledge(seq);
+                                }
+                            }
+                            // The client offers compression, so pick an
+                            // algorithm and use it from now on
+                            Ok(Some(Inbound::Control(Control::Offer(offered)))) => {
+                                let chosen = Compression::negotiate(&supported_compression, &offered);
+                                let result = message_channels.response_sender.send_control_frame(Control::Choice(chosen).to_value()).await;
+                                if let Err(e) = result {
+                                    let e = e.into();
+                                    notify_error(&observer, &id, &e);
+                                    Self::handle_connection_err(e, &mut state);
+                                    break;
+                                }
+                                compression.set(chosen);
+                            }
+                            // The client is leaving and won't send anything
+                            // else, so stop reading and acknowledge once
+                            // it's gone
+                            Ok(Some(Inbound::Control(Control::Goodbye))) => {
+                                said_goodbye = true;
+                                break;
+                            }
+                            // The client declares its protocol version
+                            Ok(Some(Inbound::Control(Control::Version(declared)))) => {
+                                let declared = Some(declared);
+                                if !version_checked {
+                                    version_checked = true;
+                                    if declared != Some(protocol_version)
+                                        && !Self::on_version_mismatch(declared, &mut state)
+                                    {
+                                        break;
+                                    }
                                 }
                             }
+                            Ok(Some(inbound)) => {
+                                // The client didn't declare a version before
+                                // its first message
+                                if !version_checked {
+                                    version_checked = true;
+                                    if !Self::on_version_mismatch(None, &mut state) {
+                                        break;
+                                    }
+                                }
+                                if let Some(observer) = &observer {
+                                    observer.on_message(&id);
+                                }
+                                let (msg, metadata) = inbound.open::<Self::ClientMessage>();
+                                let handled = match msg {
+                                    Ok(msg) if !slots.is_empty() => {
+                                        let expired = metadata.deadline.is_some_and(|deadline| deadline <= SystemTime::now());
+                                        let (mut channels, mut call_state) = slots.pop().expect("checked to be non-empty");
+                                        channels.tags.clone_from(&message_channels.tags);
+                                        let tags = message_channels.tags.clone();
+                                        let (observer, id) = (observer.clone(), id.clone());
+                                        in_flight.push(Box::pin(async move {
+                                            let on_slow = |elapsed: Duration| {
+                                                if let Some(observer) = &observer {
+                                                    observer.on_slow_handler(&id, elapsed);
+                                                }
+                                            };
+                                            let handling = AssertUnwindSafe(dispatch::<Self::ClientMessageHandler>(msg, expired, &id, &mut channels, &mut call_state)).catch_unwind();
+                                            let handled = watchdog::watch(handling, slow_handler_warn, on_slow).await;
+                                            ((channels, call_state), tags, handled)
+                                        }));
+                                        Ok(())
+                                    }
+                                    Ok(msg) => {
+                                        let expired = metadata.deadline.is_some_and(|deadline| deadline <= SystemTime::now());
+                                        let handling = AssertUnwindSafe(dispatch::<Self::ClientMessageHandler>(msg, expired, &id, &mut message_channels, &mut state)).catch_unwind();
+                                        watchdog::watch(handling, slow_handler_warn, on_slow).await
+                                    }
+                                    Err(bad) => {
+                                        let e = Error::new(bad);
+                                        notify_error(&observer, &id, &e);
+                                        let handling = AssertUnwindSafe(Self::ClientMessageHandler::handle_bad_message(e, &id, &mut message_channels, &mut state)).catch_unwind();
+                                        watchdog::watch(handling, slow_handler_warn, on_slow).await
+                                    }
+                                };
+                                if let Err(payload) = handled {
+                                    let e = panic_error(payload);
+                                    notify_error(&observer, &id, &e);
+                                    Self::handle_connection_panic(e, &mut state);
+                                    break;
+                                }
+                            }
+                            // The client shut down its write half (or closed
+                            // the connection entirely), so stop reading
+                            Ok(None) => break,
+                            // A single malformed message can be skipped
+                            Err(e) if is_deserialize_error(&e) => {
+                                let e = into_bad_message(e);
+                                notify_error(&observer, &id, &e);
+                                let handling = AssertUnwindSafe(Self::ClientMessageHandler::handle_bad_message(e, &id, &mut message_channels, &mut state)).catch_unwind();
+                                let handled = watchdog::watch(handling, slow_handler_warn, on_slow).await;
+                                if let Err(payload) = handled {
+                                    let e = panic_error(payload);
+                                    notify_error(&observer, &id, &e);
+                                    Self::handle_connection_panic(e, &mut state);
+                                    break;
+                                }
+                            }
+                            // Errors from the underlying stream or codec leave
+                            // it in an unknown state, so drop the connection
                             Err(e) => {
-                                Self::ClientMessageHandler::handle_bad_message(e.into(), &id, &mut message_channels, &mut state).await;
+                                let e = e.into();
+                                notify_error(&observer, &id, &e);
+                                Self::handle_connection_err(e, &mut state);
+                                break;
                             }
                         }
                     }
                 }
+
+                tasks.extend(mem::take(message_channels.tasks.get_mut()));
+
+                // The writer has stopped, e.g. because the client stopped
+                // reading and writing timed out, so nothing can reach the
+                // client anymore
+                if message_channels.response_sender.is_closed() {
+                    break;
+                }
+            }
+
+            // Handler calls still running are let finish, as a call would
+            // be with one at a time
+            while let Some((slot, tags, handled)) = in_flight.next().await {
+                let (channels, _) = &slot;
+                update_tags(&mut message_channels.tags, &tags, &channels.tags);
+                if let Err(payload) = handled {
+                    let e = panic_error(payload);
+                    notify_error(&observer, &id, &e);
+                    Self::handle_connection_panic(e, &mut state);
+                }
             }
-        });
+
+            // Nothing more will be read from the client, so stop any work
+            // done on its behalf
+            message_channels.cancellation.cancel();
+
+            // The write half may still be open after a half-close, so deliver
+            // any broadcasts that were already queued for this client before
+            // closing the connection.
+            loop {
+                match broadcast_receiver.try_recv() {
+                    Ok((json, recipients)) => {
+                        let (json, _priority, delivered) = open(json);
+                        if !delivered && recipients.contains(&id, &message_channels.tags) {
+                            batch.push(json);
+                        }
+                    }
+                    Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                }
+            }
+
+            let result = if message_channels.response_sender.is_closed() {
+                Ok(())
+            } else if coalesce_window.is_none() {
+                let sender = &mut message_channels.response_sender;
+                async {
+                    for json in batch {
+                        sender.feed_serialized(json).await?;
+                    }
+                    sender.flush().await
+                }
+                .await
+            } else if !batch.is_empty() {
+                message_channels
+                    .response_sender
+                    .feed_serialized(coalesce(&mut batch))
+                    .await
+            } else {
+                Ok(())
+            };
+            if let Err(e) = result {
+                let e = e.into();
+                notify_error(&observer, &id, &e);
+                Self::handle_broadcast_send_err(e, &mut state);
+            }
+
+            state.on_leave(&id);
+            let empty = message_channels.connections.remove(connection_id);
+            if let Some(observer) = &observer {
+                observer.on_leave(&id);
+            }
+            if empty {
+                Self::on_empty(&mut state);
+            }
+            if said_goodbye {
+                let _ = message_channels
+                    .response_sender
+                    .feed_control(Control::Goodbye.to_value())
+                    .await;
+            }
+
+            // Closing lets the writer finish writing any queued frames and
+            // then shut down the socket
+            let _ = message_channels.response_sender.close().await;
+            drop(stopped_reading);
+        })));
 
         Ok(())
     }
 
+    /// Called when the last connected client has left, after
+    /// [`State::on_leave`], e.g. to release resources held for a game
+    /// lobby or to shut the server down once it's idle. Called once every
+    /// time the server becomes empty, with the state of the connection
+    /// that left last.
+    ///
+    /// A new client may connect at any time, including right after the
+    /// last one left and before, or while, this is called. Check
+    /// [`Connections::count`] again before tearing down anything that
+    /// client would need, e.g. while holding a lock the new client's
+    /// [`State::on_join`] would also take.
+    ///
+    /// Default implementation does nothing.
+    fn on_empty(_state: &mut Self::State) {}
+
+    /// Decide whether to keep serving a client whose protocol version, if
+    /// it declared one, doesn't match [`Server::protocol_version`], e.g. to
+    /// keep serving older clients whose messages are still understood.
+    /// Called before any of the client's messages are handled. Returning
+    /// `false` disconnects the client, followed by [`State::on_leave`].
+    ///
+    /// Default implementation disconnects every such client.
+    fn on_version_mismatch(_client_version: Option<u32>, _state: &mut Self::State) -> bool {
+        false
+    }
+
+    /// Handle an error setting up a newly accepted connection from `addr`,
+    /// e.g. failing to configure its socket because the process has run
+    /// out of file descriptors. The connection is closed without the
+    /// client ever joining, and the server carries on accepting others.
+    ///
+    /// Also called when accepting itself fails, with the address of the
+    /// listener, after which the server waits briefly before accepting
+    /// again.
+    ///
+    /// Default implementation does nothing.
+    fn handle_setup_err(&self, _err: Error, _addr: SocketAddr) {}
+
+    /// Handle an error sharing broadcasts with the other instances of the
+    /// server, e.g. failing to publish one, or receiving a payload that
+    /// can't be read, see [`Server::cluster`]. Only the broadcast involved
+    /// is affected, and the server carries on.
+    ///
+    /// Default implementation does nothing.
+    fn handle_cluster_err(&self, _err: Error) {}
+
+    /// Handle errors that end a connection, such as IO errors or invalid
+    /// frames (e.g. a length prefix exceeding the codec's maximum frame
+    /// length). The connection is closed after this is called, followed by
+    /// [`State::on_leave`].
+    ///
+    /// Default implementation does nothing.
+    fn handle_connection_err(_err: Error, _state: &mut Self::State) {}
+
+    /// Handle a panic in the [`MessageHandler`]. Instead of taking down the
+    /// connection's task, the panic is caught and the connection is closed
+    /// as if it had errored, so [`State::on_leave`] is still called
+    /// afterwards. Note that the state may have been left half-updated by
+    /// the handler, and any [`std::sync::Mutex`] it held will be poisoned.
+    ///
+    /// Default implementation does nothing.
+    fn handle_connection_panic(_err: Error, _state: &mut Self::State) {}
+
     /// Handle broadcast channel send failures.
     ///
     /// Default implementation does nothing.
     fn handle_broadcast_send_err(_err: Error, _state: &mut Self::State) {}
 
-    /// Handle broadcast channel receive failures.
+    /// Handle broadcast channel receive failures. If the connection fell
+    /// behind and missed broadcasts, it carries on with the next one it
+    /// can still receive. If the channel was closed because every sender
+    /// is gone, e.g. while the server shuts down, the connection is closed
+    /// after this is called, as no more broadcasts can arrive.
     ///
     /// Default implementation does nothing.
     fn handle_broadcast_recv_err(_err: Error, _state: &mut Self::State) {}
@@ -238,7 +1360,7 @@ pub trait Server: 'static {
 pub trait MessageHandler {
     /// The type of incoming server messages.
     /// Should be defined in the server API.
-    type ClientMessage;
+    type ClientMessage: Send;
     /// The type used for client identifiers.
     type ClientID;
     /// The type used by the server to store state.
@@ -253,7 +1375,56 @@ pub trait MessageHandler {
         state: &mut Self::State,
     );
 
-    /// Handle a client message that couldn't be deserialized.
+    /// Called with every client message before it's handled, e.g. to
+    /// validate, decrypt or audit it in one place. The message can be
+    /// modified before it's passed on, and returning [`ControlFlow::Break`]
+    /// drops it without handling it.
+    ///
+    /// Default implementation passes every message on unchanged.
+    async fn pre_handle(
+        _msg: &mut Self::ClientMessage,
+        _id: &Self::ClientID,
+        _channels: &mut ServerMessageChannels<Self::ClientID>,
+        _state: &mut Self::State,
+    ) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called after a client message has been handled, by either
+    /// [`Self::handle_client_message`] or [`Self::handle_expired`]. Not
+    /// called for messages dropped by [`Self::pre_handle`].
+    ///
+    /// Default implementation does nothing.
+    async fn post_handle(
+        _id: &Self::ClientID,
+        _channels: &mut ServerMessageChannels<Self::ClientID>,
+        _state: &mut Self::State,
+    ) {
+    }
+
+    /// Handle a client message whose
+    /// [deadline](crate::envelope::with_deadline) had already passed by the
+    /// time it would have been handled. The message is dropped without
+    /// calling [`MessageHandler::handle_client_message`].
+    ///
+    /// Default implementation does nothing.
+    async fn handle_expired(
+        _msg: Self::ClientMessage,
+        _id: &Self::ClientID,
+        _channels: &mut ServerMessageChannels<Self::ClientID>,
+        _state: &mut Self::State,
+    ) {
+    }
+
+    /// Handle a client message that couldn't be deserialized, either because
+    /// it isn't valid JSON or because it doesn't match
+    /// [`Self::ClientMessage`]. `err` is a [`BadMessage`], holding the
+    /// deserialization error and the message itself. The connection stays
+    /// open, and the next message is read as usual.
+    ///
+    /// Errors from the connection itself, such as IO errors or frames the
+    /// codec rejects, can't be recovered from, and go to
+    /// [`Server::handle_connection_err`] instead.
     async fn handle_bad_message(
         _err: Error,
         _id: &Self::ClientID,
@@ -262,3 +1433,389 @@ pub trait MessageHandler {
     ) {
     }
 }
+
+/// How long to wait before accepting again after accepting failed, so that
+/// a persistent failure doesn't spin.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Accept connections from `acceptor` into `connections`, with the given
+/// options read once when the server starts, except while `pause` is
+/// paused, until `shutdown` or `drain` is cancelled, then
+/// wait for the connections to finish accordingly.
+async fn serve<S: Server + Sync + ?Sized, A: Acceptor>(
+    server: &S,
+    options: ServerOptions,
+    acceptor: A,
+    shutdown: &CancellationToken,
+    drain: &CancellationToken,
+    pause: &PauseHandle,
+    connections: &Connections<S::ClientID>,
+) -> Result<()> {
+    let (broadcast_sender, _rx) = broadcast::channel(options.broadcast_capacity);
+    let listeners = acceptor.listeners();
+    let cluster = share_broadcasts(server, &broadcast_sender);
+    tokio::pin!(cluster);
+
+    // Connections are set up alongside accepting, so one that's slow to
+    // open, whose state is slow to set up, or whose client is slow to take
+    // its snapshot, can't hold up the others
+    let mut setups = stream::FuturesUnordered::new();
+    // When to try accepting again after it failed
+    let mut backoff: Option<Instant> = None;
+
+    loop {
+        let (stream, addr) = tokio::select! {
+            () = shutdown.cancelled() => break,
+            () = drain.cancelled() => break,
+            () = &mut cluster => continue,
+            Some(()) = setups.next() => continue,
+            () = time::sleep_until(backoff.unwrap_or_else(Instant::now)), if backoff.is_some() => {
+                backoff = None;
+                continue;
+            }
+            result = accept::accept(listeners, pause), if backoff.is_none() => match result {
+                Ok(accepted) => accepted,
+                // Accepting may fail for a while, e.g. while the process is
+                // out of file descriptors, without the listener being broken
+                Err((e, addr)) => {
+                    server.handle_setup_err(e.into(), addr);
+                    backoff = Some(Instant::now() + ACCEPT_BACKOFF);
+                    continue;
+                }
+            },
+        };
+        let opening = acceptor.open(server, stream, &options);
+        setups.push(connections.track_setup(set_up(
+            server,
+            opening,
+            addr,
+            &options,
+            &broadcast_sender,
+            connections,
+        )));
+    }
+
+    // Connections still being set up are waited for like the others
+    let setting_up = async {
+        while setups.next().await.is_some() {}
+        future::pending::<()>().await;
+    };
+    tokio::pin!(setting_up);
+
+    // Let clients leave by themselves first when draining
+    if !shutdown.is_cancelled() {
+        let drained = within(options.drain_timeout, connections.drained());
+        tokio::select! {
+            () = drained => {}
+            () = shutdown.cancelled() => {}
+            () = &mut cluster => {}
+            () = &mut setting_up => {}
+        }
+    }
+
+    let shut_down = within(options.shutdown_timeout, connections.shut_down());
+    tokio::select! {
+        () = shut_down => {}
+        () = &mut setting_up => {}
+    }
+    Ok(())
+}
+
+/// Accept connections from `acceptor`, forever.
+async fn serve_forever<S: Server + Sync + ?Sized, A: Acceptor>(
+    server: &S,
+    options: ServerOptions,
+    acceptor: A,
+) -> Result<()> {
+    let never = CancellationToken::new();
+    serve(
+        server,
+        options,
+        acceptor,
+        &never,
+        &never,
+        &PauseHandle::new(),
+        &Connections::default(),
+    )
+    .await
+}
+
+/// Set up a connection accepted from `addr`, up to spawning the tasks
+/// serving it, passing any error to [`Server::handle_setup_err`]. Stops
+/// early once the server shuts down.
+async fn set_up<S: Server + Sync + ?Sized>(
+    server: &S,
+    opening: Opening,
+    addr: SocketAddr,
+    options: &ServerOptions,
+    broadcast_sender: &BroadcastSender<S::ClientID>,
+    connections: &Connections<S::ClientID>,
+) {
+    // A connection that can't be set up only fails itself, e.g. when the
+    // server has run out of file descriptors for the moment
+    let setup = async {
+        let (frames, frame_sink) = opening.await?;
+        server
+            .__next_connection::<crate::private::InternalFlag, _, _>(
+                frames,
+                frame_sink,
+                addr,
+                options,
+                broadcast_sender,
+                connections,
+            )
+            .await
+    };
+    let shutdown = connections.shutdown_token();
+    tokio::select! {
+        result = setup => {
+            if let Err(e) = result {
+                server.handle_setup_err(e, addr);
+            }
+        }
+        () = shutdown.cancelled() => {}
+    }
+}
+
+/// Share the broadcasts sent on `broadcast_sender` with the other instances
+/// of `server`, if it has a [`Server::cluster`]. Never returns.
+async fn share_broadcasts<S: Server + Sync + ?Sized>(
+    server: &S,
+    broadcast_sender: &BroadcastSender<S::ClientID>,
+) {
+    if let Some(backend) = server.cluster() {
+        cluster::bridge(&*backend, broadcast_sender, |e| {
+            server.handle_cluster_err(e)
+        })
+        .await;
+    }
+    future::pending().await
+}
+
+/// Split `stream` into the frames read from it, in the connection task,
+/// and the sink for frames written to it, in a separate writer task,
+/// framed with the given codecs for reading and writing.
+fn framed<T: Transport>(
+    stream: T,
+    codecs: (FrameCodec, FrameCodec),
+    options: &ServerOptions,
+) -> (
+    tokio_util::codec::FramedRead<ReadHalf<T>, FrameCodec>,
+    tokio_util::codec::FramedWrite<WriteHalf<T>, FrameCodec>,
+) {
+    let (read_half, write_half) = tokio::io::split(stream);
+    let (read_codec, write_codec) = codecs;
+    (
+        codec::framed_read(
+            read_half,
+            read_codec.chunked(options.chunk_size),
+            options.read_buffer_capacity,
+        ),
+        codec::framed_write(
+            write_half,
+            write_codec.chunked(options.chunk_size),
+            options.write_buffer_capacity,
+        ),
+    )
+}
+
+/// Wait for `future`, giving up after `timeout`.
+async fn within(timeout: Option<Duration>, future: impl Future<Output = ()>) {
+    match timeout {
+        Some(timeout) => {
+            let _ = time::timeout(timeout, future).await;
+        }
+        None => future.await,
+    }
+}
+
+/// Pass a client message through the handler's hooks, to
+/// [`MessageHandler::handle_expired`] if its deadline has passed or to
+/// [`MessageHandler::handle_client_message`] otherwise.
+async fn dispatch<H: MessageHandler + Send>(
+    mut msg: H::ClientMessage,
+    expired: bool,
+    id: &H::ClientID,
+    channels: &mut ServerMessageChannels<H::ClientID>,
+    state: &mut H::State,
+) {
+    if H::pre_handle(&mut msg, id, channels, state)
+        .await
+        .is_break()
+    {
+        return;
+    }
+    if expired {
+        H::handle_expired(msg, id, channels, state).await;
+    } else {
+        H::handle_client_message(msg, id, channels, state).await;
+    }
+    H::post_handle(id, channels, state).await;
+}
+
+/// The state and channels lent to a handler call running alongside others,
+/// see [`Server::max_in_flight`].
+type Slot<S> = (
+    ServerMessageChannels<<S as Server>::ClientID>,
+    <S as Server>::State,
+);
+
+/// What a handler call running alongside others gives back once it
+/// returns: its slot, the tags it started with, and whether it panicked.
+type InFlight<S> = (Slot<S>, HashSet<String>, Result<(), Box<dyn Any + Send>>);
+
+/// Apply the changes a handler call made to its copy of a connection's
+/// tags, from `before` to `after`, to the connection's own `tags`, which
+/// other calls may have changed in the meantime.
+fn update_tags(tags: &mut HashSet<String>, before: &HashSet<String>, after: &HashSet<String>) {
+    for tag in before.difference(after) {
+        tags.remove(tag);
+    }
+    for tag in after.difference(before) {
+        tags.insert(tag.clone());
+    }
+}
+
+/// Returns whether an error from reading a message is caused by the message
+/// not deserializing, rather than by the underlying stream or codec.
+fn is_deserialize_error(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|inner| inner.is::<BadMessage>())
+}
+
+/// Take the [`BadMessage`] out of an error for which
+/// [`is_deserialize_error`] holds.
+fn into_bad_message(err: io::Error) -> Error {
+    match err.into_inner().map(|inner| inner.downcast::<BadMessage>()) {
+        Some(Ok(bad)) => Error::new(*bad),
+        Some(Err(inner)) => anyhow!(inner),
+        None => anyhow!("bad message"),
+    }
+}
+
+/// A frame read from a client, decoded only as far as needed.
+enum Inbound {
+    /// A control frame, see [`Control`].
+    Control(Control),
+    /// A frame that may be a control frame or carry metadata, decoded to
+    /// look inside.
+    Value(Value),
+    /// A frame that can only be a message, see [`envelope::is_plain`].
+    Message(BytesMut),
+}
+
+impl Inbound {
+    fn decode(frame: BytesMut) -> io::Result<Inbound> {
+        if envelope::is_plain(&frame) {
+            return Ok(Inbound::Message(frame));
+        }
+        match serde_json::from_slice(&frame) {
+            Ok(value) => match Control::parse(&value) {
+                Some(control) => Ok(Inbound::Control(control)),
+                None => Ok(Inbound::Value(value)),
+            },
+            Err(error) => {
+                let message = frame.freeze();
+                let bad = BadMessage { error, message };
+                Err(io::Error::new(io::ErrorKind::InvalidData, bad))
+            }
+        }
+    }
+
+    /// Deserialize the message, straight from the frame if it can only be
+    /// a message, or after taking it out of its envelope otherwise.
+    fn open<M: DeserializeOwned>(self) -> (Result<M, BadMessage>, Metadata) {
+        match self {
+            // Control frames are handled before they'd get here
+            Inbound::Control(control) => Inbound::Value(control.to_value()).open(),
+            Inbound::Value(value) => {
+                let (value, metadata) = envelope::open(value);
+                let msg = M::deserialize(&value).map_err(|error| BadMessage {
+                    error,
+                    message: serde_json::to_vec(&value).unwrap_or_default().into(),
+                });
+                (msg, metadata)
+            }
+            Inbound::Message(frame) => match serde_json::from_slice(&frame) {
+                Ok(msg) => (Ok(msg), Metadata::default()),
+                Err(error) => {
+                    let message = frame.freeze();
+                    (Err(BadMessage { error, message }), Metadata::default())
+                }
+            },
+        }
+    }
+}
+
+/// Collect the settings for a connection's writer.
+fn writer_options(options: &ServerOptions) -> WriterOptions {
+    WriterOptions {
+        pretty: options.json_pretty,
+        write_timeout: options.write_timeout,
+        keepalive: None,
+        outbox: None,
+        compression: SharedCompression::default(),
+        max_queued: options.max_queued_messages,
+    }
+}
+
+/// Bind a listener to the given address, with the backlog from `options`
+/// if there is one.
+async fn bind_with_options<S: Server + ?Sized>(
+    addr: &str,
+    options: &ServerOptions,
+) -> Result<TcpListener> {
+    let (listener, _addr) = match options.backlog {
+        Some(backlog) => S::bind_with_backlog(addr, backlog).await?,
+        None => S::bind(addr).await?,
+    };
+    Ok(listener)
+}
+
+fn listen_with_backlog(addr: SocketAddr, backlog: u32) -> io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.bind(addr)?;
+    socket.listen(backlog)
+}
+
+/// Pass an error on to the observer, if there is one.
+fn notify_error<ID>(observer: &Option<Arc<dyn ConnectionObserver<ID>>>, id: &ID, err: &Error) {
+    if let Some(observer) = observer {
+        observer.on_error(id, err);
+    }
+}
+
+/// Turn the payload of a caught panic into an error, keeping the panic
+/// message if there is one.
+fn panic_error(payload: Box<dyn Any + Send>) -> Error {
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    };
+    anyhow!("message handler panicked: {message}")
+}
+
+/// Combine a batch of coalesced messages into a single JSON array, emptying
+/// the batch. The messages are already serialized, so they're joined as is.
+fn coalesce(batch: &mut Vec<Bytes>) -> Bytes {
+    if batch.len() == 1 {
+        return batch.remove(0);
+    }
+    let len = batch.iter().map(|json| json.len() + 1).sum::<usize>() + 1;
+    let mut array = BytesMut::with_capacity(len);
+    array.put_u8(b'[');
+    for (i, json) in batch.drain(..).enumerate() {
+        if i > 0 {
+            array.put_u8(b',');
+        }
+        array.extend_from_slice(&json);
+    }
+    array.put_u8(b']');
+    array.freeze()
+}
@@ -0,0 +1,59 @@
+//! A handler for servers that only push messages to their clients.
+
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+
+use super::MessageHandler;
+use crate::types::ServerMessageChannels;
+
+/// A [`MessageHandler`] that ignores every client message, for servers that
+/// only push to their clients, e.g. a price ticker that broadcasts and
+/// never listens. Type parameters are the server's client message, client
+/// ID and state types, in that order.
+///
+/// Messages from the client are still read, so the server notices when the
+/// client leaves, but are dropped once they've been deserialized. With
+/// [`Value`](serde_json::Value) as the client message type, any JSON the
+/// client sends is accepted:
+///
+/// ```
+/// # use scot::{server::{NoOpHandler, SequentialIdAllocator}, Server};
+/// # use serde_json::Value;
+/// struct Ticker {
+///     ids: SequentialIdAllocator,
+/// }
+///
+/// impl Server for Ticker {
+///     type State = SequentialIdAllocator;
+///     type ClientID = usize;
+///     type ClientMessage = Value;
+///     type ClientMessageHandler = NoOpHandler<Value, usize, SequentialIdAllocator>;
+///
+///     fn get_state(&self) -> SequentialIdAllocator {
+///         self.ids.clone()
+///     }
+/// }
+/// ```
+// Never constructed, and `fn` keeps it `Send` whatever the types are
+pub struct NoOpHandler<M, T, S>(PhantomData<fn(M, T, S)>);
+
+#[async_trait]
+impl<M, T, S> MessageHandler for NoOpHandler<M, T, S>
+where
+    M: Send + 'static,
+    T: Send + Sync + 'static,
+    S: Send + 'static,
+{
+    type ClientMessage = M;
+    type ClientID = T;
+    type State = S;
+
+    async fn handle_client_message(
+        _msg: M,
+        _id: &T,
+        _channels: &mut ServerMessageChannels<T>,
+        _state: &mut S,
+    ) {
+    }
+}
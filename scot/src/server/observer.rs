@@ -0,0 +1,69 @@
+//! Observing the lifecycle of connections.
+//!
+//! A [`ConnectionObserver`] bundles callbacks for everything that happens to
+//! a connection, from being accepted until the client leaves. Unlike the
+//! `handle_*` hooks on [`Server`](crate::Server), an observer is a value
+//! rather than part of the server's type, so it can hold its own data (e.g.
+//! counters or a log sink) and be swapped out, for example in tests.
+
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::Error;
+
+use super::ConnectionStats;
+
+/// Callbacks for connection lifecycle events, attached to a server with
+/// [`Server::observer`](crate::Server::observer).
+///
+/// All methods do nothing by default. They are called from the connection's
+/// task, so they should return quickly; anything slow should be handed off
+/// to another task.
+///
+/// Type parameter is the type used for client IDs.
+pub trait ConnectionObserver<ClientID>: Send + Sync {
+    /// Called when a connection is accepted, before the client joins.
+    fn on_accept(&self, _addr: SocketAddr) {}
+
+    /// Called after [`State::on_join`](super::State::on_join) has assigned
+    /// the client its ID.
+    fn on_join(&self, _id: &ClientID) {}
+
+    /// Called for every message received from the client, before it is
+    /// passed to the [`MessageHandler`](super::MessageHandler).
+    fn on_message(&self, _id: &ClientID) {}
+
+    /// Called for every broadcast the connection receives, whether or not
+    /// it's meant for the client, with how many more are queued behind it.
+    /// Once the queue is full (see
+    /// [`ServerOptions::broadcast_capacity`](super::ServerOptions::broadcast_capacity)),
+    /// the connection starts missing broadcasts, so a queue that's often
+    /// close to full means the client (or its handler) is too slow.
+    fn on_broadcast(&self, _id: &ClientID, _queued: usize) {}
+
+    /// Called when the connection fell too far behind and missed `missed`
+    /// broadcasts, before [`Self::on_error`].
+    fn on_broadcast_lag(&self, _id: &ClientID, _missed: u64) {}
+
+    /// Called whenever a single poll of a handler call took `elapsed`,
+    /// longer than [`Server::slow_handler_warn`](crate::Server::slow_handler_warn),
+    /// meaning the handler blocked the executor.
+    fn on_slow_handler(&self, _id: &ClientID, _elapsed: Duration) {}
+
+    /// Called when the client answers a ping sent with
+    /// [`ValueSender::ping`](crate::types::ValueSender::ping), e.g. to
+    /// measure the round trip.
+    fn on_pong(&self, _id: &ClientID) {}
+
+    /// Called after [`State::on_leave`](super::State::on_leave), once the
+    /// client has left, for whatever reason.
+    fn on_leave(&self, _id: &ClientID) {}
+
+    /// Called once the connection has been closed in both directions,
+    /// after [`Self::on_leave`], with the bytes that went over it, e.g. for
+    /// billing.
+    fn on_close(&self, _id: &ClientID, _stats: &ConnectionStats) {}
+
+    /// Called for every error on the connection, before the corresponding
+    /// `handle_*` hook.
+    fn on_error(&self, _id: &ClientID, _err: &Error) {}
+}
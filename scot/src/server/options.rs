@@ -0,0 +1,257 @@
+//! Tuning a server in one place.
+
+use std::time::Duration;
+
+use tokio_util::codec::LengthDelimitedCodec;
+
+use super::BROADCAST_CAPACITY;
+use crate::{codec::FrameCodec, compression::Compression};
+
+/// Settings for running a [`Server`](super::Server), returned by
+/// [`Server::options`](super::Server::options) and read once when the
+/// server starts.
+///
+/// Start from [`ServerOptions::default`], which matches the defaults of
+/// the individual [`Server`](super::Server) methods, and change what's
+/// needed, either through the builder methods or the fields:
+///
+/// ```
+/// # use std::time::Duration;
+/// # use scot::server::ServerOptions;
+/// let options = ServerOptions::default()
+///     .write_timeout(Duration::from_secs(5))
+///     .max_queued_messages(64)
+///     .nodelay(true);
+/// assert_eq!(options.max_queued_messages, Some(64));
+/// ```
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ServerOptions {
+    /// How many broadcasts can be waiting for the slowest connection before
+    /// it starts missing them. Defaults to [`BROADCAST_CAPACITY`].
+    pub broadcast_capacity: usize,
+    /// The listen backlog used by [`Server::start`](super::Server::start)
+    /// and [`Server::start_many`](super::Server::start_many), see
+    /// [`Server::bind_with_backlog`](super::Server::bind_with_backlog).
+    /// Defaults to `None`, which uses the OS default.
+    pub backlog: Option<u32>,
+    /// Whether to disable Nagle's algorithm on accepted TCP connections,
+    /// sending small messages straight away instead of waiting to fill a
+    /// packet. Defaults to `false`.
+    pub nodelay: bool,
+    /// See [`Server::length_field_length`](super::Server::length_field_length).
+    pub length_field_length: usize,
+    /// The longest frame accepted by the default
+    /// [`Server::codec`](super::Server::codec). Longer frames are treated as
+    /// a broken connection. Defaults to `None`, which allows up to 8 MiB.
+    pub max_frame_length: Option<usize>,
+    /// See [`Server::json_pretty`](super::Server::json_pretty).
+    pub json_pretty: bool,
+    /// See [`Server::chunk_size`](super::Server::chunk_size).
+    pub chunk_size: Option<usize>,
+    /// See [`Server::read_buffer_capacity`](super::Server::read_buffer_capacity).
+    pub read_buffer_capacity: Option<usize>,
+    /// See [`Server::write_buffer_capacity`](super::Server::write_buffer_capacity).
+    pub write_buffer_capacity: Option<usize>,
+    /// See [`Server::require_ack`](super::Server::require_ack).
+    pub require_ack: bool,
+    /// See [`Server::ordered_broadcasts`](super::Server::ordered_broadcasts).
+    pub ordered_broadcasts: bool,
+    /// See [`Server::coalesce_window`](super::Server::coalesce_window).
+    pub coalesce_window: Option<Duration>,
+    /// See [`Server::write_timeout`](super::Server::write_timeout).
+    pub write_timeout: Option<Duration>,
+    /// See [`Server::max_queued_messages`](super::Server::max_queued_messages).
+    pub max_queued_messages: Option<usize>,
+    /// See [`Server::max_in_flight`](super::Server::max_in_flight).
+    pub max_in_flight: usize,
+    /// See [`Server::shutdown_timeout`](super::Server::shutdown_timeout).
+    pub shutdown_timeout: Option<Duration>,
+    /// See [`Server::slow_handler_warn`](super::Server::slow_handler_warn).
+    pub slow_handler_warn: Option<Duration>,
+    /// See [`Server::drain_timeout`](super::Server::drain_timeout).
+    pub drain_timeout: Option<Duration>,
+    /// See [`Server::protocol_version`](super::Server::protocol_version).
+    pub protocol_version: u32,
+    /// See [`Server::compression`](super::Server::compression).
+    pub compression: Vec<Compression>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> ServerOptions {
+        ServerOptions {
+            broadcast_capacity: BROADCAST_CAPACITY,
+            backlog: None,
+            nodelay: false,
+            length_field_length: 4,
+            max_frame_length: None,
+            json_pretty: false,
+            chunk_size: None,
+            read_buffer_capacity: None,
+            write_buffer_capacity: None,
+            require_ack: false,
+            ordered_broadcasts: false,
+            coalesce_window: None,
+            write_timeout: None,
+            max_queued_messages: None,
+            max_in_flight: 1,
+            shutdown_timeout: None,
+            slow_handler_warn: None,
+            drain_timeout: None,
+            protocol_version: 0,
+            compression: Vec::new(),
+        }
+    }
+}
+
+impl ServerOptions {
+    /// Set [`ServerOptions::broadcast_capacity`].
+    #[must_use]
+    pub fn broadcast_capacity(mut self, capacity: usize) -> ServerOptions {
+        self.broadcast_capacity = capacity;
+        self
+    }
+
+    /// Set [`ServerOptions::backlog`].
+    #[must_use]
+    pub fn backlog(mut self, backlog: u32) -> ServerOptions {
+        self.backlog = Some(backlog);
+        self
+    }
+
+    /// Set [`ServerOptions::nodelay`].
+    #[must_use]
+    pub fn nodelay(mut self, nodelay: bool) -> ServerOptions {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Set [`ServerOptions::length_field_length`].
+    #[must_use]
+    pub fn length_field_length(mut self, length: usize) -> ServerOptions {
+        self.length_field_length = length;
+        self
+    }
+
+    /// Set [`ServerOptions::max_frame_length`].
+    #[must_use]
+    pub fn max_frame_length(mut self, length: usize) -> ServerOptions {
+        self.max_frame_length = Some(length);
+        self
+    }
+
+    /// Set [`ServerOptions::json_pretty`].
+    #[must_use]
+    pub fn json_pretty(mut self, pretty: bool) -> ServerOptions {
+        self.json_pretty = pretty;
+        self
+    }
+
+    /// Set [`ServerOptions::chunk_size`].
+    #[must_use]
+    pub fn chunk_size(mut self, chunk_size: usize) -> ServerOptions {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Set [`ServerOptions::read_buffer_capacity`].
+    #[must_use]
+    pub fn read_buffer_capacity(mut self, capacity: usize) -> ServerOptions {
+        self.read_buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Set [`ServerOptions::write_buffer_capacity`].
+    #[must_use]
+    pub fn write_buffer_capacity(mut self, capacity: usize) -> ServerOptions {
+        self.write_buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Set [`ServerOptions::require_ack`].
+    #[must_use]
+    pub fn require_ack(mut self, require_ack: bool) -> ServerOptions {
+        self.require_ack = require_ack;
+        self
+    }
+
+    /// Set [`ServerOptions::ordered_broadcasts`].
+    #[must_use]
+    pub fn ordered_broadcasts(mut self, ordered: bool) -> ServerOptions {
+        self.ordered_broadcasts = ordered;
+        self
+    }
+
+    /// Set [`ServerOptions::coalesce_window`].
+    #[must_use]
+    pub fn coalesce_window(mut self, window: Duration) -> ServerOptions {
+        self.coalesce_window = Some(window);
+        self
+    }
+
+    /// Set [`ServerOptions::write_timeout`].
+    #[must_use]
+    pub fn write_timeout(mut self, timeout: Duration) -> ServerOptions {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Set [`ServerOptions::max_queued_messages`].
+    #[must_use]
+    pub fn max_queued_messages(mut self, max: usize) -> ServerOptions {
+        self.max_queued_messages = Some(max);
+        self
+    }
+
+    /// Set [`ServerOptions::max_in_flight`].
+    #[must_use]
+    pub fn max_in_flight(mut self, max: usize) -> ServerOptions {
+        self.max_in_flight = max;
+        self
+    }
+
+    /// Set [`ServerOptions::shutdown_timeout`].
+    #[must_use]
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> ServerOptions {
+        self.shutdown_timeout = Some(timeout);
+        self
+    }
+
+    /// Set [`ServerOptions::slow_handler_warn`].
+    #[must_use]
+    pub fn slow_handler_warn(mut self, threshold: Duration) -> ServerOptions {
+        self.slow_handler_warn = Some(threshold);
+        self
+    }
+
+    /// Set [`ServerOptions::drain_timeout`].
+    #[must_use]
+    pub fn drain_timeout(mut self, timeout: Duration) -> ServerOptions {
+        self.drain_timeout = Some(timeout);
+        self
+    }
+
+    /// Set [`ServerOptions::protocol_version`].
+    #[must_use]
+    pub fn protocol_version(mut self, version: u32) -> ServerOptions {
+        self.protocol_version = version;
+        self
+    }
+
+    /// Set [`ServerOptions::compression`].
+    #[must_use]
+    pub fn compression(mut self, compression: Vec<Compression>) -> ServerOptions {
+        self.compression = compression;
+        self
+    }
+
+    /// The length-delimited codec described by these options.
+    pub(crate) fn length_delimited_codec(&self) -> FrameCodec {
+        let mut builder = LengthDelimitedCodec::builder();
+        builder.length_field_length(self.length_field_length);
+        if let Some(max_frame_length) = self.max_frame_length {
+            builder.max_frame_length(max_frame_length);
+        }
+        builder.new_codec().into()
+    }
+}
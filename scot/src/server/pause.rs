@@ -0,0 +1,62 @@
+//! Pausing and resuming accepting connections while the server is running.
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// A handle to pause accepting new connections, e.g. during maintenance,
+/// and to resume it later, without restarting the server. Pass it to
+/// [`Server::start_with_pause`](super::Server::start_with_pause).
+///
+/// While paused, the server doesn't accept any connections, so new clients
+/// wait in the listen backlog until accepting resumes, or until they give
+/// up. Clients that are already connected are served as usual. Unlike
+/// [draining](super::Server::start_with_drain), pausing can be undone.
+///
+/// The handle can be cloned and sent to other tasks, and all clones control
+/// the same server.
+///
+/// ```
+/// # use scot::server::PauseHandle;
+/// let pause = PauseHandle::new();
+/// let remote = pause.clone();
+/// remote.pause();
+/// assert!(pause.is_paused());
+/// remote.resume();
+/// assert!(!pause.is_paused());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PauseHandle {
+    paused: Arc<watch::Sender<bool>>,
+}
+
+impl PauseHandle {
+    /// Create a handle that starts out accepting connections.
+    #[must_use]
+    pub fn new() -> PauseHandle {
+        PauseHandle::default()
+    }
+
+    /// Stop accepting new connections. Does nothing if already paused.
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    /// Resume accepting new connections. Does nothing if not paused.
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    /// Returns whether accepting new connections is paused.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Wait until accepting is paused, if `paused`, or resumed otherwise.
+    pub(crate) async fn until(&self, paused: bool) {
+        let mut receiver = self.paused.subscribe();
+        // Can't fail, as `self` holds the sender
+        let _ = receiver.wait_for(|&is_paused| is_paused == paused).await;
+    }
+}
@@ -7,7 +7,14 @@
 //! client. For sending a message back to the client whose message you are
 //! receiving, use the `channels.response_sender` field.
 
-use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use serde::{ser, Deserialize, Serialize, Serializer};
 
 /// Enum representing who the server should send a given message to.
 /// The type parameter `T` should be the type used for client IDs.
@@ -17,11 +24,39 @@ use serde::{Deserialize, Serialize};
 ///
 /// Sending a message with recipients [`Recipients::MultipleRecipients`] will
 /// forward the message to all clients whose ID matches one in the recipients
-/// list.
+/// list. Checking the list takes time proportional to its length, in every
+/// connection, so for large lists of IDs that implement [`Eq`] and [`Hash`],
+/// use [`Recipients::set`] instead. Every connection gets its own copy of
+/// the list, so when sending to many clients, use [`Recipients::shared`],
+/// which shares a single copy between them.
+///
+/// Sending with recipients [`Recipients::Tagged`] will forward it to all
+/// clients whose connection currently has the given tag, see
+/// [`ServerMessageChannels::tags`](crate::types::ServerMessageChannels::tags).
+///
+/// Sending with recipients [`Recipients::Matching`] will forward it to all
+/// clients for which a predicate holds at the time the message is delivered.
 ///
 /// Sending with recipients [`Recipients::Everyone`] will forward it to all
 /// clients.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+///
+/// Sending with recipients [`Recipients::EveryoneExcept`] will forward it
+/// to all clients except the ones listed, e.g. everyone but the sender,
+/// without needing to know who else is connected.
+///
+/// # Snapshots and delivery-time checks
+///
+/// The ID-based variants are a snapshot: the recipients are fixed when the
+/// message is sent. If, say, the members of a room change between sending
+/// and delivery, a client that just left may still receive the message, and
+/// one that just joined won't. [`Recipients::EveryoneExcept`] only fixes
+/// who's left out, so clients that join in between do receive it.
+/// [`Recipients::Tagged`] and
+/// [`Recipients::Matching`] are checked by each connection when the message
+/// is delivered instead, so they follow such changes, at the cost of
+/// evaluating the check once per connection rather than once per message.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
 pub enum Recipients<T> {
     /// For sending to a single other client.
     SingleRecipient {
@@ -33,16 +68,273 @@ pub enum Recipients<T> {
         /// The list of client IDs to send the message to.
         recipients: Vec<T>,
     },
+    /// Like [`Recipients::MultipleRecipients`], but the list is shared
+    /// between connections instead of copied for each one. Created with
+    /// [`Recipients::shared`].
+    SharedRecipients {
+        /// The list of client IDs to send the message to.
+        recipients: Arc<[T]>,
+    },
+    /// For sending to multiple other clients, checking whether a client is
+    /// included in constant time. Created with [`Recipients::set`].
+    ///
+    /// Serializes like the list in [`Recipients::MultipleRecipients`], but
+    /// can't be deserialized.
+    #[serde(skip_deserializing)]
+    RecipientSet(RecipientSet<T>),
+    /// For sending to all clients with a given tag, e.g. a role or a room
+    /// name. Whether a client is included is decided by its own connection
+    /// when the message is delivered, so no list of IDs is needed.
+    Tagged {
+        /// The tag that recipients must have.
+        tag: String,
+    },
+    /// For sending to all clients for which a predicate holds, checked by
+    /// each connection as the message is delivered. Created with
+    /// [`Recipients::matching`].
+    ///
+    /// Can't be serialized or deserialized.
+    #[serde(skip_deserializing)]
+    Matching(RecipientFilter<T>),
     /// For sending to all clients.
     Everyone,
+    /// For sending to all clients except the ones listed, checked by each
+    /// connection as the message is delivered. Created with
+    /// [`Recipients::everyone_except`]. Checking the list takes time
+    /// proportional to its length, not to the number of clients.
+    EveryoneExcept {
+        /// The client IDs not to send the message to.
+        excluded: Vec<T>,
+    },
+}
+
+impl<T> Recipients<T> {
+    /// Returns whether these recipients are known not to include anyone,
+    /// i.e. an empty list or set of IDs. Broadcasts to no one are skipped by
+    /// [`ServerMessageChannels::try_broadcast`](crate::types::ServerMessageChannels::try_broadcast).
+    ///
+    /// ```
+    /// # use scot::server::Recipients;
+    /// assert!(Recipients::<usize>::MultipleRecipients { recipients: vec![] }.is_empty());
+    /// assert!(Recipients::everyone_but(&1, [1]).is_empty());
+    /// assert!(!Recipients::<usize>::Everyone.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Recipients::MultipleRecipients { recipients } => recipients.is_empty(),
+            Recipients::SharedRecipients { recipients } => recipients.is_empty(),
+            Recipients::RecipientSet(set) => set.ids.is_empty(),
+            Recipients::SingleRecipient { .. }
+            | Recipients::Tagged { .. }
+            | Recipients::Matching(_)
+            | Recipients::Everyone
+            | Recipients::EveryoneExcept { .. } => false,
+        }
+    }
 }
 
 impl<T: PartialEq> Recipients<T> {
+    /// Returns whether a message sent with these recipients should be
+    /// forwarded to the client with the given ID and connection tags.
+    pub fn contains(&self, client_id: &T, tags: &HashSet<String>) -> bool {
+        match self {
+            Recipients::Everyone => true,
+            Recipients::SingleRecipient { recipient } => recipient == client_id,
+            Recipients::MultipleRecipients { recipients } => recipients.contains(client_id),
+            Recipients::SharedRecipients { recipients } => recipients.contains(client_id),
+            Recipients::RecipientSet(set) => (set.lookup)(&set.ids, client_id),
+            Recipients::Tagged { tag } => tags.contains(tag),
+            Recipients::Matching(filter) => (filter.predicate)(client_id),
+            Recipients::EveryoneExcept { excluded } => !excluded.contains(client_id),
+        }
+    }
+
     /// Creates a [`Recipients`] object representing all except one of the clients.
     /// To use this function, `T` must implement [`PartialEq`].
+    ///
+    /// This needs the IDs of every client. To leave out clients without
+    /// knowing who else is connected, use [`Recipients::everyone_except`].
     pub fn everyone_but(client_id: &T, clients: impl IntoIterator<Item = T>) -> Recipients<T> {
         Recipients::MultipleRecipients {
             recipients: clients.into_iter().filter(|x| x != client_id).collect(),
         }
     }
 }
+
+impl<T> Recipients<T> {
+    /// Creates a [`Recipients::EveryoneExcept`] sending to every client
+    /// except the given ones, e.g. everyone but the sender of a chat
+    /// message:
+    ///
+    /// ```
+    /// # use std::collections::HashSet;
+    /// # use scot::server::Recipients;
+    /// let recipients = Recipients::everyone_except([1]);
+    /// assert!(!recipients.contains(&1, &HashSet::new()));
+    /// assert!(recipients.contains(&2, &HashSet::new()));
+    /// ```
+    pub fn everyone_except(excluded: impl IntoIterator<Item = T>) -> Recipients<T> {
+        Recipients::EveryoneExcept {
+            excluded: excluded.into_iter().collect(),
+        }
+    }
+
+    /// Creates a [`Recipients::SharedRecipients`] containing the given IDs.
+    /// Cloning it, as happens once per connection when broadcasting, only
+    /// copies a pointer, so prefer this over
+    /// [`Recipients::MultipleRecipients`] for long lists of IDs.
+    ///
+    /// ```
+    /// # use std::collections::HashSet;
+    /// # use scot::server::Recipients;
+    /// let recipients = Recipients::shared(vec![1, 2, 3]);
+    /// assert!(recipients.contains(&2, &HashSet::new()));
+    /// assert!(!recipients.contains(&4, &HashSet::new()));
+    /// ```
+    pub fn shared(clients: impl Into<Arc<[T]>>) -> Recipients<T> {
+        Recipients::SharedRecipients {
+            recipients: clients.into(),
+        }
+    }
+
+    /// Creates a [`Recipients::Matching`] sending to every client whose ID
+    /// satisfies `predicate` when the message is delivered. The predicate
+    /// typically consults shared state, e.g. the current members of a room:
+    ///
+    /// ```
+    /// # use std::{collections::HashSet, sync::{Arc, Mutex}};
+    /// # use scot::server::Recipients;
+    /// let room = Arc::new(Mutex::new(HashSet::from([1, 2])));
+    /// let members = room.clone();
+    /// let recipients = Recipients::matching(move |id| members.lock().unwrap().contains(id));
+    ///
+    /// // Someone joins the room after the message was sent
+    /// room.lock().unwrap().insert(3);
+    /// assert!(recipients.contains(&3, &HashSet::new()));
+    /// ```
+    ///
+    /// The predicate runs on every connection's task, so it should be quick
+    /// and must not block for long.
+    pub fn matching<F>(predicate: F) -> Recipients<T>
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        Recipients::Matching(RecipientFilter {
+            predicate: Arc::new(predicate),
+        })
+    }
+}
+
+impl<T: Eq + Hash> Recipients<T> {
+    /// Creates a [`Recipients::RecipientSet`] containing the given IDs.
+    /// Prefer this over [`Recipients::MultipleRecipients`] when sending to
+    /// many clients at once.
+    ///
+    /// ```
+    /// # use std::collections::HashSet;
+    /// # use scot::server::Recipients;
+    /// let recipients = Recipients::set([1, 2, 3]);
+    /// assert!(recipients.contains(&2, &HashSet::new()));
+    /// assert!(!recipients.contains(&4, &HashSet::new()));
+    /// assert_eq!(recipients, Recipients::set([3, 2, 1]));
+    /// ```
+    pub fn set(clients: impl IntoIterator<Item = T>) -> Recipients<T> {
+        Recipients::RecipientSet(RecipientSet {
+            ids: clients.into_iter().collect(),
+            lookup: |ids, id| ids.contains(id),
+        })
+    }
+}
+
+/// A set of client IDs, see [`Recipients::RecipientSet`].
+#[derive(Clone)]
+pub struct RecipientSet<T> {
+    ids: HashSet<T>,
+    // Looking up IDs needs `T: Hash`, which isn't required of client IDs in
+    // general, so the lookup is chosen when the set is created
+    lookup: fn(&HashSet<T>, &T) -> bool,
+}
+
+impl<T> RecipientSet<T> {
+    /// Iterate over the IDs in the set.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.ids.iter()
+    }
+}
+
+impl<T: PartialEq> PartialEq for RecipientSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ids.len() == other.ids.len()
+            && self.ids.iter().all(|id| (other.lookup)(&other.ids, id))
+    }
+}
+
+impl<T: Eq> Eq for RecipientSet<T> {}
+
+impl<T: Hash> Hash for RecipientSet<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Sets are unordered, so combine the hashes of the IDs in a way that
+        // doesn't depend on the order they're visited in
+        let combined = self.ids.iter().fold(0u64, |combined, id| {
+            let mut hasher = DefaultHasher::new();
+            id.hash(&mut hasher);
+            combined.wrapping_add(hasher.finish())
+        });
+        self.ids.len().hash(state);
+        combined.hash(state);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RecipientSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(&self.ids).finish()
+    }
+}
+
+impl<T: Serialize> Serialize for RecipientSet<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.ids)
+    }
+}
+
+/// A predicate deciding who receives a message, see
+/// [`Recipients::Matching`].
+///
+/// Two filters are equal only if they are clones of each other.
+pub struct RecipientFilter<T> {
+    predicate: Arc<dyn Fn(&T) -> bool + Send + Sync>,
+}
+
+impl<T> Clone for RecipientFilter<T> {
+    fn clone(&self) -> Self {
+        RecipientFilter {
+            predicate: self.predicate.clone(),
+        }
+    }
+}
+
+impl<T> PartialEq for RecipientFilter<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.predicate, &other.predicate)
+    }
+}
+
+impl<T> Eq for RecipientFilter<T> {}
+
+impl<T> Hash for RecipientFilter<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.predicate).cast::<()>().hash(state);
+    }
+}
+
+impl<T> fmt::Debug for RecipientFilter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RecipientFilter(..)")
+    }
+}
+
+impl<T> Serialize for RecipientFilter<T> {
+    fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+        Err(ser::Error::custom("recipient filters can't be serialized"))
+    }
+}
@@ -0,0 +1,44 @@
+//! Running servers of different types through a trait object.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::net::TcpListener;
+
+use super::Server;
+
+/// The run phase of a [`Server`], with its associated types erased, so that
+/// servers of different types can be held as `Box<dyn ServerRunner>`, e.g.
+/// to pick one at runtime. Every [`Server`] implements it.
+///
+/// The methods are named differently from their [`Server`] counterparts, so
+/// that having both traits in scope doesn't make calls ambiguous.
+///
+/// ```no_run
+/// # use scot::server::ServerRunner;
+/// # async fn run(servers: Vec<Box<dyn ServerRunner>>) -> anyhow::Result<()> {
+/// for server in &servers {
+///     server.run("localhost:31194").await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[async_trait]
+pub trait ServerRunner: Send + Sync {
+    /// Start the server on the given address, see [`Server::start`].
+    async fn run(&self, addr: &str) -> Result<()>;
+
+    /// Start the server with a [`TcpListener`], see
+    /// [`Server::start_with_listener`].
+    async fn run_with_listener(&self, listener: &TcpListener) -> Result<()>;
+}
+
+#[async_trait]
+impl<S: Server + Send + Sync> ServerRunner for S {
+    async fn run(&self, addr: &str) -> Result<()> {
+        self.start(addr).await
+    }
+
+    async fn run_with_listener(&self, listener: &TcpListener) -> Result<()> {
+        self.start_with_listener(listener).await
+    }
+}
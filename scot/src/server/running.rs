@@ -0,0 +1,108 @@
+//! A server running in the background, started with
+//! [`Server::launch`](super::Server::launch).
+
+use std::net::SocketAddr;
+
+use anyhow::{anyhow, Result};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use super::Connections;
+
+/// A handle to a server accepting connections in a background task, to
+/// find out where it's listening, look at who's connected, and stop it.
+///
+/// Dropping the handle leaves the server running, with no way to stop it
+/// other than shutting down the runtime.
+///
+/// Type parameter is the type used for client IDs.
+///
+/// ```no_run
+/// # use scot::Server;
+/// # async fn run<S: Server + Send + Sync + 'static>(server: S) -> anyhow::Result<()> {
+/// let running = server.launch("localhost:31194").await?;
+/// println!("listening on {}", running.local_addr());
+/// // ...
+/// running.drain().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RunningServer<T> {
+    local_addr: SocketAddr,
+    connections: Connections<T>,
+    shutdown: CancellationToken,
+    drain: CancellationToken,
+    task: JoinHandle<Result<()>>,
+}
+
+impl<T> RunningServer<T> {
+    pub(crate) fn new(
+        local_addr: SocketAddr,
+        connections: Connections<T>,
+        shutdown: CancellationToken,
+        drain: CancellationToken,
+        task: JoinHandle<Result<()>>,
+    ) -> RunningServer<T> {
+        RunningServer {
+            local_addr,
+            connections,
+            shutdown,
+            drain,
+            task,
+        }
+    }
+
+    /// Returns the address the server is listening on, e.g. to find out
+    /// which port it was given when launched on port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the clients currently connected to the server.
+    pub fn connections(&self) -> &Connections<T> {
+        &self.connections
+    }
+
+    /// Stop accepting connections and disconnect every client, see
+    /// [`Server::start_with_shutdown`](super::Server::start_with_shutdown).
+    ///
+    /// # Errors
+    ///
+    /// Fails if the task accepting connections panicked.
+    pub async fn shutdown(self) -> Result<()> {
+        self.shutdown.cancel();
+        self.wait().await
+    }
+
+    /// Stop accepting connections and wait for every client to leave, see
+    /// [`Server::start_with_drain`](super::Server::start_with_drain).
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`RunningServer::shutdown`].
+    pub async fn drain(self) -> Result<()> {
+        self.drain.cancel();
+        self.wait().await
+    }
+
+    /// Wait for the server to stop, which it never does by itself: failing
+    /// to accept a connection is only reported to
+    /// [`Server::handle_setup_err`](super::Server::handle_setup_err).
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`RunningServer::shutdown`].
+    pub async fn wait(self) -> Result<()> {
+        self.task
+            .await
+            .map_err(|e| anyhow!("server task failed: {e}"))?
+    }
+}
+
+impl<T: PartialEq> RunningServer<T> {
+    /// Returns the number of connected clients, see [`Connections::count`].
+    pub fn connection_count(&self) -> usize {
+        self.connections.count()
+    }
+}
@@ -5,11 +5,21 @@
 //! [`State`], and wrap it in an [`Arc<Mutex<T>>`]. In this case, the impl
 //! for the outer type will be automatically generated. Another option, for
 //! applications that want more fine-grained access to data, is to have
-//! multiple fields, each of type [`Arc<Mutex<T>>], or even a field
-//! whose type is [`Vec<Arc<Mutex<T>>>].
+//! multiple fields, each of type [`Arc<Mutex<T>>`], or even a field
+//! whose type is [`Vec<Arc<Mutex<T>>>`].
+//!
+//! For state that handlers mostly read, [`Arc<RwLock<T>>`](std::sync::RwLock)
+//! works the same way: joining and leaving take the write lock, while
+//! message handlers can take read locks and run side by side. There's no
+//! impl for [`tokio::sync::RwLock`], since [`State`]'s methods are called
+//! from async code without awaiting, where it could only be locked by
+//! blocking the runtime. Handlers holding a lock across an `.await` need
+//! their own async lock in a separate field instead.
 
 use std::sync::Arc;
 
+use serde_json::Value;
+
 /// Trait for server state type.
 ///
 /// Type parameter is the type used for client IDs.
@@ -18,8 +28,45 @@ pub trait State {
     type ClientID;
 
     /// Function to be called when a new client connects. Must return a new,
-    /// unique ID.
+    /// unique ID, e.g. one generated by an
+    /// [`IdAllocator`](super::IdAllocator).
     fn on_join(&mut self) -> Self::ClientID;
+
+    /// Function to be called when a new client connects, which can refuse
+    /// the client, e.g. because the server is full. A rejected client is
+    /// disconnected right away, after being sent the reason's message if
+    /// it has one, and [`State::on_leave`] isn't called for it.
+    ///
+    /// Defaults to accepting every client with [`State::on_join`].
+    fn try_on_join(&mut self) -> Result<Self::ClientID, RejectReason> {
+        Ok(self.on_join())
+    }
+
+    /// Function to be called when a client disconnects, including when the
+    /// connection is dropped because of an error. Does nothing by default.
+    fn on_leave(&mut self, _id: &Self::ClientID) {}
+}
+
+/// Why a client was refused by [`State::try_on_join`].
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct RejectReason {
+    /// A message to send to the client before disconnecting it.
+    pub message: Option<Value>,
+}
+
+impl RejectReason {
+    /// Reject a client without telling it why.
+    pub fn new() -> RejectReason {
+        RejectReason::default()
+    }
+
+    /// Reject a client, sending it the given message first.
+    pub fn with_message(message: Value) -> RejectReason {
+        RejectReason {
+            message: Some(message),
+        }
+    }
 }
 
 impl<T> State for Arc<std::sync::Mutex<T>>
@@ -31,8 +78,18 @@ where
     fn on_join(&mut self) -> Self::ClientID {
         self.lock().unwrap().on_join()
     }
+
+    fn try_on_join(&mut self) -> Result<Self::ClientID, RejectReason> {
+        self.lock().unwrap().try_on_join()
+    }
+
+    fn on_leave(&mut self, id: &Self::ClientID) {
+        self.lock().unwrap().on_leave(id);
+    }
 }
 
+/// Only with the `parking_lot` feature, which is on by default.
+#[cfg(feature = "parking_lot")]
 impl<T> State for Arc<parking_lot::Mutex<T>>
 where
     T: State,
@@ -42,4 +99,52 @@ where
     fn on_join(&mut self) -> Self::ClientID {
         self.lock().on_join()
     }
+
+    fn try_on_join(&mut self) -> Result<Self::ClientID, RejectReason> {
+        self.lock().try_on_join()
+    }
+
+    fn on_leave(&mut self, id: &Self::ClientID) {
+        self.lock().on_leave(id);
+    }
+}
+
+impl<T> State for Arc<std::sync::RwLock<T>>
+where
+    T: State,
+{
+    type ClientID = T::ClientID;
+
+    fn on_join(&mut self) -> Self::ClientID {
+        self.write().unwrap().on_join()
+    }
+
+    fn try_on_join(&mut self) -> Result<Self::ClientID, RejectReason> {
+        self.write().unwrap().try_on_join()
+    }
+
+    fn on_leave(&mut self, id: &Self::ClientID) {
+        self.write().unwrap().on_leave(id);
+    }
+}
+
+/// Only with the `parking_lot` feature, which is on by default.
+#[cfg(feature = "parking_lot")]
+impl<T> State for Arc<parking_lot::RwLock<T>>
+where
+    T: State,
+{
+    type ClientID = T::ClientID;
+
+    fn on_join(&mut self) -> Self::ClientID {
+        self.write().on_join()
+    }
+
+    fn try_on_join(&mut self) -> Result<Self::ClientID, RejectReason> {
+        self.write().try_on_join()
+    }
+
+    fn on_leave(&mut self, id: &Self::ClientID) {
+        self.write().on_leave(id);
+    }
 }
@@ -0,0 +1,81 @@
+//! Counting the bytes that go over connections.
+//!
+//! Every connection counts the frames it reads and writes, both for itself
+//! (see [`ServerMessageChannels::stats`](crate::types::ServerMessageChannels::stats))
+//! and towards the totals for the whole server (see
+//! [`Connections::stats`](super::Connections::stats)). Frames are counted
+//! as they go over the wire, i.e. after compression, but without the
+//! length prefix or WebSocket header that delimits them.
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use futures::prelude::*;
+use tokio_util::bytes::{Bytes, BytesMut};
+
+/// Byte counters for a connection, or for every connection of a server.
+/// Cloning gives another handle to the same counters, which keep counting
+/// for as long as the connection is open.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionStats(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    read: AtomicU64,
+    written: AtomicU64,
+}
+
+impl ConnectionStats {
+    /// Returns how many bytes have been read from the client.
+    pub fn bytes_read(&self) -> u64 {
+        self.0.read.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many bytes have been handed to the connection to be
+    /// written to the client.
+    pub fn bytes_written(&self) -> u64 {
+        self.0.written.load(Ordering::Relaxed)
+    }
+
+    fn add_read(&self, bytes: usize) {
+        self.0.read.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn add_written(&self, bytes: usize) {
+        self.0.written.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+/// Count the frames read from and written to a connection, towards both
+/// the connection's stats and the server's totals.
+pub(crate) fn count<R, W>(
+    frames: R,
+    frame_sink: W,
+    connection: &ConnectionStats,
+    total: &ConnectionStats,
+) -> (
+    impl Stream<Item = io::Result<BytesMut>> + Send + Unpin + 'static,
+    impl Sink<Bytes, Error = io::Error> + Send + Unpin + 'static,
+)
+where
+    R: Stream<Item = io::Result<BytesMut>> + Send + Unpin + 'static,
+    W: Sink<Bytes, Error = io::Error> + Send + Unpin + 'static,
+{
+    let (read, total_read) = (connection.clone(), total.clone());
+    let frames = frames.inspect_ok(move |frame| {
+        read.add_read(frame.len());
+        total_read.add_read(frame.len());
+    });
+    let (written, total_written) = (connection.clone(), total.clone());
+    let frame_sink = frame_sink.with(move |frame: Bytes| {
+        written.add_written(frame.len());
+        total_written.add_written(frame.len());
+        future::ready(Ok::<_, io::Error>(frame))
+    });
+    (frames, frame_sink)
+}
@@ -0,0 +1,19 @@
+//! Locking shared data, without depending on `parking_lot`.
+
+use std::sync::{self, MutexGuard, PoisonError};
+
+/// A [`std::sync::Mutex`] that carries on after a panic while it was held,
+/// like `parking_lot`'s. Everything the crate shares this way is left in a
+/// consistent state between statements, so there's nothing to recover.
+#[derive(Debug, Default)]
+pub(crate) struct Mutex<T>(sync::Mutex<T>);
+
+impl<T> Mutex<T> {
+    pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
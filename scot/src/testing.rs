@@ -0,0 +1,197 @@
+//! Utilities for testing servers and clients without any networking.
+//!
+//! Connections made through a [`TestServer`] run over an in-memory
+//! [`DuplexStream`] instead of a socket, so no ports need to be bound and
+//! tests don't depend on the loopback interface. Each connection is handled
+//! exactly as one accepted from a [`TcpListener`](tokio::net::TcpListener)
+//! would be, except that a [`ConnectionObserver`](crate::server::ConnectionObserver)
+//! sees the unspecified address `0.0.0.0:0` when it's accepted.
+//!
+//! Only available with the `testing` feature.
+//!
+//! ```
+//! # use async_trait::async_trait;
+//! # use scot::{server::{MessageHandler, SequentialIdAllocator}, types::ServerMessageChannels, Server};
+//! # use scot::testing::TestServer;
+//! # struct EchoHandler;
+//! # #[async_trait]
+//! # impl MessageHandler for EchoHandler {
+//! #     type ClientMessage = String;
+//! #     type ClientID = usize;
+//! #     type State = SequentialIdAllocator;
+//! #     async fn handle_client_message(
+//! #         msg: String,
+//! #         _id: &usize,
+//! #         channels: &mut ServerMessageChannels<usize>,
+//! #         _state: &mut SequentialIdAllocator,
+//! #     ) {
+//! #         channels.respond(&msg).await.unwrap();
+//! #     }
+//! # }
+//! # struct EchoServer;
+//! # impl Server for EchoServer {
+//! #     type State = SequentialIdAllocator;
+//! #     type ClientID = usize;
+//! #     type ClientMessage = String;
+//! #     type ClientMessageHandler = EchoHandler;
+//! #     fn get_state(&self) -> SequentialIdAllocator {
+//! #         SequentialIdAllocator::new()
+//! #     }
+//! # }
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let server = TestServer::new(EchoServer);
+//! let mut client = server.connect().await?;
+//! client.send(&"hello").await?;
+//! assert_eq!(client.recv::<String>().await?, Some("hello".to_string()));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr},
+};
+
+use anyhow::Result;
+use futures::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tokio::{
+    io::{duplex, DuplexStream},
+    sync::broadcast,
+    task::JoinHandle,
+};
+use tokio_util::{bytes::Bytes, codec::Framed};
+
+use crate::{
+    codec::FrameCodec,
+    server::{Connections, ServerOptions},
+    types::{BroadcastReceiver, BroadcastSender},
+    Client, Server,
+};
+
+/// How many bytes can be in flight in each direction of a connection.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// A server accepting in-memory connections. All connections share the
+/// same broadcast channel and [`Connections`], as if they had been accepted
+/// by the same listener.
+pub struct TestServer<S: Server> {
+    server: S,
+    options: ServerOptions,
+    broadcast_sender: BroadcastSender<S::ClientID>,
+    connections: Connections<S::ClientID>,
+    // Keeps the broadcast channel open while no clients are connected, like
+    // the listener loop does
+    _broadcast_receiver: BroadcastReceiver<S::ClientID>,
+}
+
+impl<S: Server + Sync> TestServer<S> {
+    /// Wrap a server for testing.
+    pub fn new(server: S) -> TestServer<S> {
+        let options = server.options();
+        let (broadcast_sender, broadcast_receiver) = broadcast::channel(options.broadcast_capacity);
+        TestServer {
+            server,
+            options,
+            broadcast_sender,
+            connections: Connections::default(),
+            _broadcast_receiver: broadcast_receiver,
+        }
+    }
+
+    /// Open a connection to the server, returning a handle for sending and
+    /// receiving raw messages.
+    pub async fn connect(&self) -> Result<TestClient> {
+        let stream = self.open().await?;
+        Ok(TestClient {
+            framed: Framed::new(stream, self.server.codec().chunked(self.options.chunk_size)),
+        })
+    }
+
+    /// Connect a [`Client`] to the server, running it in a task until it
+    /// disconnects.
+    pub async fn connect_client<C>(&self, client: C) -> Result<JoinHandle<Result<()>>>
+    where
+        C: Client + Send + Sync + 'static,
+    {
+        let stream = self.open().await?;
+        Ok(tokio::spawn(async move {
+            client.start_with_stream(stream).await
+        }))
+    }
+
+    /// The clients currently connected to the server.
+    pub fn connections(&self) -> &Connections<S::ClientID> {
+        &self.connections
+    }
+
+    /// Hand one end of a new in-memory stream to the server, returning the
+    /// other end.
+    async fn open(&self) -> Result<DuplexStream> {
+        let (client_end, server_end) = duplex(BUFFER_SIZE);
+        let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0));
+        self.server
+            .__next_client::<crate::private::InternalFlag, _>(
+                server_end,
+                addr,
+                (self.server.codec(), self.server.codec()),
+                &self.options,
+                &self.broadcast_sender,
+                &self.connections,
+            )
+            .await?;
+        Ok(client_end)
+    }
+}
+
+/// Connect a [`Client`] to a server in memory, see [`TestServer`].
+///
+/// Returns the server, so that more clients can be connected, along with the
+/// handle of the task running the client.
+pub async fn connect_in_memory<S, C>(
+    server: S,
+    client: C,
+) -> Result<(TestServer<S>, JoinHandle<Result<()>>)>
+where
+    S: Server + Sync,
+    C: Client + Send + Sync + 'static,
+{
+    let server = TestServer::new(server);
+    let client = server.connect_client(client).await?;
+    Ok((server, client))
+}
+
+/// The client end of an in-memory connection, for driving a server with
+/// raw messages.
+pub struct TestClient {
+    framed: Framed<DuplexStream, FrameCodec>,
+}
+
+impl TestClient {
+    /// Send a message to the server.
+    pub async fn send<M: Serialize + ?Sized>(&mut self, message: &M) -> io::Result<()> {
+        let bytes = serde_json::to_vec(message)?;
+        self.framed.send(Bytes::from(bytes)).await
+    }
+
+    /// Receive the next message from the server, or `None` once the server
+    /// has closed the connection.
+    pub async fn recv<M: DeserializeOwned>(&mut self) -> io::Result<Option<M>> {
+        match self.framed.next().await {
+            Some(frame) => Ok(Some(serde_json::from_slice(&frame?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Receive the next message from the server as a JSON value.
+    pub async fn recv_value(&mut self) -> io::Result<Option<Value>> {
+        self.recv().await
+    }
+
+    /// Close the connection, as a client disconnecting would.
+    pub async fn close(mut self) -> io::Result<()> {
+        self.framed.close().await
+    }
+}
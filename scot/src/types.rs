@@ -1,35 +1,534 @@
 //! Various useful types, mostly relating to sending messages between the
 //! server and the client.
 
+use std::{
+    collections::HashSet,
+    io,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use futures::{channel::mpsc, future::BoxFuture, prelude::*, stream::FuturesUnordered};
+use serde::Serialize;
 use serde_json::Value;
 use tokio::{
-    net::TcpStream,
+    io::{AsyncRead, AsyncWrite},
     sync::broadcast::{Receiver, Sender},
+    time::{self, Sleep},
 };
 use tokio_serde::{formats::Json, Framed};
-use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
+use tokio_util::{bytes::Bytes, sync::CancellationToken};
+
+use crate::{
+    compression::{Compression, SharedCompression},
+    envelope::{self, Control},
+    server::{ConnectionId, ConnectionStats, Connections, Recipients, SharedOutbox},
+    sync::Mutex,
+};
+
+/// The channel broadcasts are sent on, carrying each message along with
+/// its recipients. See [`ServerMessageChannels::try_broadcast`] for
+/// sending typed messages.
+///
+/// Messages are serialized to JSON once, before they're sent, and every
+/// connection writes the same shared bytes to its client instead of
+/// serializing the message again.
+pub(crate) type BroadcastSender<T> = Sender<(Bytes, Recipients<T>)>;
+pub(crate) type BroadcastReceiver<T> = Receiver<(Bytes, Recipients<T>)>;
+
+/// Decodes JSON values from a stream of frames, e.g. a
+/// [`FramedRead`](tokio_util::codec::FramedRead) over
+/// the read half of a connection.
+pub(crate) type MessageReceiver<F> = Framed<F, Value, Value, Json<Value, Value>>;
 
-use crate::server::Recipients;
+/// A bidirectional byte stream that a connection can run over, such as a
+/// [`tokio::net::TcpStream`]. Implemented for every type that meets the
+/// requirements.
+pub trait Transport: AsyncRead + AsyncWrite + Send + Unpin + 'static {}
 
-pub(crate) type BroadcastSender<T> = Sender<(Value, Recipients<T>)>;
-pub(crate) type BroadcastReceiver<T> = Receiver<(Value, Recipients<T>)>;
+impl<T> Transport for T where T: AsyncRead + AsyncWrite + Send + Unpin + 'static {}
 
-pub(crate) type MessageReceiver<T> =
-    Framed<FramedRead<TcpStream, LengthDelimitedCodec>, T, T, Json<T, T>>;
-pub(crate) type MessageSender<T> =
-    Framed<FramedWrite<TcpStream, LengthDelimitedCodec>, T, T, Json<T, T>>;
+/// How many outgoing messages can be queued for a connection before senders
+/// have to wait for the writer to catch up.
+const OUTGOING_CAPACITY: usize = 32;
 
 /// A channel that can be used to send serde JSON values.
 ///
 /// This mainly shows up in internal code, but is also used in
 /// [`crate::client::InputHandler`] as the type of the channel
 /// through which the client can send messages to the server.
-pub type ValueSender = MessageSender<Value>;
+///
+/// Messages are sent with [`futures::SinkExt`], and are written to the
+/// connection by a dedicated writer task. A `ValueSender` can be cloned to
+/// send from several tasks at once; each connection still only has a single
+/// writer, so frames are never interleaved on the wire.
+///
+/// [`SinkExt::send`](futures::SinkExt::send) returns once the message is
+/// queued. The writer writes out everything that is queued and only then
+/// flushes the connection, so messages sent in quick succession share a
+/// single write, and there is no need to flush manually.
+///
+/// Closing any clone, e.g. with [`SinkExt::close`](futures::SinkExt::close),
+/// closes the channel for all of them. Messages that were already queued
+/// are still written before the write half of the connection is shut down.
+///
+/// On the server, a [write timeout](crate::Server::write_timeout) limits how
+/// long the writer may be stuck on a client that stops reading, and how long
+/// `send` may wait for room in the queue. Either one timing out closes the
+/// channel, and the client is disconnected. With a
+/// [limit on queued messages](crate::Server::max_queued_messages), `send`
+/// fails straight away when the queue is full instead of waiting, closing
+/// the channel just the same.
+///
+/// # Priority
+///
+/// Messages sent with [`ValueSender::send_priority`] go on a separate lane,
+/// which the writer always empties first, so that e.g. a kick reaches a
+/// client that has fallen behind without waiting for everything queued
+/// before it. The priority lane is never full, so sending on it never
+/// waits, and it doesn't count towards the limit on queued messages.
+#[derive(Debug)]
+pub struct ValueSender {
+    inner: mpsc::Sender<Outgoing>,
+    priority: mpsc::UnboundedSender<Outgoing>,
+    write_timeout: Option<Duration>,
+    // Give up on the connection instead of waiting for room in the queue
+    fail_when_full: bool,
+    // Cancelled when giving up, so the writer stops writing what's queued
+    give_up: CancellationToken,
+    // When the send currently waiting for room in the queue times out
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl Clone for ValueSender {
+    fn clone(&self) -> Self {
+        ValueSender {
+            inner: self.inner.clone(),
+            priority: self.priority.clone(),
+            write_timeout: self.write_timeout,
+            fail_when_full: self.fail_when_full,
+            give_up: self.give_up.clone(),
+            deadline: None,
+        }
+    }
+}
+
+/// A message queued for a connection's writer.
+#[derive(Debug)]
+pub(crate) enum Outgoing {
+    /// A message still to be serialized.
+    Value(Value),
+    /// A message that's already serialized to JSON, e.g. a broadcast shared
+    /// between connections.
+    Serialized(Bytes),
+    /// A frame meant for the framework at the other end, rather than its
+    /// handler, see [`envelope`].
+    Control(Value),
+}
+
+/// How a connection's writer should behave.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct WriterOptions {
+    /// Pretty-print messages.
+    pub(crate) pretty: bool,
+    /// Give up on the connection once writing stalls for this long.
+    pub(crate) write_timeout: Option<Duration>,
+    /// Send a keepalive after this long without any other messages.
+    pub(crate) keepalive: Option<Duration>,
+    /// Number messages and keep them here until they're acknowledged.
+    pub(crate) outbox: Option<SharedOutbox>,
+    /// Compress messages with whichever algorithm has been negotiated.
+    pub(crate) compression: SharedCompression,
+    /// Give up on the connection once this many messages are queued.
+    pub(crate) max_queued: Option<usize>,
+}
+
+impl ValueSender {
+    /// Spawn a writer task serializing messages as whole frames into
+    /// `sink`, returning the channel that feeds it.
+    pub(crate) fn spawn<W>(sink: W, options: WriterOptions) -> ValueSender
+    where
+        W: Sink<Bytes, Error = io::Error> + Send + Unpin + 'static,
+    {
+        let (sender, writer) = ValueSender::with_writer(sink, options);
+        tokio::spawn(writer);
+        sender
+    }
+
+    /// Create a channel along with the writer serializing its messages as
+    /// whole frames into `sink`, leaving it to the caller to run the writer.
+    pub(crate) fn with_writer<W>(
+        mut sink: W,
+        options: WriterOptions,
+    ) -> (ValueSender, BoxFuture<'static, ()>)
+    where
+        W: Sink<Bytes, Error = io::Error> + Send + Unpin + 'static,
+    {
+        let WriterOptions {
+            pretty,
+            write_timeout,
+            keepalive,
+            outbox,
+            compression,
+            max_queued,
+        } = options;
+        let capacity = max_queued.unwrap_or(OUTGOING_CAPACITY);
+        let (sender, mut receiver) = mpsc::channel::<Outgoing>(capacity);
+        let (priority_sender, mut priority) = mpsc::unbounded::<Outgoing>();
+        let give_up = CancellationToken::new();
+        let writer_gives_up = give_up.clone();
+        let encode: fn(&Value) -> serde_json::Result<Vec<u8>> = if pretty {
+            serde_json::to_vec_pretty
+        } else {
+            serde_json::to_vec
+        };
+
+        // Anything earlier connections didn't get through goes first
+        let resend = outbox
+            .as_ref()
+            .map(|outbox| outbox.lock().unacknowledged())
+            .unwrap_or_default();
+        let numbering = outbox.is_some();
+        let number = move |value: Value| match &outbox {
+            Some(outbox) => outbox.lock().push(value),
+            None => value,
+        };
+        let negotiated = compression.clone();
+        let serialize = move |value: &Value| -> io::Result<Bytes> {
+            let json = encode(value)?;
+            Ok(Bytes::from(compression.get().compress(json)?))
+        };
+        let reserialize = serialize.clone();
+        let frame = move |outgoing: Outgoing| -> io::Result<Bytes> {
+            match outgoing {
+                // Frames for the framework aren't acknowledged, and are
+                // small, and the hello answering an offer has to be
+                // readable before compression is known to be on
+                Outgoing::Control(value) => Ok(Bytes::from(encode(&value)?)),
+                Outgoing::Value(value) => serialize(&envelope::escape(number(value))),
+                // Numbering wraps the message itself, and escaping may, so
+                // it has to be parsed again
+                Outgoing::Serialized(json) if numbering || envelope::may_need_escape(&json) => {
+                    serialize(&envelope::escape(number(serde_json::from_slice(&json)?)))
+                }
+                Outgoing::Serialized(json) => {
+                    let compression = negotiated.get();
+                    if compression == Compression::None {
+                        return Ok(json);
+                    }
+                    Ok(Bytes::from(compression.compress(json.to_vec())?))
+                }
+            }
+        };
+
+        let writer = async move {
+            let resent = async {
+                for value in &resend {
+                    sink.feed(reserialize(value)?).await?;
+                }
+                sink.flush().await
+            };
+            if within(write_timeout, resent).await.is_ok() {
+                loop {
+                    // Both lanes are closed together, so the priority lane
+                    // has been emptied by the time the other one ends
+                    let next = async {
+                        tokio::select! {
+                            biased;
+                            Some(outgoing) = priority.next() => Some(outgoing),
+                            outgoing = receiver.next() => outgoing,
+                        }
+                    };
+                    let next = match keepalive {
+                        Some(interval) => {
+                            time::timeout(interval, next).await.unwrap_or_else(|_| {
+                                Some(Outgoing::Control(Control::Keepalive.to_value()))
+                            })
+                        }
+                        None => next.await,
+                    };
+                    let Some(outgoing) = next else {
+                        break;
+                    };
+
+                    // Write everything that's already queued before flushing
+                    let write = async {
+                        sink.feed(frame(outgoing)?).await?;
+                        while let Some(outgoing) = try_next(&mut priority, &mut receiver) {
+                            sink.feed(frame(outgoing)?).await?;
+                        }
+                        sink.flush().await
+                    };
+                    let written = tokio::select! {
+                        result = within(write_timeout, write) => result,
+                        () = give_up.cancelled() => Err(queue_full()),
+                    };
+                    if written.is_err() {
+                        break;
+                    }
+                }
+            }
+
+            // Make any further sends fail, then shut down the write half,
+            // unless the client fell too far behind to wait for
+            receiver.close();
+            priority.close();
+            if !give_up.is_cancelled() {
+                let _ = within(write_timeout, sink.close()).await;
+            }
+        };
+
+        let sender = ValueSender {
+            inner: sender,
+            priority: priority_sender,
+            write_timeout,
+            fail_when_full: max_queued.is_some(),
+            give_up: writer_gives_up,
+            deadline: None,
+        };
+        (sender, writer.boxed())
+    }
+
+    /// Serialize a message and send it, returning an error instead of
+    /// panicking if it can't be serialized (e.g. a map with non-string
+    /// keys). Serialization errors have kind
+    /// [`io::ErrorKind::InvalidData`], and nothing is sent.
+    pub async fn send_message<M: Serialize + ?Sized>(&mut self, message: &M) -> io::Result<()> {
+        let value = serde_json::to_value(message).map_err(io::Error::from)?;
+        self.send(value).await
+    }
+
+    /// Serialize several messages and send them in order, flushing once at
+    /// the end instead of after each one, so they go out in a single write
+    /// when the writer isn't busy. If any message can't be serialized,
+    /// nothing is sent, as with [`ValueSender::send_message`].
+    pub async fn send_batch<'a, M, I>(&mut self, messages: I) -> io::Result<()>
+    where
+        M: Serialize + 'a,
+        I: IntoIterator<Item = &'a M>,
+    {
+        let values = messages
+            .into_iter()
+            .map(serde_json::to_value)
+            .collect::<serde_json::Result<Vec<_>>>()
+            .map_err(io::Error::from)?;
+        for value in values {
+            self.feed(value).await?;
+        }
+        self.flush().await
+    }
+
+    /// Queue a message that's already serialized to JSON, like
+    /// [`SinkExt::feed`](futures::SinkExt::feed), so that it's written as
+    /// is.
+    pub(crate) async fn feed_serialized(&mut self, json: Bytes) -> io::Result<()> {
+        future::poll_fn(|cx| Pin::new(&mut *self).poll_ready(cx)).await?;
+        self.inner
+            .start_send(Outgoing::Serialized(json))
+            .map_err(closed)
+    }
+
+    /// Send a message that's already serialized to JSON, like
+    /// [`SinkExt::send`](futures::SinkExt::send), so that it's written as
+    /// is.
+    pub(crate) async fn send_serialized(&mut self, json: Bytes) -> io::Result<()> {
+        self.feed_serialized(json).await?;
+        self.flush().await
+    }
+
+    /// Serialize a message and queue it on the priority lane, ahead of
+    /// everything queued normally that the writer hasn't started writing
+    /// yet, see [priority](ValueSender#priority). Never waits, and fails
+    /// like [`ValueSender::send_message`] or if the channel is closed.
+    pub fn send_priority<M: Serialize + ?Sized>(&self, message: &M) -> io::Result<()> {
+        let value = serde_json::to_value(message).map_err(io::Error::from)?;
+        self.queue_priority(Outgoing::Value(value))
+    }
+
+    /// Queue a message that's already serialized to JSON on the priority
+    /// lane, see [`ValueSender::send_priority`].
+    pub(crate) fn send_serialized_priority(&self, json: Bytes) -> io::Result<()> {
+        self.queue_priority(Outgoing::Serialized(json))
+    }
+
+    /// Ask the other end whether it's still there, ahead of everything
+    /// queued normally, see [priority](ValueSender#priority). The other end
+    /// answers by itself, without involving its handlers, and the answer is
+    /// passed to [`ConnectionObserver::on_pong`](crate::server::ConnectionObserver::on_pong)
+    /// on the server, or
+    /// [`client::MessageHandler::on_pong`](crate::client::MessageHandler::on_pong)
+    /// on the client. Fails if the channel is closed.
+    pub fn ping(&self) -> io::Result<()> {
+        self.send_control(Control::Ping)
+    }
+
+    /// Queue a control frame on the priority lane.
+    pub(crate) fn send_control(&self, control: Control) -> io::Result<()> {
+        self.queue_priority(Outgoing::Control(control.to_value()))
+    }
+
+    /// Queue a frame meant for the framework at the other end, like
+    /// [`SinkExt::feed`](futures::SinkExt::feed), so that it's never taken
+    /// for a message, see [`envelope`].
+    pub(crate) async fn feed_control(&mut self, frame: Value) -> io::Result<()> {
+        future::poll_fn(|cx| Pin::new(&mut *self).poll_ready(cx)).await?;
+        self.inner
+            .start_send(Outgoing::Control(frame))
+            .map_err(closed)
+    }
+
+    /// Send a frame meant for the framework at the other end, like
+    /// [`SinkExt::send`](futures::SinkExt::send), see
+    /// [`ValueSender::feed_control`].
+    pub(crate) async fn send_control_frame(&mut self, frame: Value) -> io::Result<()> {
+        self.feed_control(frame).await?;
+        self.flush().await
+    }
+
+    fn queue_priority(&self, outgoing: Outgoing) -> io::Result<()> {
+        self.priority
+            .unbounded_send(outgoing)
+            .map_err(|err| closed(err.into_send_error()))
+    }
+
+    /// Close both lanes, making any further sends fail.
+    fn close_channels(&mut self) {
+        self.inner.close_channel();
+        self.priority.close_channel();
+    }
+
+    /// Returns whether the channel has been closed, either explicitly or
+    /// because the connection's writer has stopped. Sending on a closed
+    /// channel fails.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Wait for room in the queue, giving up after the write timeout.
+    fn poll_room(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<Result<(), mpsc::SendError>>,
+    ) -> Poll<io::Result<()>> {
+        if let Poll::Ready(result) = poll {
+            self.deadline = None;
+            return Poll::Ready(result.map_err(closed));
+        }
+        let Some(write_timeout) = self.write_timeout else {
+            return Poll::Pending;
+        };
+        let deadline = self
+            .deadline
+            .get_or_insert_with(|| Box::pin(time::sleep(write_timeout)));
+        ready!(deadline.as_mut().poll(cx));
+
+        // The client isn't keeping up, so stop sending to it altogether
+        self.deadline = None;
+        self.close_channels();
+        Poll::Ready(Err(timed_out()))
+    }
+}
+
+/// Take the next message that's already queued, from the priority lane if
+/// there is one.
+fn try_next(
+    priority: &mut mpsc::UnboundedReceiver<Outgoing>,
+    receiver: &mut mpsc::Receiver<Outgoing>,
+) -> Option<Outgoing> {
+    priority.try_recv().or_else(|_| receiver.try_recv()).ok()
+}
+
+/// Run an IO operation, failing if it takes longer than `timeout`.
+async fn within<F>(timeout: Option<Duration>, operation: F) -> io::Result<()>
+where
+    F: Future<Output = io::Result<()>>,
+{
+    match timeout {
+        Some(timeout) => time::timeout(timeout, operation)
+            .await
+            .unwrap_or_else(|_| Err(timed_out())),
+        None => operation.await,
+    }
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "write timed out")
+}
+
+fn queue_full() -> io::Error {
+    io::Error::new(io::ErrorKind::WouldBlock, "too many queued messages")
+}
+
+/// Convert an error from the underlying channel into an IO error, as the
+/// channel only fails once the connection's writer has stopped.
+fn closed(err: mpsc::SendError) -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, err)
+}
+
+impl Sink<Value> for ValueSender {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = this.inner.poll_ready(cx);
+        if this.fail_when_full && poll.is_pending() {
+            // The client isn't keeping up, so stop sending to it altogether
+            this.close_channels();
+            this.give_up.cancel();
+            return Poll::Ready(Err(queue_full()));
+        }
+        this.poll_room(cx, poll)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Value) -> io::Result<()> {
+        self.inner.start_send(Outgoing::Value(item)).map_err(closed)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_flush(cx);
+        if this.fail_when_full && poll.is_pending() {
+            // The message is queued, and only the next one needs room
+            return Poll::Ready(Ok(()));
+        }
+        this.poll_room(cx, poll)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.close_channels();
+        Poll::Ready(Ok(()))
+    }
+}
 
 /// Channels the server can use to send messages to clients.
 /// broadcast_sender is for sending to multiple clients, while
-/// value_sender is for sending messages back to the specific client
-/// attached to value_sender.
+/// response_sender is for sending messages back to the specific client
+/// attached to response_sender. Broadcasts meant for this client end up
+/// being sent through response_sender as well, so it carries everything
+/// written to the client.
+///
+/// # Ordering
+///
+/// Each connection is served by a single task, which both runs the message
+/// handler and forwards broadcasts to its client, using the same
+/// `response_sender` for both. Broadcasts are only forwarded between handler
+/// calls, so for the client whose message is being handled, every response
+/// sent during `handle_client_message` arrives before any broadcast sent
+/// during the same call, regardless of the order in which the handler sent
+/// them. Responses to one client are always delivered in the order they
+/// were sent, as are broadcasts from any one sender.
+///
+/// Broadcasts from other connections arrive whenever that client's task
+/// next gets to them, so they may be interleaved with its responses in
+/// any order relative to when they were sent.
+///
+/// With [ordered broadcasts](crate::Server::ordered_broadcasts), the
+/// associated client's own copy of a message sent with
+/// [`ServerMessageChannels::broadcast`] is queued on `response_sender`
+/// straight away instead, so it arrives in the order the handler sent it
+/// relative to responses.
 #[non_exhaustive]
 pub struct ServerMessageChannels<T> {
     /// Channel for sending messages back to the associated client.
@@ -37,4 +536,422 @@ pub struct ServerMessageChannels<T> {
     /// Channel to be used for sending messages across threads,
     /// i.e., for sending to other clients.
     pub broadcast_sender: BroadcastSender<T>,
+    /// Tags of the associated client's connection, used for routing
+    /// broadcasts sent with [`Recipients::Tagged`]. Starts out empty.
+    pub tags: HashSet<String>,
+    /// The clients currently connected to the server, including the
+    /// associated client.
+    pub connections: Connections<T>,
+    /// Cancelled once the server stops reading from the associated client,
+    /// e.g. because it disconnected. Work started on the client's behalf
+    /// that outlives a handler call, such as a spawned task streaming
+    /// results, can `select!` on [`CancellationToken::cancelled`] to stop
+    /// early instead of being orphaned, or be started with
+    /// [`ServerMessageChannels::spawn`], which does that for it. Handlers
+    /// themselves hold up the connection while they run, so this isn't
+    /// cancelled during a call.
+    pub cancellation: CancellationToken,
+    /// The bytes read from and written to the associated client so far,
+    /// see [`ConnectionStats`].
+    pub stats: ConnectionStats,
+    /// Identifies the associated client's connection, telling it apart from
+    /// other connections with the same client ID, see [`ConnectionId`].
+    pub connection_id: ConnectionId,
+    /// Where broadcasts come from, with ordered broadcasts.
+    pub(crate) origin: Option<Origin<T>>,
+    /// Whether to pretty-print broadcasts, see
+    /// [`crate::Server::json_pretty`].
+    pub(crate) pretty: bool,
+    /// Tasks spawned with [`ServerMessageChannels::spawn`], polled by the
+    /// connection's task and dropped when it ends. Only ever borrowed
+    /// mutably, the lock just keeps the channels `Sync`.
+    pub(crate) tasks: Mutex<FuturesUnordered<BoxFuture<'static, ()>>>,
+}
+
+/// The connection broadcasts sent through [`ServerMessageChannels`] come
+/// from, so that it can deliver its own copy in order and skip the one
+/// coming back through the broadcast channel, which is told apart by
+/// [`ServerMessageChannels::connection_id`].
+pub(crate) struct Origin<T> {
+    /// The associated client's ID.
+    pub(crate) id: T,
+}
+
+impl<T: PartialEq> ServerMessageChannels<T> {
+    /// Returns the number of clients currently connected to the server,
+    /// including the associated client. Shorthand for
+    /// [`Connections::count`] on [`Self::connections`].
+    pub fn connected_count(&self) -> usize {
+        self.connections.count()
+    }
+
+    /// Returns the IDs of the clients currently connected to the server,
+    /// including the associated client. Shorthand for [`Connections::ids`]
+    /// on [`Self::connections`], see there for how up to date they are.
+    pub fn connected_ids(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.connections.ids()
+    }
+
+    /// Serialize a message and broadcast it to the given recipients, like
+    /// [`ServerMessageChannels::try_broadcast`]. With
+    /// [ordered broadcasts](crate::Server::ordered_broadcasts), if the
+    /// associated client is one of the recipients, its copy is sent on
+    /// `response_sender` before this returns, keeping its place among the
+    /// responses. Otherwise, it's the same as `try_broadcast`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no one left to receive the message, it can't be
+    /// serialized, or the associated client's copy can't be sent.
+    pub async fn broadcast<M: Serialize + ?Sized>(
+        &mut self,
+        message: &M,
+        recipients: Recipients<T>,
+    ) -> Result<(), BroadcastError> {
+        if recipients.is_empty() {
+            return Ok(());
+        }
+        let json = to_json(message, self.pretty)?;
+        self.broadcast_serialized(json, recipients).await
+    }
+
+    /// Serialize a message and broadcast it to the given recipients with
+    /// high priority, e.g. to announce that the server is going down. Each
+    /// recipient's connection queues it on the
+    /// [priority lane](ValueSender#priority) instead of after everything
+    /// else already queued for its client, and doesn't
+    /// [coalesce](crate::Server::coalesce_window) it. With
+    /// [ordered broadcasts](crate::Server::ordered_broadcasts), the
+    /// associated client's copy is queued on its priority lane before this
+    /// returns. Nothing is sent if the recipients are
+    /// [empty](Recipients::is_empty).
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`ServerMessageChannels::broadcast`].
+    pub fn broadcast_priority<M: Serialize + ?Sized>(
+        &self,
+        message: &M,
+        recipients: Recipients<T>,
+    ) -> Result<(), BroadcastError> {
+        if recipients.is_empty() {
+            return Ok(());
+        }
+        let json = to_json(message, self.pretty)?;
+        let Some(origin) = &self.origin else {
+            self.broadcast_sender
+                .send((envelope::with_priority(&json), recipients))
+                .map_err(|_| BroadcastError::NoReceivers)?;
+            return Ok(());
+        };
+        let own_copy = recipients
+            .contains(&origin.id, &self.tags)
+            .then(|| json.clone());
+        let envelope = envelope::with_origin(self.connection_id.get(), &json);
+        self.broadcast_sender
+            .send((envelope::with_priority(&envelope), recipients))
+            .map_err(|_| BroadcastError::NoReceivers)?;
+        if let Some(json) = own_copy {
+            self.response_sender.send_serialized_priority(json)?;
+        }
+        Ok(())
+    }
+
+    /// Respond to the associated client and broadcast to the given
+    /// recipients for the same event, e.g. confirming a chat message to its
+    /// sender while passing it on to everyone else. Both messages are
+    /// serialized before either is sent, and the response is queued on
+    /// `response_sender` before the broadcast is sent, like
+    /// [`ServerMessageChannels::respond`] followed by
+    /// [`ServerMessageChannels::broadcast`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if either message can't be serialized, in which case neither
+    /// is sent, if the response can't be sent, in which case the broadcast
+    /// isn't either, or if the broadcast fails as described for
+    /// [`ServerMessageChannels::broadcast`].
+    pub async fn respond_and_broadcast<S, B>(
+        &mut self,
+        to_sender: &S,
+        to_others: &B,
+        recipients: Recipients<T>,
+    ) -> Result<(), BroadcastError>
+    where
+        S: Serialize + ?Sized,
+        B: Serialize + ?Sized,
+    {
+        let response = to_json(to_sender, self.pretty)?;
+        let json = to_json(to_others, self.pretty)?;
+        self.response_sender.send_serialized(response).await?;
+        if recipients.is_empty() {
+            return Ok(());
+        }
+        self.broadcast_serialized(json, recipients).await
+    }
+
+    /// Broadcast an already serialized message to the given, non-empty
+    /// recipients, see [`ServerMessageChannels::broadcast`].
+    async fn broadcast_serialized(
+        &mut self,
+        json: Bytes,
+        recipients: Recipients<T>,
+    ) -> Result<(), BroadcastError> {
+        let Some(origin) = &self.origin else {
+            self.broadcast_sender
+                .send((json, recipients))
+                .map_err(|_| BroadcastError::NoReceivers)?;
+            return Ok(());
+        };
+        let own_copy = recipients
+            .contains(&origin.id, &self.tags)
+            .then(|| json.clone());
+        self.broadcast_sender
+            .send((
+                envelope::with_origin(self.connection_id.get(), &json),
+                recipients,
+            ))
+            .map_err(|_| BroadcastError::NoReceivers)?;
+        if let Some(json) = own_copy {
+            self.response_sender.send_serialized(json).await?;
+        }
+        Ok(())
+    }
+
+    /// Serialize a message and send it to a single client, who may or may
+    /// not be the associated client. Shorthand for [`Connections::send_to`]
+    /// on [`Self::connections`], which unlike broadcasting with
+    /// [`Recipients::SingleRecipient`] doesn't involve any other connection.
+    pub async fn send_to<M: Serialize + ?Sized>(&self, id: &T, message: &M) -> io::Result<()> {
+        self.connections.send_to(id, message).await
+    }
+
+    /// Serialize a message and send it to every connected client among
+    /// `recipients`, returning how many connections it was queued for.
+    /// Shorthand for [`Connections::send_to_recipients`] on
+    /// [`Self::connections`].
+    pub async fn send_to_recipients<M: Serialize + ?Sized>(
+        &self,
+        recipients: &Recipients<T>,
+        message: &M,
+    ) -> io::Result<usize> {
+        self.connections
+            .send_to_recipients(recipients, message)
+            .await
+    }
+}
+
+impl<T: Clone> ServerMessageChannels<T> {
+    /// Another set of channels for the same connection, for a handler call
+    /// running alongside others, see [`crate::Server::max_in_flight`]. It
+    /// starts out with the same tags, and no spawned tasks.
+    pub(crate) fn fork(&self) -> Self {
+        ServerMessageChannels {
+            response_sender: self.response_sender.clone(),
+            broadcast_sender: self.broadcast_sender.clone(),
+            tags: self.tags.clone(),
+            connections: self.connections.clone(),
+            cancellation: self.cancellation.clone(),
+            stats: self.stats.clone(),
+            connection_id: self.connection_id,
+            origin: self.origin.as_ref().map(|origin| Origin {
+                id: origin.id.clone(),
+            }),
+            pretty: self.pretty,
+            tasks: Mutex::default(),
+        }
+    }
+}
+
+impl<T> ServerMessageChannels<T> {
+    /// Serialize a message and send it back to the associated client, see
+    /// [`ValueSender::send_message`].
+    pub async fn respond<M: Serialize + ?Sized>(&mut self, message: &M) -> io::Result<()> {
+        self.response_sender.send_message(message).await
+    }
+
+    /// Serialize a message and send it back to the associated client ahead
+    /// of anything already queued for it, see [`ValueSender::send_priority`].
+    pub fn respond_priority<M: Serialize + ?Sized>(&self, message: &M) -> io::Result<()> {
+        self.response_sender.send_priority(message)
+    }
+
+    /// Add a tag to the associated client's connection, so that it receives
+    /// broadcasts sent with [`Recipients::Tagged`] for that tag, e.g. after
+    /// the client joins a room. Returns whether the tag is new. Shorthand
+    /// for inserting into [`Self::tags`].
+    ///
+    /// The connection's task checks its tags whenever it forwards a
+    /// broadcast, which happens between handler calls. Tags changed in a
+    /// handler therefore apply to every broadcast forwarded once the
+    /// handler returns, including any sent by other clients while it ran.
+    pub fn set_tag(&mut self, tag: &str) -> bool {
+        self.tags.insert(tag.to_string())
+    }
+
+    /// Remove a tag from the associated client's connection, e.g. after
+    /// the client leaves a room. Returns whether the tag was set. See
+    /// [`ServerMessageChannels::set_tag`].
+    pub fn clear_tag(&mut self, tag: &str) -> bool {
+        self.tags.remove(tag)
+    }
+
+    /// Returns how many broadcasts are still waiting to be received by the
+    /// slowest connection. Once the queue is full (see
+    /// [`ServerOptions::broadcast_capacity`](crate::server::ServerOptions::broadcast_capacity)),
+    /// slow connections start missing broadcasts.
+    pub fn broadcast_queue_len(&self) -> usize {
+        self.broadcast_sender.len()
+    }
+
+    /// Serialize a message and broadcast it to the given recipients,
+    /// returning an error instead of panicking if it can't be sent. Nothing
+    /// is sent if the recipients are [empty](Recipients::is_empty).
+    ///
+    /// Unlike [`ValueSender`], broadcasting never waits: clients that fall
+    /// too far behind miss messages instead, see
+    /// [`crate::Server::handle_broadcast_recv_err`].
+    pub fn try_broadcast<M: Serialize + ?Sized>(
+        &self,
+        message: &M,
+        recipients: Recipients<T>,
+    ) -> Result<(), BroadcastError> {
+        broadcast(&self.broadcast_sender, message, recipients, self.pretty)
+    }
+
+    /// Spawn a task doing work on the associated client's behalf that
+    /// outlives the handler, e.g. a slow query whose result is sent once
+    /// it's ready, without holding up the connection in the meantime. The
+    /// task is given its own handle to `response_sender` to respond with.
+    ///
+    /// The task runs on the connection's task, like the handler, so it
+    /// goes wherever [`crate::Server::spawn_connection`] puts connections,
+    /// and makes progress whenever the connection isn't busy with a
+    /// handler call. It's stopped once [`Self::cancellation`] is cancelled,
+    /// i.e. once the server stops reading from the client, instead of being
+    /// orphaned.
+    pub fn spawn<F, Fut>(&mut self, task: F)
+    where
+        F: FnOnce(ValueSender) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let task = task(self.response_sender.clone());
+        let cancellation = self.cancellation.clone();
+        self.tasks.get_mut().push(Box::pin(async move {
+            // A panicking task only ends itself, as it would if spawned
+            // on its own
+            let task = AssertUnwindSafe(task).catch_unwind().map(drop);
+            tokio::select! {
+                () = task => {}
+                () = cancellation.cancelled() => {}
+            }
+        }));
+    }
+
+    /// Wait until every message sent on `response_sender` has been queued
+    /// for writing. Queued messages are written and flushed by the
+    /// connection's writer task without further action, see [`ValueSender`].
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.response_sender.flush().await
+    }
+}
+
+/// Broadcasting typed messages straight on the broadcast channel, where
+/// there are no [`ServerMessageChannels`] at hand, e.g. in
+/// [`crate::Server::after_join`].
+pub trait TryBroadcast<T> {
+    /// Serialize a message and broadcast it to the given recipients, see
+    /// [`ServerMessageChannels::try_broadcast`].
+    ///
+    /// Every client may have left by the time a message is broadcast, in
+    /// which case this returns [`BroadcastError::NoReceivers`] rather than
+    /// panicking, and the message is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no one left to receive the message, or it can't be
+    /// serialized.
+    fn try_broadcast<M: Serialize + ?Sized>(
+        &self,
+        message: &M,
+        recipients: Recipients<T>,
+    ) -> Result<(), BroadcastError>;
+}
+
+impl<T> TryBroadcast<T> for BroadcastSender<T> {
+    fn try_broadcast<M: Serialize + ?Sized>(
+        &self,
+        message: &M,
+        recipients: Recipients<T>,
+    ) -> Result<(), BroadcastError> {
+        broadcast(self, message, recipients, false)
+    }
+}
+
+/// Serialize a message once and broadcast it to the given recipients.
+fn broadcast<T, M: Serialize + ?Sized>(
+    sender: &BroadcastSender<T>,
+    message: &M,
+    recipients: Recipients<T>,
+    pretty: bool,
+) -> Result<(), BroadcastError> {
+    if recipients.is_empty() {
+        return Ok(());
+    }
+    sender
+        .send((to_json(message, pretty)?, recipients))
+        .map_err(|_| BroadcastError::NoReceivers)?;
+    Ok(())
+}
+
+/// Serialize a message to JSON, to be written as is.
+fn to_json<M: Serialize + ?Sized>(message: &M, pretty: bool) -> serde_json::Result<Bytes> {
+    let json = if pretty {
+        serde_json::to_vec_pretty(message)?
+    } else {
+        serde_json::to_vec(message)?
+    };
+    Ok(Bytes::from(json))
+}
+
+/// A client message that couldn't be deserialized, given to
+/// [`MessageHandler::handle_bad_message`](crate::server::MessageHandler::handle_bad_message)
+/// as its error, e.g. to log what the client sent along with what's wrong
+/// with it. Get it back with [`anyhow::Error::downcast_ref`].
+#[derive(Debug, thiserror::Error)]
+#[error("bad message: {error}")]
+#[non_exhaustive]
+pub struct BadMessage {
+    /// Why the message couldn't be deserialized.
+    #[source]
+    pub error: serde_json::Error,
+    /// The message as the client sent it, after decompression. For a
+    /// message sent with [metadata](crate::envelope), this is the message
+    /// alone, serialized again after being taken out of its envelope.
+    pub message: Bytes,
+}
+
+/// Errors returned by [`ServerMessageChannels::try_broadcast`],
+/// [`ServerMessageChannels::broadcast`],
+/// [`ServerMessageChannels::respond_and_broadcast`] and
+/// [`TryBroadcast::try_broadcast`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum BroadcastError {
+    /// There are no connections left to receive the message, e.g. because
+    /// every client has disconnected.
+    #[error("no connections to receive the broadcast")]
+    NoReceivers,
+    /// The message couldn't be serialized.
+    #[error("failed to serialize broadcast: {0}")]
+    Serialize(#[from] serde_json::Error),
+    /// A message to the associated client couldn't be sent, i.e. its own
+    /// copy of the broadcast (see [`ServerMessageChannels::broadcast`]) or
+    /// the response (see
+    /// [`ServerMessageChannels::respond_and_broadcast`]).
+    #[error("failed to send to the associated client: {0}")]
+    Send(#[from] io::Error),
 }
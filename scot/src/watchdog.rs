@@ -0,0 +1,35 @@
+//! Catching handlers that block the executor, e.g. by reading from stdin
+//! or doing other blocking IO instead of awaiting.
+
+use std::{
+    future::Future,
+    pin::pin,
+    time::{Duration, Instant},
+};
+
+use futures::future;
+
+/// Run `future`, calling `on_slow` with how long it took whenever a single
+/// poll takes at least `threshold`. A poll that long means the future
+/// blocked the thread it runs on, rather than waiting for something, which
+/// is what awaiting does.
+pub(crate) async fn watch<F: Future>(
+    future: F,
+    threshold: Option<Duration>,
+    mut on_slow: impl FnMut(Duration),
+) -> F::Output {
+    let Some(threshold) = threshold else {
+        return future.await;
+    };
+    let mut future = pin!(future);
+    future::poll_fn(|cx| {
+        let start = Instant::now();
+        let poll = future.as_mut().poll(cx);
+        let elapsed = start.elapsed();
+        if elapsed >= threshold {
+            on_slow(elapsed);
+        }
+        poll
+    })
+    .await
+}
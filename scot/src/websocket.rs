@@ -0,0 +1,51 @@
+//! Running connections over WebSockets, e.g. for browser clients.
+//!
+//! WebSockets already split the connection into messages, so each scot
+//! message is sent as one WebSocket message instead of going through a
+//! [`FrameCodec`](crate::codec::FrameCodec). Messages are sent as text, as
+//! they're JSON, but both text and binary messages are accepted. Pings are
+//! answered automatically, and a close message ends the connection like
+//! the other end shutting down its write half would.
+
+use std::{future::ready, io};
+
+use futures::prelude::*;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::{
+    tungstenite::{Message, Utf8Bytes},
+    WebSocketStream,
+};
+use tokio_util::bytes::{Bytes, BytesMut};
+
+/// Split a WebSocket into the stream of messages read from it and the sink
+/// for messages written to it, as raw frames.
+pub(crate) fn split<S>(
+    ws: WebSocketStream<S>,
+) -> (
+    impl Stream<Item = io::Result<BytesMut>> + Send + Unpin + 'static,
+    impl Sink<Bytes, Error = io::Error> + Send + Unpin + 'static,
+)
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (sink, stream) = ws.split();
+    let frames = stream
+        .map_err(io::Error::other)
+        .try_take_while(|message| ready(Ok(!message.is_close())))
+        .try_filter_map(|message| {
+            ready(Ok(match message {
+                Message::Text(text) => Some(BytesMut::from(text.as_bytes())),
+                Message::Binary(data) => Some(BytesMut::from(&data[..])),
+                _ => None,
+            }))
+        });
+    let frame_sink = sink
+        .sink_map_err(io::Error::other)
+        .with(|frame: Bytes| ready(Ok(to_message(frame))));
+    (frames, frame_sink)
+}
+
+/// Send frames as text when possible, which browsers can read directly.
+fn to_message(frame: Bytes) -> Message {
+    Utf8Bytes::try_from(frame.clone()).map_or(Message::Binary(frame), Message::Text)
+}
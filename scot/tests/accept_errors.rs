@@ -0,0 +1,109 @@
+#![cfg(unix)]
+
+use std::{
+    fs::File,
+    net::SocketAddr,
+    os::fd::AsRawFd,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::{net::TcpStream, time};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// How many errors have been reported to `handle_setup_err`.
+static SETUP_ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.response_sender.send(msg).await.unwrap();
+    }
+}
+
+struct EchoServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for EchoServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    fn handle_setup_err(&self, _err: Error, _addr: SocketAddr) {
+        SETUP_ERRORS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Set the soft limit on open file descriptors, returning the old one.
+fn set_fd_limit(soft: libc::rlim_t) -> libc::rlim_t {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    assert_eq!(
+        unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) },
+        0
+    );
+    let old = limit.rlim_cur;
+    limit.rlim_cur = soft;
+    assert_eq!(unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) }, 0);
+    old
+}
+
+#[tokio::test]
+async fn keeps_serving_after_accepting_fails() {
+    let server = EchoServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    let running = server.launch("127.0.0.1:0").await.unwrap();
+
+    // Leave room for the client's socket, but not for the server's end of
+    // the connection, so that accepting it fails
+    let next_fd = File::open("/dev/null").unwrap().as_raw_fd();
+    let old_limit = set_fd_limit(libc::rlim_t::try_from(next_fd).unwrap() + 1);
+    let stream = TcpStream::connect(running.local_addr()).await;
+    time::timeout(Duration::from_secs(5), async {
+        while SETUP_ERRORS.load(Ordering::SeqCst) == 0 {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    set_fd_limit(old_limit);
+
+    // The connection is accepted once there are descriptors to spare again
+    let mut framed = Framed::new(stream.unwrap(), LengthDelimitedCodec::new());
+    framed.send(Bytes::from("\"hello\"")).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    let value: Value = serde_json::from_slice(&frame).unwrap();
+    assert_eq!(value, Value::from("hello"));
+}
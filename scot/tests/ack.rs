@@ -0,0 +1,190 @@
+use std::ops::ControlFlow;
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{self, InputHandler},
+    server::{self, State},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// Every client is the same user, as if it had logged in.
+struct SameUser;
+
+impl State for SameUser {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        7
+    }
+}
+
+struct IgnoreHandler;
+
+#[async_trait]
+impl server::MessageHandler for IgnoreHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SameUser;
+
+    async fn handle_client_message(
+        _msg: Value,
+        _id: &usize,
+        _channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SameUser,
+    ) {
+    }
+}
+
+struct ReliableServer;
+
+#[async_trait]
+impl Server for ReliableServer {
+    type State = SameUser;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = IgnoreHandler;
+
+    fn get_state(&self) -> SameUser {
+        SameUser
+    }
+
+    fn require_ack(&self) -> bool {
+        true
+    }
+
+    async fn on_join_snapshot(
+        &self,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SameUser,
+    ) {
+        channels.respond(&"one").await.unwrap();
+        channels.respond(&"two").await.unwrap();
+    }
+}
+
+async fn start() -> std::net::SocketAddr {
+    let (listener, addr) = ReliableServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { ReliableServer.start_with_listener(&listener).await });
+    addr
+}
+
+async fn connect(addr: std::net::SocketAddr) -> Framed<TcpStream, LengthDelimitedCodec> {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    Framed::new(stream, LengthDelimitedCodec::new())
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+async fn send(framed: &mut Framed<TcpStream, LengthDelimitedCodec>, value: Value) {
+    let frame = serde_json::to_vec(&value).unwrap();
+    framed.send(Bytes::from(frame)).await.unwrap();
+}
+
+#[tokio::test]
+async fn unacknowledged_messages_are_resent_on_reconnect() {
+    let addr = start().await;
+
+    let mut first = connect(addr).await;
+    assert_eq!(
+        recv(&mut first).await,
+        json!({ "seq": 0, "message": "one" })
+    );
+    assert_eq!(
+        recv(&mut first).await,
+        json!({ "seq": 1, "message": "two" })
+    );
+    send(&mut first, json!({ "scot": "ack", "seq": 0 })).await;
+    send(&mut first, json!({ "scot": "goodbye" })).await;
+    assert_eq!(recv(&mut first).await, json!({ "scot": "goodbye" }));
+
+    let mut second = connect(addr).await;
+    assert_eq!(
+        recv(&mut second).await,
+        json!({ "seq": 1, "message": "two" })
+    );
+    assert_eq!(
+        recv(&mut second).await,
+        json!({ "seq": 2, "message": "one" })
+    );
+    assert_eq!(
+        recv(&mut second).await,
+        json!({ "seq": 3, "message": "two" })
+    );
+}
+
+struct LeaveAfterTwo;
+
+#[async_trait]
+impl client::MessageHandler for LeaveAfterTwo {
+    type ServerMessage = String;
+
+    async fn handle_server_message(_msg: String, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        msg: String,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        if msg == "two" {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+struct AcknowledgingClient;
+
+impl Client for AcknowledgingClient {
+    type ServerMessage = String;
+    type ServerMessageHandler = LeaveAfterTwo;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+
+    fn require_ack(&self) -> bool {
+        true
+    }
+}
+
+#[tokio::test]
+async fn client_acknowledges_handled_messages() {
+    let addr = start().await;
+    let stream = TcpStream::connect(addr).await.unwrap();
+    AcknowledgingClient.start_with_stream(stream).await.unwrap();
+
+    // Nothing is left to resend, so only the new snapshot arrives
+    let mut framed = connect(addr).await;
+    assert_eq!(
+        recv(&mut framed).await,
+        json!({ "seq": 0, "message": "one" })
+    );
+    assert_eq!(
+        recv(&mut framed).await,
+        json!({ "seq": 1, "message": "two" })
+    );
+    send(&mut framed, json!({ "scot": "goodbye" })).await;
+    assert_eq!(recv(&mut framed).await, json!({ "scot": "goodbye" }));
+}
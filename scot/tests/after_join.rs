@@ -0,0 +1,81 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, SequentialIdAllocator},
+    types::{ServerMessageChannels, TryBroadcast},
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::{net::TcpStream, sync::broadcast};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct IgnoreHandler;
+
+#[async_trait]
+impl MessageHandler for IgnoreHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        _msg: Value,
+        _id: &usize,
+        _channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+    }
+}
+
+struct AnnouncingServer {
+    ids: SequentialIdAllocator,
+}
+
+#[async_trait]
+impl Server for AnnouncingServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = IgnoreHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    async fn after_join(
+        &self,
+        id: &usize,
+        broadcast_sender: &broadcast::Sender<(Bytes, Recipients<usize>)>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        let message = json!({ "joined": id });
+        broadcast_sender
+            .try_broadcast(&message, Recipients::Everyone)
+            .unwrap();
+    }
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn joins_are_announced_to_everyone() {
+    let (listener, addr) = AnnouncingServer::bind("127.0.0.1:0").await.unwrap();
+    let server = AnnouncingServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut first = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(recv(&mut first).await, json!({ "joined": 0 }));
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut second = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(recv(&mut first).await, json!({ "joined": 1 }));
+    assert_eq!(recv(&mut second).await, json!({ "joined": 1 }));
+}
@@ -0,0 +1,134 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::{net::TcpStream, task, time};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// Records being dropped, i.e. the task holding it stopping.
+struct Stopped;
+
+impl Drop for Stopped {
+    fn drop(&mut self) {
+        STOPPED.store(true, Ordering::SeqCst);
+    }
+}
+
+struct BackgroundHandler;
+
+#[async_trait]
+impl MessageHandler for BackgroundHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        match msg.as_str() {
+            // Respond later, after handling other messages
+            "later" => channels.spawn(|mut sender| async move {
+                time::sleep(Duration::from_millis(50)).await;
+                sender.send_message("later").await.unwrap();
+            }),
+            // Never finish, unless cancelled
+            "forever" => {
+                let stopped = Stopped;
+                channels.spawn(|_sender| async move {
+                    let _stopped = stopped;
+                    future::pending::<()>().await;
+                });
+            }
+            // Respond with whether the task runs on the connection's task
+            "where" => {
+                let connection_task = task::id();
+                channels.spawn(move |mut sender| async move {
+                    let same = task::id() == connection_task;
+                    sender.send_message(&same).await.unwrap();
+                });
+            }
+            _ => channels.respond(&msg).await.unwrap(),
+        }
+    }
+}
+
+struct BackgroundServer;
+
+impl Server for BackgroundServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = BackgroundHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+}
+
+async fn connect() -> Framed<TcpStream, LengthDelimitedCodec> {
+    let (listener, addr) = BackgroundServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { BackgroundServer.start_with_listener(&listener).await });
+    let stream = TcpStream::connect(addr).await.unwrap();
+    Framed::new(stream, LengthDelimitedCodec::new())
+}
+
+async fn send(framed: &mut Framed<TcpStream, LengthDelimitedCodec>, msg: &str) {
+    let frame = serde_json::to_vec(msg).unwrap();
+    framed.send(Bytes::from(frame)).await.unwrap();
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn spawned_tasks_respond_later() {
+    let mut framed = connect().await;
+    send(&mut framed, "later").await;
+    send(&mut framed, "now").await;
+    assert_eq!(recv(&mut framed).await, json!("now"));
+    assert_eq!(recv(&mut framed).await, json!("later"));
+}
+
+#[tokio::test]
+async fn spawned_tasks_run_on_the_connection_task() {
+    let mut framed = connect().await;
+    send(&mut framed, "where").await;
+    assert_eq!(recv(&mut framed).await, json!(true));
+}
+
+#[tokio::test]
+async fn spawned_tasks_stop_when_client_disconnects() {
+    let mut framed = connect().await;
+    send(&mut framed, "forever").await;
+    send(&mut framed, "ping").await;
+    assert_eq!(recv(&mut framed).await, json!("ping"));
+    assert!(!STOPPED.load(Ordering::SeqCst));
+
+    drop(framed);
+    time::timeout(Duration::from_secs(5), async {
+        while !STOPPED.load(Ordering::SeqCst) {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,79 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.response_sender.send(msg).await.unwrap();
+    }
+}
+
+struct EchoServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for EchoServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+#[tokio::test]
+async fn accepts_connections_queued_in_the_backlog() {
+    let (listener, addr) = EchoServer::bind_with_backlog("127.0.0.1:0", 1024)
+        .await
+        .unwrap();
+
+    // Connect everyone before the server starts accepting
+    let mut clients = Vec::new();
+    for _ in 0..64 {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        clients.push(Framed::new(stream, LengthDelimitedCodec::new()));
+    }
+
+    let server = EchoServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    for framed in &mut clients {
+        framed.send(Bytes::from("\"hello\"")).await.unwrap();
+        let frame = framed.next().await.unwrap().unwrap();
+        let value: Value = serde_json::from_slice(&frame).unwrap();
+        assert_eq!(value, Value::from("hello"));
+    }
+}
+
+#[tokio::test]
+async fn fails_to_bind_an_unresolvable_address() {
+    assert!(EchoServer::bind_with_backlog("not an address", 16)
+        .await
+        .is_err());
+}
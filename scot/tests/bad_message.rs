@@ -0,0 +1,93 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::{BadMessage, ServerMessageChannels},
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct ReportingHandler;
+
+#[async_trait]
+impl MessageHandler for ReportingHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+
+    /// Tell the client what was wrong with its message.
+    async fn handle_bad_message(
+        err: anyhow::Error,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        let bad = err.downcast_ref::<BadMessage>().unwrap();
+        let report = json!({
+            "message": String::from_utf8_lossy(&bad.message),
+            "syntax": bad.error.is_syntax(),
+        });
+        channels.respond(&report).await.unwrap();
+    }
+}
+
+struct ReportingServer;
+
+impl Server for ReportingServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = ReportingHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+}
+
+async fn exchange(framed: &mut Framed<TcpStream, LengthDelimitedCodec>, frame: &str) -> Value {
+    framed.send(Bytes::from(frame.to_string())).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn handler_gets_the_offending_message() {
+    let (listener, addr) = ReportingServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { ReportingServer.start_with_listener(&listener).await });
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+
+    // Not JSON at all
+    assert_eq!(
+        exchange(&mut framed, "{nope").await,
+        json!({ "message": "{nope", "syntax": true })
+    );
+    // JSON, but not a string
+    assert_eq!(
+        exchange(&mut framed, "[1,2]").await,
+        json!({ "message": "[1,2]", "syntax": false })
+    );
+    // Metadata is taken off before the message is deserialized
+    let enveloped = json!({ "__scot": { "deadline_ms": u64::MAX, "message": 42 } });
+    assert_eq!(
+        exchange(&mut framed, &enveloped.to_string()).await,
+        json!({ "message": "42", "syntax": false })
+    );
+
+    // The connection carries on as usual
+    assert_eq!(exchange(&mut framed, "\"fine\"").await, json!("fine"));
+}
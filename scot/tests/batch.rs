@@ -0,0 +1,118 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{InputHandler, MessageHandler},
+    types::ValueSender,
+    Client,
+};
+use serde_json::{json, Value};
+use tokio::io::{duplex, AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// A stream counting how often it's flushed.
+struct CountingStream {
+    inner: DuplexStream,
+    flushes: Arc<AtomicUsize>,
+}
+
+impl AsyncRead for CountingStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for CountingStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.flushes.fetch_add(1, Ordering::SeqCst);
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+struct ReplyHandler;
+
+#[async_trait]
+impl MessageHandler for ReplyHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, response_channel: &mut ValueSender) {
+        let replies: Vec<u32> = (0..5).collect();
+        response_channel.send_batch(&replies).await.unwrap();
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+struct BatchClient;
+
+impl Client for BatchClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = ReplyHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+}
+
+#[tokio::test]
+async fn batch_is_flushed_once() {
+    let (client_end, server_end) = duplex(64 * 1024);
+    let flushes = Arc::new(AtomicUsize::new(0));
+    let stream = CountingStream {
+        inner: client_end,
+        flushes: flushes.clone(),
+    };
+    let client = tokio::spawn(async move { BatchClient.start_with_stream(stream).await });
+
+    let mut framed = Framed::new(server_end, LengthDelimitedCodec::new());
+    let mut flushes_after = Vec::new();
+    for _ in 0..2 {
+        framed.send(Bytes::from("\"go\"")).await.unwrap();
+        for expected in 0..5 {
+            let frame = framed.next().await.unwrap().unwrap();
+            let reply: Value = serde_json::from_slice(&frame).unwrap();
+            assert_eq!(reply, json!(expected));
+        }
+        flushes_after.push(flushes.load(Ordering::SeqCst));
+    }
+    // The writer also flushes once when it starts, so compare batches
+    assert_eq!(flushes_after[1] - flushes_after[0], 1);
+
+    client.abort();
+}
@@ -0,0 +1,86 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use scot::{
+    client::BlockingClient,
+    server::{MessageHandler, State},
+    types::ServerMessageChannels,
+    Server,
+};
+
+#[derive(Default)]
+struct Roster {
+    next_id: usize,
+    online: Vec<usize>,
+}
+
+impl State for Roster {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.online.push(self.next_id);
+        self.next_id
+    }
+
+    fn on_leave(&mut self, id: &usize) {
+        self.online.retain(|x| x != id);
+    }
+}
+
+struct ShoutHandler;
+
+#[async_trait]
+impl MessageHandler for ShoutHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = Arc<Mutex<Roster>>;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Roster>>,
+    ) {
+        channels.respond(&msg.to_uppercase()).await.unwrap();
+    }
+}
+
+struct ShoutServer {
+    roster: Arc<Mutex<Roster>>,
+}
+
+impl Server for ShoutServer {
+    type State = Arc<Mutex<Roster>>;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = ShoutHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Roster>> {
+        self.roster.clone()
+    }
+}
+
+#[test]
+fn blocking_clients_work_without_an_async_caller() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let roster = Arc::new(Mutex::new(Roster::default()));
+    let server = ShoutServer {
+        roster: roster.clone(),
+    };
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(server.start_with_std_listener(listener))
+    });
+
+    let mut client = BlockingClient::<String>::connect(&addr.to_string()).unwrap();
+    client.send("hello").unwrap();
+    client.send("world").unwrap();
+    assert_eq!(client.recv().unwrap().as_deref(), Some("HELLO"));
+    assert_eq!(client.recv().unwrap().as_deref(), Some("WORLD"));
+
+    // The server is done with the client once closing returns
+    client.close().unwrap();
+    assert!(roster.lock().unwrap().online.is_empty());
+}
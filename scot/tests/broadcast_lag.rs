@@ -0,0 +1,110 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::{channel::mpsc, prelude::*};
+use scot::{
+    server::{
+        ConnectionObserver, MessageHandler, Recipients, SequentialIdAllocator, BROADCAST_CAPACITY,
+    },
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Broadcast(usize),
+    Lag(u64),
+}
+
+struct Recorder {
+    events: Mutex<mpsc::UnboundedSender<Event>>,
+}
+
+impl ConnectionObserver<usize> for Recorder {
+    fn on_broadcast(&self, _id: &usize, queued: usize) {
+        let events = self.events.lock().unwrap();
+        events.unbounded_send(Event::Broadcast(queued)).unwrap();
+    }
+
+    fn on_broadcast_lag(&self, _id: &usize, missed: u64) {
+        let events = self.events.lock().unwrap();
+        events.unbounded_send(Event::Lag(missed)).unwrap();
+    }
+}
+
+/// Broadcasts as many messages as asked, faster than they can be forwarded.
+struct FloodHandler;
+
+#[async_trait]
+impl MessageHandler for FloodHandler {
+    type ClientMessage = usize;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        count: usize,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        for i in 0..count {
+            channels.try_broadcast(&i, Recipients::Everyone).unwrap();
+        }
+        let queued = channels.broadcast_queue_len();
+        channels.respond(&queued).await.unwrap();
+    }
+}
+
+struct FloodServer {
+    observer: Arc<Recorder>,
+}
+
+impl Server for FloodServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = usize;
+    type ClientMessageHandler = FloodHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+
+    fn observer(&self) -> Option<Arc<dyn ConnectionObserver<usize>>> {
+        Some(self.observer.clone())
+    }
+}
+
+#[tokio::test]
+async fn queue_depth_and_lag_are_reported() {
+    let (sender, events) = mpsc::unbounded();
+    let (listener, addr) = FloodServer::bind("127.0.0.1:0").await.unwrap();
+    let server = FloodServer {
+        observer: Arc::new(Recorder {
+            events: Mutex::new(sender),
+        }),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    // The channel rounds its capacity up
+    let capacity = BROADCAST_CAPACITY.next_power_of_two();
+    let flood = 3 * capacity;
+    framed.send(Bytes::from(flood.to_string())).await.unwrap();
+
+    let frame = framed.next().await.unwrap().unwrap();
+    let queued: Value = serde_json::from_slice(&frame).unwrap();
+    assert_eq!(queued, capacity);
+
+    // Only the newest broadcasts are left once the connection catches up
+    let received: Vec<Event> = events.take(capacity + 1).collect().await;
+    let mut expected = vec![Event::Lag(2 * capacity as u64)];
+    expected.extend((0..capacity).rev().map(Event::Broadcast));
+    assert_eq!(received, expected);
+}
@@ -0,0 +1,109 @@
+use std::ops::ControlFlow;
+
+use async_trait::async_trait;
+use futures::future;
+use scot::{
+    client,
+    server::{self, SequentialIdAllocator},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde_json::Value;
+
+/// Big enough to need many reads and writes with the default buffers.
+const BULK_LEN: usize = 1 << 20;
+
+struct EchoHandler;
+
+#[async_trait]
+impl server::MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct BulkServer;
+
+impl Server for BulkServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+
+    fn read_buffer_capacity(&self) -> Option<usize> {
+        Some(256 * 1024)
+    }
+
+    fn write_buffer_capacity(&self) -> Option<usize> {
+        Some(256 * 1024)
+    }
+}
+
+struct CheckEcho;
+
+#[async_trait]
+impl client::MessageHandler for CheckEcho {
+    type ServerMessage = String;
+
+    async fn handle_server_message(_msg: String, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        msg: String,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        assert_eq!(msg.len(), BULK_LEN);
+        ControlFlow::Break(())
+    }
+}
+
+struct SendBulk;
+
+#[async_trait]
+impl client::InputHandler for SendBulk {
+    async fn next_input(&mut self, message_channel: &mut ValueSender) {
+        let bulk = "x".repeat(BULK_LEN);
+        message_channel.send_message(&bulk).await.unwrap();
+        future::pending::<()>().await;
+    }
+}
+
+struct BulkClient;
+
+impl Client for BulkClient {
+    type ServerMessage = String;
+    type ServerMessageHandler = CheckEcho;
+    type InputHandler = SendBulk;
+
+    fn input_handler(&self) -> SendBulk {
+        SendBulk
+    }
+
+    fn read_buffer_capacity(&self) -> Option<usize> {
+        Some(64)
+    }
+
+    fn write_buffer_capacity(&self) -> Option<usize> {
+        Some(64)
+    }
+}
+
+#[tokio::test]
+async fn bulk_messages_get_through_any_buffer_size() {
+    let (listener, addr) = BulkServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { BulkServer.start_with_listener(&listener).await });
+
+    BulkClient.start(&addr.to_string()).await.unwrap();
+}
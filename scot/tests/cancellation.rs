@@ -0,0 +1,83 @@
+use std::{sync::Mutex, time::Duration};
+
+use async_trait::async_trait;
+use futures::{channel::oneshot, prelude::*};
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+static CANCELLED: Mutex<Option<oneshot::Sender<()>>> = Mutex::new(None);
+
+struct StreamingHandler;
+
+#[async_trait]
+impl MessageHandler for StreamingHandler {
+    type ClientMessage = ();
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        _msg: (),
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&"started").await.unwrap();
+
+        // Keep streaming until the client goes away
+        let token = channels.cancellation.clone();
+        let mut sender = channels.response_sender.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    () = token.cancelled() => break,
+                    () = tokio::time::sleep(Duration::from_millis(5)) => {
+                        let _ = sender.send_message(&"tick").await;
+                    }
+                }
+            }
+            let done = CANCELLED.lock().unwrap().take();
+            done.unwrap().send(()).unwrap();
+        });
+    }
+}
+
+struct StreamingServer;
+
+impl Server for StreamingServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = ();
+    type ClientMessageHandler = StreamingHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+}
+
+#[tokio::test]
+async fn work_is_cancelled_when_client_disconnects() {
+    let (sender, cancelled) = oneshot::channel();
+    *CANCELLED.lock().unwrap() = Some(sender);
+
+    let (listener, addr) = StreamingServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { StreamingServer.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("null")).await.unwrap();
+    assert_eq!(&framed.next().await.unwrap().unwrap()[..], b"\"started\"");
+    drop(framed);
+
+    tokio::time::timeout(Duration::from_secs(5), cancelled)
+        .await
+        .unwrap()
+        .unwrap();
+}
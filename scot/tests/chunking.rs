@@ -0,0 +1,109 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::{BufMut, Bytes, BytesMut},
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+const CHUNK_SIZE: usize = 16;
+const HEADER_LEN: usize = 13;
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct ChunkingServer;
+
+impl Server for ChunkingServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+
+    fn chunk_size(&self) -> Option<usize> {
+        Some(CHUNK_SIZE)
+    }
+}
+
+fn chunk(id: u32, index: u32, count: u32, payload: &[u8]) -> Bytes {
+    let mut chunk = BytesMut::new();
+    chunk.put_u8(0);
+    chunk.put_u32(id);
+    chunk.put_u32(index);
+    chunk.put_u32(count);
+    chunk.put_slice(payload);
+    chunk.freeze()
+}
+
+async fn connect() -> Framed<TcpStream, LengthDelimitedCodec> {
+    let (listener, addr) = ChunkingServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { ChunkingServer.start_with_listener(&listener).await });
+    let stream = TcpStream::connect(addr).await.unwrap();
+    Framed::new(stream, LengthDelimitedCodec::new())
+}
+
+#[tokio::test]
+async fn large_messages_are_chunked_both_ways() {
+    let mut framed = connect().await;
+    let text = "a message much longer than a single chunk";
+    let message = serde_json::to_vec(&json!(text)).unwrap();
+
+    let pieces: Vec<_> = message.chunks(10).collect();
+    let count = u32::try_from(pieces.len()).unwrap();
+    for (index, piece) in (0..count).zip(pieces) {
+        framed.send(chunk(7, index, count, piece)).await.unwrap();
+    }
+
+    let mut reply = Vec::new();
+    loop {
+        let frame = framed.next().await.unwrap().unwrap();
+        assert_eq!(frame[0], 0);
+        assert!(frame.len() <= HEADER_LEN + CHUNK_SIZE);
+        let index = u32::from_be_bytes(frame[5..9].try_into().unwrap());
+        let count = u32::from_be_bytes(frame[9..13].try_into().unwrap());
+        reply.extend_from_slice(&frame[HEADER_LEN..]);
+        if index + 1 == count {
+            break;
+        }
+    }
+    assert_eq!(
+        serde_json::from_slice::<Value>(&reply).unwrap(),
+        json!(text)
+    );
+}
+
+#[tokio::test]
+async fn small_messages_are_not_chunked() {
+    let mut framed = connect().await;
+    framed
+        .send(Bytes::from(serde_json::to_vec(&json!("hi")).unwrap()))
+        .await
+        .unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    assert_eq!(&frame[..], b"\"hi\"");
+}
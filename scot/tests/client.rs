@@ -0,0 +1,274 @@
+use std::{
+    ops::ControlFlow,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{InputHandler, MessageHandler},
+    types::ValueSender,
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Serialize, Deserialize)]
+enum Command {
+    Stay,
+    Kick,
+}
+
+struct KickHandler;
+
+#[async_trait]
+impl MessageHandler for KickHandler {
+    type ServerMessage = Command;
+
+    async fn handle_server_message(_msg: Command, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        msg: Command,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        match msg {
+            Command::Stay => ControlFlow::Continue(()),
+            Command::Kick => ControlFlow::Break(()),
+        }
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+struct KickableClient;
+
+impl Client for KickableClient {
+    type ServerMessage = Command;
+    type ServerMessageHandler = KickHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+}
+
+#[tokio::test]
+async fn message_handler_can_disconnect() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        KickableClient.start_with_stream(stream).await
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    for command in [Command::Stay, Command::Kick] {
+        let frame = serde_json::to_vec(&command).unwrap();
+        framed.send(Bytes::from(frame)).await.unwrap();
+    }
+
+    // The client says goodbye, stops writing, and waits for the reply
+    let frame = framed.next().await.unwrap().unwrap();
+    let goodbye: Value = serde_json::from_slice(&frame).unwrap();
+    assert_eq!(goodbye, json!({ "scot": "goodbye" }));
+    assert!(framed.next().await.is_none());
+
+    framed.send(Bytes::from(frame)).await.unwrap();
+    client.await.unwrap().unwrap();
+}
+
+static SERVER_CLOSES: AtomicUsize = AtomicUsize::new(0);
+
+struct CloseCountingHandler;
+
+#[async_trait]
+impl MessageHandler for CloseCountingHandler {
+    type ServerMessage = Command;
+
+    async fn handle_server_message(_msg: Command, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_close() {
+        SERVER_CLOSES.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+struct AbandonedClient;
+
+impl Client for AbandonedClient {
+    type ServerMessage = Command;
+    type ServerMessageHandler = CloseCountingHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+}
+
+#[tokio::test]
+async fn server_close_is_reported_once() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        AbandonedClient.start_with_stream(stream).await
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    let frame = serde_json::to_vec(&Command::Stay).unwrap();
+    framed.send(Bytes::from(frame)).await.unwrap();
+    drop(framed);
+
+    // The client returns instead of waiting for input forever
+    client.await.unwrap().unwrap();
+    assert_eq!(SERVER_CLOSES.load(Ordering::SeqCst), 1);
+}
+
+const BURST: usize = 100;
+
+static BURST_SENT: AtomicBool = AtomicBool::new(false);
+
+struct ReplyingHandler;
+
+#[async_trait]
+impl MessageHandler for ReplyingHandler {
+    type ServerMessage = usize;
+
+    async fn handle_server_message(msg: usize, response_channel: &mut ValueSender) {
+        let reply = Value::from(format!("reply {msg}"));
+        response_channel.send(reply).await.unwrap();
+    }
+}
+
+struct BurstInput;
+
+#[async_trait]
+impl InputHandler for BurstInput {
+    async fn next_input(&mut self, message_channel: &mut ValueSender) {
+        if BURST_SENT.swap(true, Ordering::SeqCst) {
+            future::pending::<()>().await;
+        }
+        for i in 0..BURST {
+            let input = Value::from(format!("input {i}"));
+            message_channel.send(input).await.unwrap();
+        }
+    }
+}
+
+struct ChattyClient;
+
+impl Client for ChattyClient {
+    type ServerMessage = usize;
+    type ServerMessageHandler = ReplyingHandler;
+    type InputHandler = BurstInput;
+
+    fn input_handler(&self) -> BurstInput {
+        BurstInput
+    }
+}
+
+#[tokio::test]
+async fn input_and_message_handlers_can_send_concurrently() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        ChattyClient.start_with_stream(stream).await
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    for i in 0..BURST {
+        let frame = serde_json::to_vec(&i).unwrap();
+        framed.send(Bytes::from(frame)).await.unwrap();
+    }
+
+    // Every frame must still be a complete message
+    let mut inputs = 0;
+    let mut replies = 0;
+    for _ in 0..2 * BURST {
+        let frame = framed.next().await.unwrap().unwrap();
+        let value: Value = serde_json::from_slice(&frame).unwrap();
+        match value.as_str().unwrap().split_once(' ').unwrap().0 {
+            "input" => inputs += 1,
+            "reply" => replies += 1,
+            other => panic!("unexpected message {other}"),
+        }
+    }
+    assert_eq!((inputs, replies), (BURST, BURST));
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Receipt {
+    command: String,
+    count: u32,
+}
+
+struct ReceiptHandler;
+
+#[async_trait]
+impl MessageHandler for ReceiptHandler {
+    type ServerMessage = Command;
+
+    async fn handle_server_message(msg: Command, response_channel: &mut ValueSender) {
+        let command = match msg {
+            Command::Stay => "stay",
+            Command::Kick => "kick",
+        };
+        let receipt = Receipt {
+            command: command.to_string(),
+            count: 1,
+        };
+        response_channel.send_message(&receipt).await.unwrap();
+    }
+}
+
+struct ReceiptClient;
+
+impl Client for ReceiptClient {
+    type ServerMessage = Command;
+    type ServerMessageHandler = ReceiptHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+}
+
+#[tokio::test]
+async fn typed_reply_reaches_the_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        ReceiptClient.start_with_stream(stream).await
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    let frame = serde_json::to_vec(&Command::Stay).unwrap();
+    framed.send(Bytes::from(frame)).await.unwrap();
+
+    let frame = framed.next().await.unwrap().unwrap();
+    let receipt: Receipt = serde_json::from_slice(&frame).unwrap();
+    assert_eq!(
+        receipt,
+        Receipt {
+            command: "stay".to_string(),
+            count: 1,
+        }
+    );
+}
@@ -0,0 +1,70 @@
+use std::{net::SocketAddr, ops::ControlFlow, sync::Arc};
+
+use async_trait::async_trait;
+use futures::future;
+use parking_lot::Mutex;
+use scot::{
+    client::{InputHandler, MessageHandler},
+    types::ValueSender,
+    Client,
+};
+use serde_json::Value;
+use tokio::net::TcpListener;
+
+struct LeaveHandler;
+
+#[async_trait]
+impl MessageHandler for LeaveHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        _msg: Value,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        ControlFlow::Break(())
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+#[derive(Default)]
+struct ReportingClient {
+    endpoints: Arc<Mutex<Option<(SocketAddr, SocketAddr)>>>,
+}
+
+impl Client for ReportingClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = LeaveHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+
+    fn on_connect(&self, local_addr: SocketAddr, peer_addr: SocketAddr) {
+        *self.endpoints.lock() = Some((local_addr, peer_addr));
+    }
+}
+
+#[tokio::test]
+async fn client_learns_its_endpoints() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = ReportingClient::default();
+    let (_sink, run) = client.start_with_sink(&addr.to_string()).await.unwrap();
+
+    let (stream, client_addr) = listener.accept().await.unwrap();
+    assert_eq!(*client.endpoints.lock(), Some((client_addr, addr)));
+
+    drop(stream);
+    run.await;
+}
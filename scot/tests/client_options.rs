@@ -0,0 +1,147 @@
+use std::{ops::ControlFlow, time::Duration};
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{ClientOptions, InputHandler, MessageHandler},
+    types::ValueSender,
+    Client,
+};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::{
+    bytes::{Bytes, BytesMut},
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct LeaveHandler;
+
+#[async_trait]
+impl MessageHandler for LeaveHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        _msg: Value,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        ControlFlow::Break(())
+    }
+}
+
+/// Says hello once, and nothing after that.
+struct HelloOnce {
+    said_hello: bool,
+}
+
+#[async_trait]
+impl InputHandler for HelloOnce {
+    async fn next_input(&mut self, message_channel: &mut ValueSender) {
+        if self.said_hello {
+            future::pending::<()>().await;
+        }
+        self.said_hello = true;
+        message_channel
+            .send(json!({ "text": "hello" }))
+            .await
+            .unwrap();
+    }
+}
+
+struct TunedClient;
+
+impl Client for TunedClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = LeaveHandler;
+    type InputHandler = HelloOnce;
+
+    fn input_handler(&self) -> HelloOnce {
+        HelloOnce { said_hello: false }
+    }
+
+    fn options(&self) -> ClientOptions {
+        ClientOptions::default()
+            .length_field_length(2)
+            .json_pretty(true)
+            .chunk_size(1024)
+            .read_buffer_capacity(512)
+            .write_buffer_capacity(512)
+            .require_ack(false)
+            .keepalive_interval(Duration::from_millis(50))
+            .coalesced(true)
+            .compression(Vec::new())
+            .slow_handler_warn(Duration::from_secs(1))
+            .protocol_version(3)
+    }
+}
+
+struct DefaultClient;
+
+impl Client for DefaultClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = LeaveHandler;
+    type InputHandler = HelloOnce;
+
+    fn input_handler(&self) -> HelloOnce {
+        HelloOnce { said_hello: false }
+    }
+}
+
+#[test]
+fn default_options_match_the_individual_methods() {
+    assert_eq!(
+        format!("{:?}", DefaultClient.options()),
+        format!("{:?}", ClientOptions::default())
+    );
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> BytesMut {
+    framed.next().await.unwrap().unwrap()
+}
+
+#[tokio::test]
+async fn options_configure_the_connection() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        TunedClient.start_with_stream(stream).await
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let codec = LengthDelimitedCodec::builder()
+        .length_field_length(2)
+        .new_codec();
+    let mut framed = Framed::new(stream, codec);
+
+    // The client declares its version and sends its message, pretty
+    let frames = [recv(&mut framed).await, recv(&mut framed).await];
+    assert!(frames.iter().all(|frame| frame.contains(&b'\n')));
+    let values: Vec<Value> = frames
+        .iter()
+        .map(|frame| serde_json::from_slice(frame).unwrap())
+        .collect();
+    assert!(values.contains(&json!({ "scot": "version", "version": 3 })));
+    assert!(values.contains(&json!({ "text": "hello" })));
+
+    // Idle, so the client keeps the connection alive
+    let keepalive = recv(&mut framed).await;
+    assert_eq!(
+        serde_json::from_slice::<Value>(&keepalive).unwrap(),
+        json!({ "scot": "keepalive" })
+    );
+
+    // The server has to declare the same version for the client to stay
+    let version = serde_json::to_vec(&json!({ "scot": "version", "version": 3 })).unwrap();
+    framed.send(Bytes::from(version)).await.unwrap();
+    framed.send(Bytes::from("[1, 2]")).await.unwrap();
+    loop {
+        let frame = recv(&mut framed).await;
+        if serde_json::from_slice::<Value>(&frame).unwrap() == json!({ "scot": "goodbye" }) {
+            framed.send(frame.freeze()).await.unwrap();
+            break;
+        }
+    }
+    client.await.unwrap().unwrap();
+}
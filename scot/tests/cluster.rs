@@ -0,0 +1,202 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use futures::{prelude::*, stream::BoxStream};
+use scot::{
+    server::{ClusterBackend, MessageHandler, Recipients, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::{net::TcpStream, sync::broadcast, time};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// A message bus in memory, shared by every server in a test.
+#[derive(Clone)]
+struct Bus(broadcast::Sender<Bytes>);
+
+impl Bus {
+    fn new() -> Bus {
+        Bus(broadcast::channel(16).0)
+    }
+
+    async fn subscribers(&self, count: usize) {
+        while self.0.receiver_count() < count {
+            time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+}
+
+#[async_trait]
+impl ClusterBackend for Bus {
+    async fn publish(&self, payload: Bytes) -> io::Result<()> {
+        self.0.send(payload).map(|_| ()).map_err(io::Error::other)
+    }
+
+    async fn subscribe(&self) -> io::Result<BoxStream<'static, io::Result<Bytes>>> {
+        let receiver = self.0.subscribe();
+        Ok(stream::unfold(receiver, |mut receiver| async move {
+            let payload = receiver.recv().await.map_err(io::Error::other);
+            Some((payload, receiver))
+        })
+        .boxed())
+    }
+}
+
+/// Answers "join" to let the client know it has joined, broadcasts what
+/// to say to the given IDs for `{ "to": [..], "say": .. }`, and broadcasts
+/// anything else to everyone.
+struct ShoutHandler;
+
+#[async_trait]
+impl MessageHandler for ShoutHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        if msg == "join" {
+            channels.respond(&msg).await.unwrap();
+        } else if let Some(to) = msg.get("to") {
+            let to: Vec<usize> = serde_json::from_value(to.clone()).unwrap();
+            channels
+                .broadcast(&msg["say"], Recipients::set(to))
+                .await
+                .unwrap();
+        } else {
+            channels
+                .broadcast(&msg, Recipients::Everyone)
+                .await
+                .unwrap();
+        }
+    }
+}
+
+struct ShoutServer {
+    ids: SequentialIdAllocator,
+    bus: Bus,
+    cluster_errors: Arc<AtomicUsize>,
+}
+
+impl Server for ShoutServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = ShoutHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    fn cluster(&self) -> Option<Arc<dyn ClusterBackend>> {
+        Some(Arc::new(self.bus.clone()))
+    }
+
+    fn handle_cluster_err(&self, _err: Error) {
+        self.cluster_errors.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn server(bus: &Bus) -> ShoutServer {
+    ShoutServer {
+        ids: SequentialIdAllocator::new(),
+        bus: bus.clone(),
+        cluster_errors: Arc::default(),
+    }
+}
+
+type Client = Framed<TcpStream, LengthDelimitedCodec>;
+
+async fn join(addr: std::net::SocketAddr) -> Client {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("\"join\"")).await.unwrap();
+    assert_eq!(receive(&mut framed).await, json!("join"));
+    framed
+}
+
+async fn receive(client: &mut Client) -> Value {
+    let frame = time::timeout(Duration::from_secs(5), client.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn broadcasts_reach_clients_of_other_instances() {
+    let bus = Bus::new();
+    let first = server(&bus).launch("127.0.0.1:0").await.unwrap();
+    let second = server(&bus).launch("127.0.0.1:0").await.unwrap();
+    bus.subscribers(2).await;
+
+    let mut alice = join(first.local_addr()).await;
+    let mut bob = join(second.local_addr()).await;
+
+    alice.send(Bytes::from("\"hi\"")).await.unwrap();
+    assert_eq!(receive(&mut alice).await, json!("hi"));
+    assert_eq!(receive(&mut bob).await, json!("hi"));
+
+    // Each broadcast arrives once, without echoing back through the bus
+    bob.send(Bytes::from("\"hello\"")).await.unwrap();
+    assert_eq!(receive(&mut alice).await, json!("hello"));
+    assert_eq!(receive(&mut bob).await, json!("hello"));
+}
+
+#[tokio::test]
+async fn broadcasts_to_sets_reach_other_instances() {
+    let bus = Bus::new();
+    let (first, second) = (server(&bus), server(&bus));
+    let cluster_errors = [first.cluster_errors.clone(), second.cluster_errors.clone()];
+    let first = first.launch("127.0.0.1:0").await.unwrap();
+    let second = second.launch("127.0.0.1:0").await.unwrap();
+    bus.subscribers(2).await;
+
+    // Both are the first to join their instance, so they have the same ID
+    let mut alice = join(first.local_addr()).await;
+    let mut bob = join(second.local_addr()).await;
+
+    let shout = json!({ "to": [0], "say": "psst" });
+    alice.send(Bytes::from(shout.to_string())).await.unwrap();
+    assert_eq!(receive(&mut alice).await, json!("psst"));
+    assert_eq!(receive(&mut bob).await, json!("psst"));
+    for errors in cluster_errors {
+        assert_eq!(errors.load(Ordering::SeqCst), 0);
+    }
+}
+
+#[tokio::test]
+async fn unreadable_payloads_are_reported() {
+    let bus = Bus::new();
+    let server = server(&bus);
+    let cluster_errors = server.cluster_errors.clone();
+    let running = server.launch("127.0.0.1:0").await.unwrap();
+    bus.subscribers(1).await;
+
+    bus.publish(Bytes::from("not json")).await.unwrap();
+    while cluster_errors.load(Ordering::SeqCst) == 0 {
+        time::sleep(Duration::from_millis(5)).await;
+    }
+
+    // The server carries on
+    let mut client = join(running.local_addr()).await;
+    client.send(Bytes::from("\"still here\"")).await.unwrap();
+    assert_eq!(receive(&mut client).await, json!("still here"));
+}
@@ -0,0 +1,159 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::{channel::mpsc, future, prelude::*};
+use scot::{
+    client::{self, InputHandler},
+    server::{self, Recipients, State},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Default)]
+struct Counter {
+    next_id: usize,
+}
+
+impl State for Counter {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Burst {
+    count: usize,
+}
+
+struct BurstHandler;
+
+#[async_trait]
+impl server::MessageHandler for BurstHandler {
+    type ClientMessage = Burst;
+    type ClientID = usize;
+    type State = Arc<Mutex<Counter>>;
+
+    async fn handle_client_message(
+        msg: Burst,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Counter>>,
+    ) {
+        for i in 0..msg.count {
+            channels.try_broadcast(&i, Recipients::Everyone).unwrap();
+        }
+    }
+}
+
+struct CoalescingServer {
+    state: Arc<Mutex<Counter>>,
+}
+
+impl Server for CoalescingServer {
+    type State = Arc<Mutex<Counter>>;
+    type ClientID = usize;
+    type ClientMessage = Burst;
+    type ClientMessageHandler = BurstHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Counter>> {
+        self.state.clone()
+    }
+
+    fn coalesce_window(&self) -> Option<Duration> {
+        Some(Duration::from_millis(50))
+    }
+}
+
+static RECEIVED: Mutex<Option<mpsc::UnboundedSender<usize>>> = Mutex::new(None);
+
+struct CollectingHandler;
+
+#[async_trait]
+impl client::MessageHandler for CollectingHandler {
+    type ServerMessage = usize;
+
+    async fn handle_server_message(msg: usize, _response_channel: &mut ValueSender) {
+        if let Some(received) = RECEIVED.lock().unwrap().as_ref() {
+            received.unbounded_send(msg).unwrap();
+        }
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+struct CoalescedClient;
+
+impl Client for CoalescedClient {
+    type ServerMessage = usize;
+    type ServerMessageHandler = CollectingHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+
+    fn coalesced(&self) -> bool {
+        true
+    }
+}
+
+#[tokio::test]
+async fn broadcasts_within_window_share_a_frame() {
+    let (listener, addr) = CoalescingServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move {
+        let server = CoalescingServer {
+            state: Arc::default(),
+        };
+        server.start_with_listener(&listener).await
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    let request = serde_json::to_vec(&Burst { count: 3 }).unwrap();
+    framed.send(Bytes::from(request)).await.unwrap();
+
+    let frame = framed.next().await.unwrap().unwrap();
+    let value: Value = serde_json::from_slice(&frame).unwrap();
+    assert_eq!(value, json!([0, 1, 2]));
+}
+
+#[tokio::test]
+async fn client_splits_coalesced_frames() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (sender, receiver) = mpsc::unbounded();
+    *RECEIVED.lock().unwrap() = Some(sender);
+    tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        CoalescedClient.start_with_stream(stream).await
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("[1,2,3]")).await.unwrap();
+    framed.send(Bytes::from("4")).await.unwrap();
+
+    let received: Vec<usize> = receiver.take(4).collect().await;
+    assert_eq!(received, vec![1, 2, 3, 4]);
+}
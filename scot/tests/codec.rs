@@ -0,0 +1,170 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use async_trait::async_trait;
+use futures::{channel::oneshot, prelude::*};
+use scot::{
+    client::{self, InputHandler},
+    codec::FrameCodec,
+    server::{self, State},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Framed, LinesCodec};
+
+#[derive(Default)]
+struct Counter {
+    next_id: usize,
+}
+
+impl State for Counter {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Text {
+    text: String,
+}
+
+struct EchoHandler;
+
+#[async_trait]
+impl server::MessageHandler for EchoHandler {
+    type ClientMessage = Text;
+    type ClientID = usize;
+    type State = Arc<Mutex<Counter>>;
+
+    async fn handle_client_message(
+        msg: Text,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Counter>>,
+    ) {
+        let reply = serde_json::to_value(msg).unwrap();
+        channels.response_sender.send(reply).await.unwrap();
+    }
+}
+
+struct NdjsonServer {
+    state: Arc<Mutex<Counter>>,
+}
+
+impl Server for NdjsonServer {
+    type State = Arc<Mutex<Counter>>;
+    type ClientID = usize;
+    type ClientMessage = Text;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Counter>> {
+        self.state.clone()
+    }
+
+    fn codec(&self) -> FrameCodec {
+        FrameCodec::lines()
+    }
+}
+
+static REPLY: Mutex<Option<oneshot::Sender<Text>>> = Mutex::new(None);
+static SENT: AtomicBool = AtomicBool::new(false);
+
+struct ReplyHandler;
+
+#[async_trait]
+impl client::MessageHandler for ReplyHandler {
+    type ServerMessage = Text;
+
+    async fn handle_server_message(msg: Text, _response_channel: &mut ValueSender) {
+        if let Some(reply) = REPLY.lock().unwrap().take() {
+            let _ = reply.send(msg);
+        }
+    }
+}
+
+struct SendOnce;
+
+#[async_trait]
+impl InputHandler for SendOnce {
+    async fn next_input(&mut self, message_channel: &mut ValueSender) {
+        if SENT.swap(true, Ordering::SeqCst) {
+            future::pending::<()>().await;
+        }
+        let msg = serde_json::to_value(Text {
+            text: "from client".to_string(),
+        })
+        .unwrap();
+        message_channel.send(msg).await.unwrap();
+    }
+}
+
+struct NdjsonClient;
+
+impl Client for NdjsonClient {
+    type ServerMessage = Text;
+    type ServerMessageHandler = ReplyHandler;
+    type InputHandler = SendOnce;
+
+    fn input_handler(&self) -> SendOnce {
+        SendOnce
+    }
+
+    fn codec(&self) -> FrameCodec {
+        FrameCodec::lines()
+    }
+}
+
+#[tokio::test]
+async fn server_speaks_ndjson() {
+    let (listener, addr) = NdjsonServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move {
+        let server = NdjsonServer {
+            state: Arc::default(),
+        };
+        server.start_with_listener(&listener).await
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut lines = Framed::new(stream, LinesCodec::new());
+    lines.send(r#"{"text":"one"}"#).await.unwrap();
+    lines.send(r#"{"text":"two"}"#).await.unwrap();
+
+    assert_eq!(lines.next().await.unwrap().unwrap(), r#"{"text":"one"}"#);
+    assert_eq!(lines.next().await.unwrap().unwrap(), r#"{"text":"two"}"#);
+}
+
+#[tokio::test]
+async fn client_speaks_ndjson() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (reply_sender, reply_receiver) = oneshot::channel();
+    *REPLY.lock().unwrap() = Some(reply_sender);
+    tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        NdjsonClient.start_with_stream(stream).await
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut lines = Framed::new(stream, LinesCodec::new());
+    assert_eq!(
+        lines.next().await.unwrap().unwrap(),
+        r#"{"text":"from client"}"#
+    );
+    lines.send(r#"{"text":"from server"}"#).await.unwrap();
+
+    let reply = reply_receiver.await.unwrap();
+    assert_eq!(
+        reply,
+        Text {
+            text: "from server".to_string()
+        }
+    );
+}
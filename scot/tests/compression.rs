@@ -0,0 +1,113 @@
+use std::io::{Read, Write};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    compression::Compression,
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct CompressingServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for CompressingServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    fn compression(&self) -> Vec<Compression> {
+        vec![Compression::Zstd, Compression::Gzip]
+    }
+}
+
+async fn connect() -> Framed<TcpStream, LengthDelimitedCodec> {
+    let (listener, addr) = CompressingServer::bind("127.0.0.1:0").await.unwrap();
+    let server = CompressingServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+    let stream = TcpStream::connect(addr).await.unwrap();
+    Framed::new(stream, LengthDelimitedCodec::new())
+}
+
+async fn send(framed: &mut Framed<TcpStream, LengthDelimitedCodec>, value: Value) {
+    let frame = serde_json::to_vec(&value).unwrap();
+    framed.send(Bytes::from(frame)).await.unwrap();
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn server_picks_its_preferred_algorithm_the_client_offered() {
+    let mut framed = connect().await;
+    let offer = json!({ "scot": "hello", "compression": ["brotli", "gzip"] });
+    send(&mut framed, offer).await;
+    let choice = json!({ "scot": "hello", "compression": "gzip" });
+    assert_eq!(recv(&mut framed).await, choice);
+
+    // Compressed messages start with the marker for gzip
+    let mut encoder = flate2::write::GzEncoder::new(vec![1], flate2::Compression::default());
+    encoder.write_all(br#""hello""#).unwrap();
+    framed
+        .send(Bytes::from(encoder.finish().unwrap()))
+        .await
+        .unwrap();
+
+    let frame = framed.next().await.unwrap().unwrap();
+    assert_eq!(frame[0], 1);
+    let mut json = String::new();
+    flate2::read::GzDecoder::new(&frame[1..])
+        .read_to_string(&mut json)
+        .unwrap();
+    assert_eq!(json, r#""hello""#);
+}
+
+#[tokio::test]
+async fn messages_stay_uncompressed_without_overlap() {
+    let mut framed = connect().await;
+    send(
+        &mut framed,
+        json!({ "scot": "hello", "compression": ["brotli"] }),
+    )
+    .await;
+    let choice = json!({ "scot": "hello", "compression": "none" });
+    assert_eq!(recv(&mut framed).await, choice);
+
+    send(&mut framed, json!("hello")).await;
+    assert_eq!(recv(&mut framed).await, json!("hello"));
+}
@@ -0,0 +1,121 @@
+use std::{net::SocketAddr, ops::ControlFlow, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::future;
+use parking_lot::Mutex;
+use scot::{
+    client::{InputHandler, Jitter, MessageHandler, RetryPolicy},
+    types::ValueSender,
+    Client,
+};
+use serde_json::Value;
+use tokio::{net::TcpListener, time};
+
+struct LeaveHandler;
+
+#[async_trait]
+impl MessageHandler for LeaveHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        _msg: Value,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        ControlFlow::Break(())
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+#[derive(Default)]
+struct RetryingClient {
+    attempts: Arc<Mutex<Vec<u32>>>,
+}
+
+impl Client for RetryingClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = LeaveHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+
+    fn on_connect_attempt(&self, attempt: u32, _err: &std::io::Error) {
+        self.attempts.lock().push(attempt);
+    }
+}
+
+/// An address nothing is listening on, at least for now.
+async fn unused_addr() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap()
+}
+
+#[tokio::test]
+async fn connecting_waits_for_the_server_to_start() {
+    let addr = unused_addr().await;
+    tokio::spawn(async move {
+        time::sleep(Duration::from_millis(100)).await;
+        let listener = TcpListener::bind(addr).await.unwrap();
+        // Closing the connection straight away lets the client return
+        let _ = listener.accept().await.unwrap();
+    });
+
+    let client = RetryingClient::default();
+    let policy = RetryPolicy::default()
+        .max_attempts(50)
+        .initial_delay(Duration::from_millis(10))
+        .max_delay(Duration::from_millis(20));
+    client
+        .connect_retrying(&addr.to_string(), policy)
+        .await
+        .unwrap();
+
+    let attempts = client.attempts.lock().clone();
+    assert!(!attempts.is_empty());
+    assert_eq!(attempts, (1..=attempts.len() as u32).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn connecting_gives_up_after_the_last_attempt() {
+    let addr = unused_addr().await;
+    let client = RetryingClient::default();
+    let policy = RetryPolicy::default()
+        .max_attempts(3)
+        .initial_delay(Duration::from_millis(1));
+    let result = client.connect_retrying(&addr.to_string(), policy).await;
+
+    assert!(result.is_err());
+    assert_eq!(*client.attempts.lock(), vec![1, 2, 3]);
+}
+
+#[test]
+fn seeded_jitter_is_deterministic() {
+    let policy = RetryPolicy::default()
+        .max_attempts(8)
+        .jitter(Jitter::Full)
+        .seed(42);
+    let delays: Vec<_> = policy.delays().collect();
+    assert_eq!(delays.len(), 7);
+    assert_eq!(policy.delays().collect::<Vec<_>>(), delays);
+
+    // Each delay is somewhere up to the one without jitter
+    let unjittered = policy.clone().jitter(Jitter::None);
+    for (delay, max) in delays.iter().zip(unjittered.delays()) {
+        assert!(*delay <= max);
+    }
+
+    // A different seed spreads attempts differently
+    let other = policy.seed(43).delays().collect::<Vec<_>>();
+    assert_ne!(other, delays);
+}
@@ -0,0 +1,89 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// Every client is the same user, e.g. on several devices.
+#[derive(Clone)]
+struct OneUser;
+
+impl State for OneUser {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        7
+    }
+}
+
+struct WhoAmIHandler;
+
+#[async_trait]
+impl MessageHandler for WhoAmIHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = OneUser;
+
+    async fn handle_client_message(
+        _msg: Value,
+        id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut OneUser,
+    ) {
+        let reply = json!({
+            "connection": channels.connection_id.get(),
+            "connected": channels.connections.is_connected(id),
+        });
+        channels.respond(&reply).await.unwrap();
+    }
+}
+
+struct WhoAmIServer;
+
+impl Server for WhoAmIServer {
+    type State = OneUser;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = WhoAmIHandler;
+
+    fn get_state(&self) -> OneUser {
+        OneUser
+    }
+}
+
+async fn who_am_i(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    framed.send(Bytes::from("null")).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn connections_with_the_same_client_id_are_told_apart() {
+    let (listener, addr) = WhoAmIServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { WhoAmIServer.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut first = Framed::new(stream, LengthDelimitedCodec::new());
+    let first_id = who_am_i(&mut first).await["connection"].as_u64().unwrap();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut second = Framed::new(stream, LengthDelimitedCodec::new());
+    let second_id = who_am_i(&mut second).await["connection"].as_u64().unwrap();
+    assert!(second_id > first_id);
+
+    // The first connection leaving doesn't take the second one with it
+    let goodbye = serde_json::to_vec(&json!({ "scot": "goodbye" })).unwrap();
+    first.send(Bytes::from(goodbye)).await.unwrap();
+    first.next().await.unwrap().unwrap();
+    assert_eq!(
+        who_am_i(&mut second).await,
+        json!({ "connection": second_id, "connected": true })
+    );
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct PresenceHandler;
+
+#[async_trait]
+impl MessageHandler for PresenceHandler {
+    /// The ID of the client to ask about.
+    type ClientMessage = usize;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        other: usize,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        let reply = json!([
+            channels.connected_count(),
+            channels.connections.is_connected(&other),
+            channels.connected_ids()
+        ]);
+        channels.response_sender.send(reply).await.unwrap();
+    }
+}
+
+struct PresenceServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for PresenceServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = usize;
+    type ClientMessageHandler = PresenceHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+async fn ask(framed: &mut Framed<TcpStream, LengthDelimitedCodec>, other: usize) -> Value {
+    framed.send(Bytes::from(other.to_string())).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn connections_track_joins_and_leaves() {
+    let (listener, addr) = PresenceServer::bind("127.0.0.1:0").await.unwrap();
+    let server = PresenceServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut first = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(ask(&mut first, 1).await, json!([1, false, [0]]));
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut second = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(ask(&mut second, 0).await, json!([2, true, [0, 1]]));
+    assert_eq!(ask(&mut first, 1).await, json!([2, true, [0, 1]]));
+
+    drop(second);
+    // Leaving happens in the background, so give it a moment
+    let mut reply = ask(&mut first, 1).await;
+    for _ in 0..50 {
+        if reply == json!([1, false, [0]]) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        reply = ask(&mut first, 1).await;
+    }
+    assert_eq!(reply, json!([1, false, [0]]));
+}
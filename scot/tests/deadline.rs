@@ -0,0 +1,99 @@
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    envelope,
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct DeadlineHandler;
+
+#[async_trait]
+impl MessageHandler for DeadlineHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        let reply = json!({ "handled": msg });
+        channels.response_sender.send(reply).await.unwrap();
+    }
+
+    async fn handle_expired(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        let reply = json!({ "expired": msg });
+        channels.response_sender.send(reply).await.unwrap();
+    }
+}
+
+struct DeadlineServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for DeadlineServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = DeadlineHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+async fn request(framed: &mut Framed<TcpStream, LengthDelimitedCodec>, value: Value) -> Value {
+    let bytes = serde_json::to_vec(&value).unwrap();
+    framed.send(Bytes::from(bytes)).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn expired_messages_are_not_handled() {
+    let (listener, addr) = DeadlineServer::bind("127.0.0.1:0").await.unwrap();
+    let server = DeadlineServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+
+    let plain = json!("plain");
+    assert_eq!(
+        request(&mut framed, plain).await,
+        json!({ "handled": "plain" })
+    );
+
+    let later = SystemTime::now() + Duration::from_secs(60);
+    let timely = envelope::with_deadline(json!("timely"), later);
+    assert_eq!(
+        request(&mut framed, timely).await,
+        json!({ "handled": "timely" })
+    );
+
+    let earlier = SystemTime::now() - Duration::from_secs(60);
+    let late = envelope::with_deadline(json!("late"), earlier);
+    assert_eq!(
+        request(&mut framed, late).await,
+        json!({ "expired": "late" })
+    );
+}
@@ -0,0 +1,173 @@
+use std::{io, time::Duration};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::{net::TcpStream, time};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct WhisperHandler;
+
+#[async_trait]
+impl MessageHandler for WhisperHandler {
+    /// The ID of the client to whisper to.
+    type ClientMessage = usize;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        to: usize,
+        id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        let whisper = format!("psst from {id}");
+        let reply = match channels.send_to(&to, &whisper).await {
+            Ok(()) => json!("sent"),
+            Err(e) if e.kind() == io::ErrorKind::NotConnected => json!("not connected"),
+            Err(e) => panic!("{e}"),
+        };
+        channels.respond(&reply).await.unwrap();
+    }
+}
+
+struct WhisperServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for WhisperServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = usize;
+    type ClientMessageHandler = WhisperHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+async fn whisper(framed: &mut Framed<TcpStream, LengthDelimitedCodec>, to: usize) -> Value {
+    framed.send(Bytes::from(to.to_string())).await.unwrap();
+    recv(framed).await
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn messages_reach_a_single_client() {
+    let (listener, addr) = WhisperServer::bind("127.0.0.1:0").await.unwrap();
+    let server = WhisperServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut first = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(whisper(&mut first, 1).await, json!("not connected"));
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut second = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(whisper(&mut second, 0).await, json!("sent"));
+    assert_eq!(recv(&mut first).await, json!("psst from 1"));
+
+    assert_eq!(whisper(&mut first, 1).await, json!("sent"));
+    assert_eq!(recv(&mut second).await, json!("psst from 0"));
+}
+
+#[tokio::test]
+async fn sending_to_recipients_counts_deliveries() {
+    let server = WhisperServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    let running = server.launch("127.0.0.1:0").await.unwrap();
+    let connections = running.connections();
+
+    let stream = TcpStream::connect(running.local_addr()).await.unwrap();
+    let mut first = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(whisper(&mut first, 7).await, json!("not connected"));
+    let stream = TcpStream::connect(running.local_addr()).await.unwrap();
+    let mut second = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(whisper(&mut second, 7).await, json!("not connected"));
+
+    let some = Recipients::MultipleRecipients {
+        recipients: vec![0, 7],
+    };
+    assert_eq!(
+        connections.send_to_recipients(&some, "hi").await.unwrap(),
+        1
+    );
+    assert_eq!(recv(&mut first).await, json!("hi"));
+    let everyone = Recipients::Everyone;
+    assert_eq!(
+        connections
+            .send_to_recipients(&everyone, "all")
+            .await
+            .unwrap(),
+        2
+    );
+    assert_eq!(recv(&mut first).await, json!("all"));
+    assert_eq!(recv(&mut second).await, json!("all"));
+
+    // Once the recipient has left, nobody receives it
+    drop(first);
+    while connections.is_connected(&0) {
+        time::sleep(Duration::from_millis(5)).await;
+    }
+    let gone = Recipients::SingleRecipient { recipient: 0 };
+    assert_eq!(
+        connections.send_to_recipients(&gone, "hi").await.unwrap(),
+        0
+    );
+
+    let tagged = Recipients::Tagged {
+        tag: "room".to_string(),
+    };
+    let err = connections
+        .send_to_recipients(&tagged, "hi")
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[tokio::test]
+async fn a_full_queue_holds_up_no_other_recipient() {
+    let server = WhisperServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    let running = server.launch("127.0.0.1:0").await.unwrap();
+    let connections = running.connections().clone();
+
+    // Stops reading once it has joined, until its queue is full
+    let stream = TcpStream::connect(running.local_addr()).await.unwrap();
+    let mut stuck = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(whisper(&mut stuck, 7).await, json!("not connected"));
+    let big = "x".repeat(64 * 1024);
+    while time::timeout(Duration::from_millis(100), connections.send_to(&0, &big))
+        .await
+        .is_ok()
+    {}
+
+    let stream = TcpStream::connect(running.local_addr()).await.unwrap();
+    let mut other = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(whisper(&mut other, 7).await, json!("not connected"));
+
+    let sending = tokio::spawn(async move {
+        connections
+            .send_to_recipients(&Recipients::Everyone, "all")
+            .await
+    });
+    let received = time::timeout(Duration::from_secs(5), recv(&mut other)).await;
+    assert_eq!(received.unwrap(), json!("all"));
+    assert!(!sending.is_finished());
+}
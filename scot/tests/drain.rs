@@ -0,0 +1,144 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+    sync::CancellationToken,
+};
+
+#[derive(Default)]
+struct Roster {
+    next_id: usize,
+    online: Vec<usize>,
+}
+
+impl State for Roster {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.online.push(self.next_id);
+        self.next_id
+    }
+
+    fn on_leave(&mut self, id: &usize) {
+        self.online.retain(|x| x != id);
+    }
+}
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = Arc<Mutex<Roster>>;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Roster>>,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct EchoServer {
+    roster: Arc<Mutex<Roster>>,
+    drain_timeout: Option<Duration>,
+}
+
+impl Server for EchoServer {
+    type State = Arc<Mutex<Roster>>;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Roster>> {
+        self.roster.clone()
+    }
+
+    fn drain_timeout(&self) -> Option<Duration> {
+        self.drain_timeout
+    }
+}
+
+type Client = Framed<TcpStream, LengthDelimitedCodec>;
+
+async fn echo(framed: &mut Client, message: &'static str) {
+    framed
+        .send(Bytes::from(format!("\"{message}\"")))
+        .await
+        .unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&frame).unwrap(),
+        json!(message)
+    );
+}
+
+/// Start draining a server with one client connected, which has joined.
+async fn drain_with_client(
+    drain_timeout: Option<Duration>,
+) -> (
+    Arc<Mutex<Roster>>,
+    Client,
+    tokio::task::JoinHandle<anyhow::Result<()>>,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let roster = Arc::new(Mutex::new(Roster::default()));
+    let server = EchoServer {
+        roster: roster.clone(),
+        drain_timeout,
+    };
+    let drain = CancellationToken::new();
+    let running = tokio::spawn({
+        let drain = drain.clone();
+        async move { server.start_with_drain(&listener, drain).await }
+    });
+
+    let mut client = Framed::new(
+        TcpStream::connect(addr).await.unwrap(),
+        LengthDelimitedCodec::new(),
+    );
+    echo(&mut client, "hello").await;
+    drain.cancel();
+    (roster, client, running)
+}
+
+#[tokio::test]
+async fn draining_serves_clients_until_they_leave() {
+    let (roster, mut client, running) = drain_with_client(None).await;
+
+    // Still served while draining
+    echo(&mut client, "still here").await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!running.is_finished());
+
+    drop(client);
+    running.await.unwrap().unwrap();
+    assert!(roster.lock().unwrap().online.is_empty());
+}
+
+#[tokio::test]
+async fn draining_disconnects_clients_after_the_timeout() {
+    let (roster, mut client, running) = drain_with_client(Some(Duration::from_millis(50))).await;
+
+    running.await.unwrap().unwrap();
+    assert!(roster.lock().unwrap().online.is_empty());
+    assert!(client.next().await.is_none());
+}
@@ -0,0 +1,89 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct ChatHandler;
+
+#[async_trait]
+impl MessageHandler for ChatHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    /// Pass chat messages on to everyone but their sender.
+    async fn handle_client_message(
+        msg: String,
+        id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        if msg != "join" {
+            channels
+                .try_broadcast(&msg, Recipients::everyone_except([*id]))
+                .unwrap();
+        }
+        channels.respond("done").await.unwrap();
+    }
+}
+
+struct ChatServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for ChatServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = ChatHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+async fn send(framed: &mut Framed<TcpStream, LengthDelimitedCodec>, msg: &str) {
+    let frame = serde_json::to_vec(msg).unwrap();
+    framed.send(Bytes::from(frame)).await.unwrap();
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn everyone_but_the_excluded_receives_it() {
+    let (listener, addr) = ChatServer::bind("127.0.0.1:0").await.unwrap();
+    let server = ChatServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let mut clients = Vec::new();
+    for _ in 0..3 {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+        send(&mut framed, "join").await;
+        assert_eq!(recv(&mut framed).await, json!("done"));
+        clients.push(framed);
+    }
+
+    send(&mut clients[0], "hello").await;
+    assert_eq!(recv(&mut clients[1]).await, json!("hello"));
+    assert_eq!(recv(&mut clients[2]).await, json!("hello"));
+
+    // The sender only gets the response to its own messages
+    assert_eq!(recv(&mut clients[0]).await, json!("done"));
+    send(&mut clients[0], "join").await;
+    assert_eq!(recv(&mut clients[0]).await, json!("done"));
+}
@@ -0,0 +1,126 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Default)]
+struct Roster {
+    next_id: usize,
+    online: Vec<usize>,
+}
+
+impl State for Roster {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.online.push(self.next_id);
+        self.next_id
+    }
+
+    fn on_leave(&mut self, id: &usize) {
+        self.online.retain(|x| x != id);
+    }
+}
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = Arc<Mutex<Roster>>;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Roster>>,
+    ) {
+        channels
+            .response_sender
+            .send(Value::from(msg))
+            .await
+            .unwrap();
+    }
+
+    async fn handle_bad_message(
+        _err: anyhow::Error,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Roster>>,
+    ) {
+        let reply = Value::from("bad message");
+        channels.response_sender.send(reply).await.unwrap();
+    }
+}
+
+struct EchoServer {
+    state: Arc<Mutex<Roster>>,
+}
+
+impl Server for EchoServer {
+    type State = Arc<Mutex<Roster>>;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Roster>> {
+        self.state.clone()
+    }
+}
+
+async fn start() -> (std::net::SocketAddr, Arc<Mutex<Roster>>) {
+    let (listener, addr) = EchoServer::bind("127.0.0.1:0").await.unwrap();
+    let state = Arc::<Mutex<Roster>>::default();
+    let server = EchoServer {
+        state: state.clone(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+    (addr, state)
+}
+
+async fn receive(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn bad_json_keeps_connection_open() {
+    let (addr, _state) = start().await;
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+
+    framed.send(Bytes::from("{not json")).await.unwrap();
+    assert_eq!(receive(&mut framed).await, Value::from("bad message"));
+
+    framed.send(Bytes::from(r#""still here""#)).await.unwrap();
+    assert_eq!(receive(&mut framed).await, Value::from("still here"));
+}
+
+#[tokio::test]
+async fn oversized_length_prefix_closes_connection() {
+    let (addr, state) = start().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // Claims a ~4 GiB frame, far above the default maximum frame length
+    stream
+        .write_all(&[0xff, 0xff, 0xff, 0xff, b'x'])
+        .await
+        .unwrap();
+
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    assert!(framed.next().await.is_none());
+    assert!(state.lock().unwrap().online.is_empty());
+}
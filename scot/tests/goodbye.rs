@@ -0,0 +1,158 @@
+use std::{
+    ops::ControlFlow,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{self, InputHandler},
+    server::{self, State},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Default)]
+struct Roster {
+    next_id: usize,
+    online: Vec<usize>,
+}
+
+impl State for Roster {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.online.push(self.next_id);
+        self.next_id
+    }
+
+    fn on_leave(&mut self, id: &usize) {
+        self.online.retain(|x| x != id);
+    }
+}
+
+struct IgnoreHandler;
+
+#[async_trait]
+impl server::MessageHandler for IgnoreHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = Arc<Mutex<Roster>>;
+
+    async fn handle_client_message(
+        _msg: Value,
+        _id: &usize,
+        _channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Roster>>,
+    ) {
+    }
+}
+
+/// Tells every client to leave as soon as it joins.
+struct DismissingServer {
+    roster: Arc<Mutex<Roster>>,
+}
+
+#[async_trait]
+impl Server for DismissingServer {
+    type State = Arc<Mutex<Roster>>;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = IgnoreHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Roster>> {
+        self.roster.clone()
+    }
+
+    async fn on_join_snapshot(
+        &self,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Roster>>,
+    ) {
+        channels.respond(&"leave").await.unwrap();
+    }
+}
+
+async fn start() -> (Arc<Mutex<Roster>>, std::net::SocketAddr) {
+    let roster = Arc::new(Mutex::new(Roster::default()));
+    let server = DismissingServer {
+        roster: roster.clone(),
+    };
+    let (listener, addr) = DismissingServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+    (roster, addr)
+}
+
+struct LeavingHandler;
+
+#[async_trait]
+impl client::MessageHandler for LeavingHandler {
+    type ServerMessage = String;
+
+    async fn handle_server_message(_msg: String, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        _msg: String,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        ControlFlow::Break(())
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+struct LeavingClient;
+
+impl Client for LeavingClient {
+    type ServerMessage = String;
+    type ServerMessageHandler = LeavingHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+}
+
+#[tokio::test]
+async fn server_has_handled_leaving_when_client_returns() {
+    let (roster, addr) = start().await;
+    let stream = TcpStream::connect(addr).await.unwrap();
+    LeavingClient.start_with_stream(stream).await.unwrap();
+
+    // No waiting needed: the server acknowledged after `on_leave`
+    let roster = roster.lock().unwrap();
+    assert_eq!(roster.next_id, 1);
+    assert!(roster.online.is_empty());
+}
+
+#[tokio::test]
+async fn server_acknowledges_goodbye_and_closes() {
+    let (roster, addr) = start().await;
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(&framed.next().await.unwrap().unwrap()[..], b"\"leave\"");
+
+    let goodbye = json!({ "scot": "goodbye" });
+    let frame = serde_json::to_vec(&goodbye).unwrap();
+    framed.send(Bytes::from(frame)).await.unwrap();
+
+    let frame = framed.next().await.unwrap().unwrap();
+    assert_eq!(serde_json::from_slice::<Value>(&frame).unwrap(), goodbye);
+    assert!(roster.lock().unwrap().online.is_empty());
+    assert!(framed.next().await.is_none());
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use parking_lot::Mutex;
+use scot::{
+    server::{MessageHandler, Recipients, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Default)]
+struct Counter {
+    next_id: usize,
+}
+
+impl State for Counter {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Burst {
+    count: usize,
+}
+
+struct BurstHandler;
+
+#[async_trait]
+impl MessageHandler for BurstHandler {
+    type ClientMessage = Burst;
+    type ClientID = usize;
+    type State = Arc<Mutex<Counter>>;
+
+    async fn handle_client_message(
+        msg: Burst,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Counter>>,
+    ) {
+        for i in 0..msg.count {
+            channels.try_broadcast(&i, Recipients::Everyone).unwrap();
+        }
+    }
+}
+
+struct BurstServer {
+    state: Arc<Mutex<Counter>>,
+}
+
+impl Server for BurstServer {
+    type State = Arc<Mutex<Counter>>;
+    type ClientID = usize;
+    type ClientMessage = Burst;
+    type ClientMessageHandler = BurstHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Counter>> {
+        self.state.clone()
+    }
+}
+
+#[tokio::test]
+async fn queued_broadcasts_are_delivered_after_half_close() {
+    let (listener, addr) = BurstServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move {
+        let server = BurstServer {
+            state: Arc::default(),
+        };
+        server.start_with_listener(&listener).await
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    let request = serde_json::to_vec(&Burst { count: 5 }).unwrap();
+    framed.send(Bytes::from(request)).await.unwrap();
+
+    // Half-close: the server sees EOF, but can still write to us
+    framed.get_mut().shutdown().await.unwrap();
+
+    let mut received = Vec::new();
+    while let Some(frame) = framed.next().await {
+        let value: Value = serde_json::from_slice(&frame.unwrap()).unwrap();
+        received.push(value);
+    }
+
+    let expected: Vec<Value> = (0..5).map(Value::from).collect();
+    assert_eq!(received, expected);
+}
@@ -0,0 +1,83 @@
+use std::ops::ControlFlow;
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{InputHandler, MessageHandler},
+    types::ValueSender,
+    Client,
+};
+use serde_json::Value;
+use tokio::net::TcpListener;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct LeaveHandler;
+
+#[async_trait]
+impl MessageHandler for LeaveHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        _msg: Value,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        ControlFlow::Break(())
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+struct RacingClient;
+
+impl Client for RacingClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = LeaveHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+}
+
+#[tokio::test]
+async fn connects_through_whichever_address_answers() {
+    // Only listen on IPv4, so an IPv6 address for localhost is refused
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let client = tokio::spawn(async move {
+        RacingClient
+            .start_happy_eyeballs(&format!("localhost:{port}"))
+            .await
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("\"bye\"")).await.unwrap();
+    let goodbye = framed.next().await.unwrap().unwrap();
+    framed.send(goodbye.freeze()).await.unwrap();
+    client.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn fails_when_no_address_answers() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+
+    assert!(RacingClient
+        .start_happy_eyeballs(&addr.to_string())
+        .await
+        .is_err());
+}
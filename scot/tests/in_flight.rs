@@ -0,0 +1,156 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::{net::TcpStream, sync::Semaphore, time};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// How many messages have been handled.
+static STARTED: AtomicUsize = AtomicUsize::new(0);
+/// Holds handler calls until the test lets them finish.
+static GATE: Semaphore = Semaphore::const_new(0);
+
+struct SlowHandler;
+
+#[async_trait]
+impl MessageHandler for SlowHandler {
+    type ClientMessage = u32;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: u32,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        STARTED.fetch_add(1, Ordering::SeqCst);
+        GATE.acquire().await.unwrap().forget();
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct LimitedServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for LimitedServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = u32;
+    type ClientMessageHandler = SlowHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    fn max_in_flight(&self) -> usize {
+        2
+    }
+}
+
+/// Records whether [`State::on_join`] was called on it.
+#[derive(Default)]
+struct JoinState {
+    joined: bool,
+}
+
+impl State for JoinState {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.joined = true;
+        0
+    }
+}
+
+/// Responds with whether the state the call was given went through
+/// [`State::on_join`].
+struct JoinedHandler;
+
+#[async_trait]
+impl MessageHandler for JoinedHandler {
+    type ClientMessage = u32;
+    type ClientID = usize;
+    type State = JoinState;
+
+    async fn handle_client_message(
+        _msg: u32,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        state: &mut JoinState,
+    ) {
+        channels.respond(&state.joined).await.unwrap();
+    }
+}
+
+struct JoinedServer;
+
+impl Server for JoinedServer {
+    type State = JoinState;
+    type ClientID = usize;
+    type ClientMessage = u32;
+    type ClientMessageHandler = JoinedHandler;
+
+    fn get_state(&self) -> JoinState {
+        JoinState::default()
+    }
+
+    fn max_in_flight(&self) -> usize {
+        2
+    }
+}
+
+#[tokio::test]
+async fn handler_calls_run_concurrently_up_to_the_limit() {
+    let server = LimitedServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    let running = server.launch("127.0.0.1:0").await.unwrap();
+    let stream = TcpStream::connect(running.local_addr()).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    for i in 0..3u32 {
+        framed.send(Bytes::from(i.to_string())).await.unwrap();
+    }
+
+    // The first two calls run at once and fill the limit, so the third
+    // message isn't read
+    while STARTED.load(Ordering::SeqCst) < 2 {
+        time::sleep(Duration::from_millis(5)).await;
+    }
+    time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(STARTED.load(Ordering::SeqCst), 2);
+
+    GATE.add_permits(3);
+    let mut responses = Vec::new();
+    for _ in 0..3 {
+        let frame = framed.next().await.unwrap().unwrap();
+        responses.push(serde_json::from_slice::<Value>(&frame).unwrap());
+    }
+    responses.sort_by_key(|response| response.as_u64());
+    assert_eq!(responses, [0, 1, 2].map(Value::from));
+    assert_eq!(STARTED.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn handler_calls_get_states_that_never_joined() {
+    let running = JoinedServer.launch("127.0.0.1:0").await.unwrap();
+    let stream = TcpStream::connect(running.local_addr()).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("0")).await.unwrap();
+
+    let frame = framed.next().await.unwrap().unwrap();
+    assert!(!serde_json::from_slice::<bool>(&frame).unwrap());
+}
@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.response_sender.send(msg).await.unwrap();
+    }
+}
+
+struct EchoServer {
+    ids: SequentialIdAllocator,
+    pretty: bool,
+}
+
+impl Server for EchoServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    fn json_pretty(&self) -> bool {
+        self.pretty
+    }
+}
+
+/// Send a message to an echo server and return the raw bytes of the reply.
+async fn echo(pretty: bool, message: &Value) -> Bytes {
+    let (listener, addr) = EchoServer::bind("127.0.0.1:0").await.unwrap();
+    let server = EchoServer {
+        ids: SequentialIdAllocator::new(),
+        pretty,
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    let request = serde_json::to_vec(message).unwrap();
+    framed.send(Bytes::from(request)).await.unwrap();
+    framed.next().await.unwrap().unwrap().freeze()
+}
+
+#[tokio::test]
+async fn compact_by_default() {
+    let message = json!({ "name": "scot", "tags": [1, 2] });
+    let reply = echo(false, &message).await;
+    assert_eq!(reply, serde_json::to_vec(&message).unwrap());
+}
+
+#[tokio::test]
+async fn pretty_when_enabled() {
+    let message = json!({ "name": "scot", "tags": [1, 2] });
+    let reply = echo(true, &message).await;
+    assert_eq!(reply, serde_json::to_vec_pretty(&message).unwrap());
+    assert_ne!(reply, serde_json::to_vec(&message).unwrap());
+}
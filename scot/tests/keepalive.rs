@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{self, InputHandler},
+    server::{self, SequentialIdAllocator},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct IgnoreHandler;
+
+#[async_trait]
+impl client::MessageHandler for IgnoreHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, _response_channel: &mut ValueSender) {}
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+struct IdleClient;
+
+impl Client for IdleClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = IgnoreHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+
+    fn keepalive_interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(50))
+    }
+}
+
+#[tokio::test]
+async fn idle_client_sends_keepalives() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        IdleClient.start_with_stream(stream).await
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    for _ in 0..2 {
+        let frame = tokio::time::timeout(Duration::from_secs(5), framed.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let value: Value = serde_json::from_slice(&frame).unwrap();
+        assert_eq!(value, json!({ "scot": "keepalive" }));
+    }
+}
+
+struct EchoHandler;
+
+#[async_trait]
+impl server::MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.response_sender.send(msg).await.unwrap();
+    }
+}
+
+struct EchoServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for EchoServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+#[tokio::test]
+async fn server_skips_keepalives() {
+    let (listener, addr) = EchoServer::bind("127.0.0.1:0").await.unwrap();
+    let server = EchoServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed
+        .send(Bytes::from(r#"{"scot":"keepalive"}"#))
+        .await
+        .unwrap();
+    framed.send(Bytes::from("\"hello\"")).await.unwrap();
+
+    let frame = framed.next().await.unwrap().unwrap();
+    let value: Value = serde_json::from_slice(&frame).unwrap();
+    assert_eq!(value, json!("hello"));
+}
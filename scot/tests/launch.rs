@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::{net::TcpStream, time};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct EchoServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for EchoServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+type Client = Framed<TcpStream, LengthDelimitedCodec>;
+
+/// Connect and wait until the client has joined.
+async fn join(addr: std::net::SocketAddr) -> Client {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("\"hello\"")).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&frame).unwrap(),
+        json!("hello")
+    );
+    framed
+}
+
+fn server() -> EchoServer {
+    EchoServer {
+        ids: SequentialIdAllocator::new(),
+    }
+}
+
+#[tokio::test]
+async fn shutdown_disconnects_everyone() {
+    let running = server().launch("127.0.0.1:0").await.unwrap();
+    let mut first = join(running.local_addr()).await;
+    let mut second = join(running.local_addr()).await;
+    assert_eq!(running.connection_count(), 2);
+    assert_eq!(running.connections().ids().len(), 2);
+
+    running.shutdown().await.unwrap();
+    assert!(first.next().await.is_none());
+    assert!(second.next().await.is_none());
+}
+
+#[tokio::test]
+async fn drain_waits_for_everyone_to_leave() {
+    let running = server().launch("127.0.0.1:0").await.unwrap();
+    let addr = running.local_addr();
+    let client = join(addr).await;
+
+    let draining = tokio::spawn(running.drain());
+    time::sleep(Duration::from_millis(50)).await;
+    assert!(!draining.is_finished());
+
+    drop(client);
+    draining.await.unwrap().unwrap();
+}
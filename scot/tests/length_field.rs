@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+
+    async fn handle_bad_message(
+        _err: anyhow::Error,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels
+            .respond(&json!({ "error": "bad message" }))
+            .await
+            .unwrap();
+    }
+}
+
+struct ShortPrefixServer;
+
+impl Server for ShortPrefixServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+
+    fn length_field_length(&self) -> usize {
+        2
+    }
+}
+
+fn codec(length_field_length: usize) -> LengthDelimitedCodec {
+    LengthDelimitedCodec::builder()
+        .length_field_length(length_field_length)
+        .new_codec()
+}
+
+async fn connect(length_field_length: usize) -> Framed<TcpStream, LengthDelimitedCodec> {
+    let (listener, addr) = ShortPrefixServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { ShortPrefixServer.start_with_listener(&listener).await });
+    let stream = TcpStream::connect(addr).await.unwrap();
+    Framed::new(stream, codec(length_field_length))
+}
+
+async fn reply(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn matching_length_fields_round_trip() {
+    let mut framed = connect(2).await;
+    framed.send(Bytes::from_static(b"\"hi\"")).await.unwrap();
+
+    assert_eq!(reply(&mut framed).await, json!("hi"));
+}
+
+#[tokio::test]
+async fn mismatched_length_fields_split_frames_wrongly() {
+    // The server reads the first 2 bytes of the 4-byte prefix as the length
+    // of an empty frame, which isn't valid JSON
+    let mut framed = connect(4).await;
+    framed.send(Bytes::from_static(b"\"hi\"")).await.unwrap();
+
+    // Read the reply as the server sent it
+    let stream = framed.into_inner();
+    let mut framed = Framed::new(stream, codec(2));
+    assert_eq!(reply(&mut framed).await, json!({ "error": "bad message" }));
+}
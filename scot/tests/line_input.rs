@@ -0,0 +1,56 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    client::{LineInputHandler, MessageHandler},
+    types::ValueSender,
+    Client,
+};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+struct IgnoreHandler;
+
+#[async_trait]
+impl MessageHandler for IgnoreHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, _response_channel: &mut ValueSender) {}
+}
+
+type Lines = LineInputHandler<&'static [u8], fn(String) -> Option<String>>;
+
+fn shout(line: String) -> Option<String> {
+    (!line.is_empty()).then(|| line.to_uppercase())
+}
+
+struct ScriptedClient;
+
+impl Client for ScriptedClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = IgnoreHandler;
+    type InputHandler = Lines;
+
+    fn input_handler(&self) -> Lines {
+        LineInputHandler::new(b"hello\n\nworld\r\nbye", shout)
+    }
+}
+
+#[tokio::test]
+async fn lines_are_mapped_and_sent() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        ScriptedClient.start_with_stream(stream).await
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    let mut received = Vec::new();
+    for _ in 0..3 {
+        let frame = framed.next().await.unwrap().unwrap();
+        received.push(serde_json::from_slice::<Value>(&frame).unwrap());
+    }
+    assert_eq!(received, [json!("HELLO"), json!("WORLD"), json!("BYE")]);
+}
@@ -0,0 +1,108 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Default)]
+struct Counter {
+    next_id: usize,
+}
+
+impl State for Counter {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+struct RelayHandler;
+
+#[async_trait]
+impl MessageHandler for RelayHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = Arc<Mutex<Counter>>;
+
+    async fn handle_client_message(
+        msg: String,
+        id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Counter>>,
+    ) {
+        if msg == "join" {
+            let reply = Value::from("joined");
+            channels.response_sender.send(reply).await.unwrap();
+            return;
+        }
+
+        let recipients = Recipients::everyone_but(id, [1, 2]);
+        channels.try_broadcast(&msg, recipients).unwrap();
+    }
+}
+
+struct RelayServer {
+    state: Arc<Mutex<Counter>>,
+}
+
+impl Server for RelayServer {
+    type State = Arc<Mutex<Counter>>;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = RelayHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Counter>> {
+        self.state.clone()
+    }
+}
+
+/// Connect to the server and wait until it has set up the connection.
+async fn join(addr: std::net::SocketAddr) -> Framed<TcpStream, LengthDelimitedCodec> {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from(r#""join""#)).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    assert_eq!(&frame[..], br#""joined""#);
+    framed
+}
+
+#[tokio::test]
+async fn clients_on_different_listeners_can_broadcast_to_each_other() {
+    let (first, first_addr) = RelayServer::bind("127.0.0.1:0").await.unwrap();
+    let (second, second_addr) = RelayServer::bind("127.0.0.1:0").await.unwrap();
+    let server = RelayServer {
+        state: Arc::default(),
+    };
+    tokio::spawn(async move { server.start_with_listeners(&[first, second]).await });
+
+    let mut alice = join(first_addr).await;
+    let mut bob = join(second_addr).await;
+
+    alice.send(Bytes::from(r#""hello bob""#)).await.unwrap();
+    let frame = bob.next().await.unwrap().unwrap();
+    assert_eq!(&frame[..], br#""hello bob""#);
+
+    bob.send(Bytes::from(r#""hello alice""#)).await.unwrap();
+    let frame = alice.next().await.unwrap().unwrap();
+    assert_eq!(&frame[..], br#""hello alice""#);
+}
+
+#[tokio::test]
+async fn starting_without_listeners_fails() {
+    let server = RelayServer {
+        state: Arc::default(),
+    };
+    assert!(server.start_with_listeners(&[]).await.is_err());
+}
@@ -0,0 +1,134 @@
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    envelope::{self, Metadata},
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// Unknown fields are rejected, so metadata has to be stripped for these
+/// to be handled at all.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+enum Request {
+    Ping,
+    Say { text: String },
+}
+
+struct MetadataHandler;
+
+#[async_trait]
+impl MessageHandler for MetadataHandler {
+    type ClientMessage = Request;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Request,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        let reply = match msg {
+            Request::Ping => json!("pong"),
+            Request::Say { text } => json!({ "said": text }),
+        };
+        channels.respond(&reply).await.unwrap();
+    }
+
+    async fn handle_expired(
+        _msg: Request,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&"expired").await.unwrap();
+    }
+}
+
+struct MetadataServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for MetadataServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Request;
+    type ClientMessageHandler = MetadataHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+async fn request(framed: &mut Framed<TcpStream, LengthDelimitedCodec>, value: Value) -> Value {
+    let bytes = serde_json::to_vec(&value).unwrap();
+    framed.send(Bytes::from(bytes)).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn metadata_is_stripped_before_handling() {
+    let (listener, addr) = MetadataServer::bind("127.0.0.1:0").await.unwrap();
+    let server = MetadataServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    let later = Metadata::default().deadline(SystemTime::now() + Duration::from_secs(60));
+    let earlier = Metadata::default().deadline(SystemTime::now() - Duration::from_secs(60));
+
+    // Struct variants serialize to an object, which the metadata is added to
+    let say = serde_json::to_value(Request::Say {
+        text: "hi".to_string(),
+    })
+    .unwrap();
+    let say = envelope::with_metadata(say, &later);
+    assert!(say.get("__scot").is_some());
+    assert_eq!(request(&mut framed, say).await, json!({ "said": "hi" }));
+
+    // Unit variants don't, so they're carried inside the metadata
+    let ping = serde_json::to_value(Request::Ping).unwrap();
+    let ping = envelope::with_metadata(ping, &later);
+    assert_eq!(request(&mut framed, ping).await, json!("pong"));
+
+    let late = serde_json::to_value(Request::Ping).unwrap();
+    let late = envelope::with_metadata(late, &earlier);
+    assert_eq!(request(&mut framed, late).await, json!("expired"));
+}
+
+#[tokio::test]
+async fn messages_without_metadata_are_handled() {
+    let (listener, addr) = MetadataServer::bind("127.0.0.1:0").await.unwrap();
+    let server = MetadataServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    let said = json!({ "said": "hi" });
+
+    // Deserialized straight from the frame
+    let say = json!({ "Say": { "text": "hi" } });
+    assert_eq!(request(&mut framed, say).await, said);
+
+    // Escaped field names are looked inside first, with the same result
+    let frame = br#"{ "S\u0061y": { "text": "hi" } }"#;
+    framed.send(Bytes::from_static(frame)).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    assert_eq!(serde_json::from_slice::<Value>(&frame).unwrap(), said);
+}
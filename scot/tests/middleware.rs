@@ -0,0 +1,95 @@
+use std::ops::ControlFlow;
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct ShoutingHandler;
+
+#[async_trait]
+impl MessageHandler for ShoutingHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn pre_handle(
+        msg: &mut String,
+        _id: &usize,
+        _channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) -> ControlFlow<()> {
+        if msg.starts_with("drop") {
+            return ControlFlow::Break(());
+        }
+        *msg = msg.to_uppercase();
+        ControlFlow::Continue(())
+    }
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+
+    async fn post_handle(
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&"handled").await.unwrap();
+    }
+}
+
+struct ShoutingServer;
+
+impl Server for ShoutingServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = ShoutingHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+}
+
+#[tokio::test]
+async fn hooks_run_around_handling() {
+    let (listener, addr) = ShoutingServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { ShoutingServer.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    for msg in ["hello", "drop this", "bye"] {
+        let frame = serde_json::to_vec(&msg).unwrap();
+        framed.send(Bytes::from(frame)).await.unwrap();
+    }
+
+    let mut replies = Vec::new();
+    for _ in 0..4 {
+        let frame = framed.next().await.unwrap().unwrap();
+        replies.push(serde_json::from_slice::<Value>(&frame).unwrap());
+    }
+    assert_eq!(
+        replies,
+        [
+            json!("HELLO"),
+            json!("handled"),
+            json!("BYE"),
+            json!("handled")
+        ]
+    );
+}
@@ -0,0 +1,133 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use futures::{channel::mpsc, prelude::*};
+use scot::{
+    server::{ConnectionObserver, MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Accept,
+    Join(usize),
+    Message(usize),
+    Error(usize),
+    Leave(usize),
+}
+
+struct Recorder {
+    events: Mutex<mpsc::UnboundedSender<Event>>,
+}
+
+impl Recorder {
+    fn record(&self, event: Event) {
+        self.events.lock().unwrap().unbounded_send(event).unwrap();
+    }
+}
+
+impl ConnectionObserver<usize> for Recorder {
+    fn on_accept(&self, _addr: SocketAddr) {
+        self.record(Event::Accept);
+    }
+
+    fn on_join(&self, id: &usize) {
+        self.record(Event::Join(*id));
+    }
+
+    fn on_message(&self, id: &usize) {
+        self.record(Event::Message(*id));
+    }
+
+    fn on_leave(&self, id: &usize) {
+        self.record(Event::Leave(*id));
+    }
+
+    fn on_error(&self, id: &usize, _err: &anyhow::Error) {
+        self.record(Event::Error(*id));
+    }
+}
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels
+            .response_sender
+            .send(Value::from(msg))
+            .await
+            .unwrap();
+    }
+}
+
+struct ObservedServer {
+    ids: SequentialIdAllocator,
+    observer: Arc<Recorder>,
+}
+
+impl Server for ObservedServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    fn observer(&self) -> Option<Arc<dyn ConnectionObserver<usize>>> {
+        Some(self.observer.clone())
+    }
+}
+
+#[tokio::test]
+async fn observer_sees_connection_lifecycle() {
+    let (sender, mut events) = mpsc::unbounded();
+    let (listener, addr) = ObservedServer::bind("127.0.0.1:0").await.unwrap();
+    let server = ObservedServer {
+        ids: SequentialIdAllocator::new(),
+        observer: Arc::new(Recorder {
+            events: Mutex::new(sender),
+        }),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("\"hello\"")).await.unwrap();
+    framed.next().await.unwrap().unwrap();
+    framed.send(Bytes::from("not json")).await.unwrap();
+    drop(framed);
+
+    let received: Vec<Event> = events.by_ref().take(5).collect().await;
+    assert_eq!(
+        received,
+        vec![
+            Event::Accept,
+            Event::Join(0),
+            Event::Message(0),
+            Event::Error(0),
+            Event::Leave(0),
+        ]
+    );
+}
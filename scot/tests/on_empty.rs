@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Default)]
+struct Lobby {
+    next_id: usize,
+    emptied: usize,
+}
+
+impl State for Lobby {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+struct IgnoreHandler;
+
+#[async_trait]
+impl MessageHandler for IgnoreHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = Arc<Mutex<Lobby>>;
+
+    async fn handle_client_message(
+        _msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Lobby>>,
+    ) {
+        channels.respond("joined").await.unwrap();
+    }
+}
+
+struct LobbyServer {
+    lobby: Arc<Mutex<Lobby>>,
+}
+
+impl Server for LobbyServer {
+    type State = Arc<Mutex<Lobby>>;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = IgnoreHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Lobby>> {
+        self.lobby.clone()
+    }
+
+    fn on_empty(state: &mut Arc<Mutex<Lobby>>) {
+        state.lock().unwrap().emptied += 1;
+    }
+}
+
+async fn join(addr: std::net::SocketAddr) -> Framed<TcpStream, LengthDelimitedCodec> {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("null")).await.unwrap();
+    framed.next().await.unwrap().unwrap();
+    framed
+}
+
+/// Leave, waiting for the server to have handled it.
+async fn leave(mut framed: Framed<TcpStream, LengthDelimitedCodec>) {
+    let goodbye = serde_json::to_vec(&json!({ "scot": "goodbye" })).unwrap();
+    framed.send(Bytes::from(goodbye)).await.unwrap();
+    framed.next().await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn called_when_last_client_leaves() {
+    let lobby = Arc::new(Mutex::new(Lobby::default()));
+    let server = LobbyServer {
+        lobby: lobby.clone(),
+    };
+    let (listener, addr) = LobbyServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let first = join(addr).await;
+    let second = join(addr).await;
+    leave(first).await;
+    assert_eq!(lobby.lock().unwrap().emptied, 0);
+    leave(second).await;
+    assert_eq!(lobby.lock().unwrap().emptied, 1);
+
+    // Filling up and emptying again calls it again
+    leave(join(addr).await).await;
+    assert_eq!(lobby.lock().unwrap().emptied, 2);
+}
@@ -0,0 +1,148 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator, ServerOptions},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct TunedServer;
+
+impl Server for TunedServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+
+    fn options(&self) -> ServerOptions {
+        ServerOptions::default()
+            .length_field_length(2)
+            .write_timeout(Duration::from_secs(5))
+            .nodelay(true)
+    }
+}
+
+struct DefaultServer;
+
+impl Server for DefaultServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+
+    fn json_pretty(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn default_options_collect_the_individual_methods() {
+    let options = DefaultServer.options();
+    assert!(options.json_pretty);
+    assert_eq!(options.length_field_length, 4);
+    assert_eq!(options.write_timeout, None);
+}
+
+#[tokio::test]
+async fn options_configure_connections() {
+    let (listener, addr) = TunedServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { TunedServer.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let codec = LengthDelimitedCodec::builder()
+        .length_field_length(2)
+        .new_codec();
+    let mut framed = Framed::new(stream, codec);
+    framed.send(Bytes::from("\"hi\"")).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&frame).unwrap(),
+        json!("hi")
+    );
+}
+
+/// Counts how often its options are read.
+struct CountingServer {
+    reads: Arc<AtomicUsize>,
+}
+
+impl Server for CountingServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+
+    fn options(&self) -> ServerOptions {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        ServerOptions::default().length_field_length(2)
+    }
+}
+
+#[tokio::test]
+async fn options_are_only_read_when_starting() {
+    let reads = Arc::new(AtomicUsize::new(0));
+    let server = CountingServer {
+        reads: reads.clone(),
+    };
+    let running = server.launch("127.0.0.1:0").await.unwrap();
+
+    // Starting may read them a few times, but serving clients doesn't
+    let mut started = 0;
+    for i in 0..3 {
+        if i == 1 {
+            started = reads.load(Ordering::SeqCst);
+        }
+        let stream = TcpStream::connect(running.local_addr()).await.unwrap();
+        let codec = LengthDelimitedCodec::builder()
+            .length_field_length(2)
+            .new_codec();
+        let mut framed = Framed::new(stream, codec);
+        framed.send(Bytes::from("\"hi\"")).await.unwrap();
+        framed.next().await.unwrap().unwrap();
+    }
+    assert_eq!(reads.load(Ordering::SeqCst), started);
+}
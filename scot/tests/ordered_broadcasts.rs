@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct AnnounceHandler;
+
+#[async_trait]
+impl MessageHandler for AnnounceHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    /// Acknowledge the message on both sides of announcing it.
+    async fn handle_client_message(
+        _msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond("before").await.unwrap();
+        channels
+            .broadcast("news", Recipients::Everyone)
+            .await
+            .unwrap();
+        channels.respond("after").await.unwrap();
+    }
+}
+
+struct AnnouncingServer {
+    ordered: bool,
+}
+
+impl Server for AnnouncingServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = AnnounceHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+
+    fn ordered_broadcasts(&self) -> bool {
+        self.ordered
+    }
+}
+
+async fn connect(ordered: bool) -> (Framed<TcpStream, LengthDelimitedCodec>, TcpStream) {
+    let (listener, addr) = AnnouncingServer::bind("127.0.0.1:0").await.unwrap();
+    let server = AnnouncingServer { ordered };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let sender = TcpStream::connect(addr).await.unwrap();
+    let bystander = TcpStream::connect(addr).await.unwrap();
+    (Framed::new(sender, LengthDelimitedCodec::new()), bystander)
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn own_broadcast_arrives_in_order_with_responses() {
+    let (mut sender, bystander) = connect(true).await;
+    let mut bystander = Framed::new(bystander, LengthDelimitedCodec::new());
+    // Let the bystander join before anything is broadcast
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    sender.send(Bytes::from("null")).await.unwrap();
+    assert_eq!(recv(&mut sender).await, json!("before"));
+    assert_eq!(recv(&mut sender).await, json!("news"));
+    assert_eq!(recv(&mut sender).await, json!("after"));
+    assert_eq!(recv(&mut bystander).await, json!("news"));
+
+    // The copy going around the broadcast channel isn't delivered again
+    sender.send(Bytes::from("null")).await.unwrap();
+    assert_eq!(recv(&mut sender).await, json!("before"));
+    assert_eq!(recv(&mut sender).await, json!("news"));
+    assert_eq!(recv(&mut sender).await, json!("after"));
+}
+
+#[tokio::test]
+async fn own_broadcast_arrives_after_responses_by_default() {
+    let (mut sender, _bystander) = connect(false).await;
+
+    sender.send(Bytes::from("null")).await.unwrap();
+    assert_eq!(recv(&mut sender).await, json!("before"));
+    assert_eq!(recv(&mut sender).await, json!("after"));
+    assert_eq!(recv(&mut sender).await, json!("news"));
+}
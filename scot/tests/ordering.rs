@@ -0,0 +1,69 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct BroadcastFirstHandler;
+
+#[async_trait]
+impl MessageHandler for BroadcastFirstHandler {
+    type ClientMessage = ();
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        _msg: (),
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels
+            .try_broadcast(&"broadcast", Recipients::Everyone)
+            .unwrap();
+        let response = Value::from("response");
+        channels.response_sender.send(response).await.unwrap();
+    }
+}
+
+struct OrderingServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for OrderingServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = ();
+    type ClientMessageHandler = BroadcastFirstHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+#[tokio::test]
+async fn responses_arrive_before_broadcasts_from_the_same_handler() {
+    let (listener, addr) = OrderingServer::bind("127.0.0.1:0").await.unwrap();
+    let server = OrderingServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("null")).await.unwrap();
+
+    for expected in ["response", "broadcast"] {
+        let frame = framed.next().await.unwrap().unwrap();
+        let value: Value = serde_json::from_slice(&frame).unwrap();
+        assert_eq!(value, Value::from(expected));
+    }
+}
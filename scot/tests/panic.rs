@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use parking_lot::Mutex;
+use scot::{
+    server::{MessageHandler, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Default)]
+struct Record {
+    next_id: usize,
+    left: Vec<usize>,
+    panics: Vec<String>,
+}
+
+impl State for Record {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn on_leave(&mut self, id: &usize) {
+        self.left.push(*id);
+    }
+}
+
+struct FragileHandler;
+
+#[async_trait]
+impl MessageHandler for FragileHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = Arc<Mutex<Record>>;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Record>>,
+    ) {
+        assert_ne!(msg, "boom", "handler exploded");
+        channels
+            .response_sender
+            .send(Value::from(msg))
+            .await
+            .unwrap();
+    }
+}
+
+struct FragileServer {
+    state: Arc<Mutex<Record>>,
+}
+
+impl Server for FragileServer {
+    type State = Arc<Mutex<Record>>;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = FragileHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Record>> {
+        self.state.clone()
+    }
+
+    fn handle_connection_panic(err: anyhow::Error, state: &mut Self::State) {
+        state.lock().panics.push(err.to_string());
+    }
+}
+
+#[tokio::test]
+async fn panicking_handler_disconnects_client() {
+    let state: Arc<Mutex<Record>> = Arc::default();
+    let (listener, addr) = FragileServer::bind("127.0.0.1:0").await.unwrap();
+    let server = FragileServer {
+        state: state.clone(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("\"boom\"")).await.unwrap();
+    assert!(framed.next().await.is_none());
+
+    let state = state.lock();
+    assert_eq!(state.left, vec![1]);
+    assert_eq!(state.panics.len(), 1);
+    assert!(state.panics[0].contains("handler exploded"));
+}
+
+#[tokio::test]
+async fn other_clients_are_unaffected_by_a_panic() {
+    let (listener, addr) = FragileServer::bind("127.0.0.1:0").await.unwrap();
+    let server = FragileServer {
+        state: Arc::default(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut survivor = Framed::new(stream, LengthDelimitedCodec::new());
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut victim = Framed::new(stream, LengthDelimitedCodec::new());
+
+    victim.send(Bytes::from("\"boom\"")).await.unwrap();
+    assert!(victim.next().await.is_none());
+
+    survivor.send(Bytes::from("\"still here\"")).await.unwrap();
+    let frame = survivor.next().await.unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&frame).unwrap(),
+        Value::from("still here")
+    );
+}
@@ -0,0 +1,103 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, PauseHandle, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    time,
+};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct EchoServer;
+
+impl Server for EchoServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+}
+
+type Client = Framed<TcpStream, LengthDelimitedCodec>;
+
+async fn connect(listener: &TcpListener) -> Client {
+    let stream = TcpStream::connect(listener.local_addr().unwrap())
+        .await
+        .unwrap();
+    Framed::new(stream, LengthDelimitedCodec::new())
+}
+
+async fn echo(client: &mut Client, msg: Value) -> Value {
+    send(client, &msg).await;
+    let frame = client.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+async fn send(client: &mut Client, msg: &Value) {
+    let frame = serde_json::to_vec(msg).unwrap();
+    client.send(Bytes::from(frame)).await.unwrap();
+}
+
+#[tokio::test]
+async fn paused_server_holds_off_new_clients() {
+    let listener = Arc::new(TcpListener::bind("127.0.0.1:0").await.unwrap());
+    let pause = PauseHandle::new();
+    let server = {
+        let (listener, pause) = (listener.clone(), pause.clone());
+        tokio::spawn(async move { EchoServer.start_with_pause(&listener, pause).await })
+    };
+
+    let mut connected = connect(&listener).await;
+    assert_eq!(echo(&mut connected, json!(1)).await, json!(1));
+
+    pause.pause();
+    time::sleep(Duration::from_millis(50)).await;
+
+    // A new client only gets as far as the listen backlog
+    let mut waiting = connect(&listener).await;
+    send(&mut waiting, &json!("waiting")).await;
+    let held_off = time::timeout(Duration::from_millis(200), waiting.next()).await;
+    assert!(held_off.is_err());
+
+    // Clients that were already connected are still served
+    assert_eq!(echo(&mut connected, json!(2)).await, json!(2));
+
+    // Once resumed, the waiting client is accepted and its message handled
+    pause.resume();
+    let frame = waiting.next().await.unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&frame).unwrap(),
+        json!("waiting")
+    );
+    assert!(!server.is_finished());
+}
@@ -0,0 +1,275 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{self, InputHandler},
+    server::{ConnectionObserver, MessageHandler, SequentialIdAllocator},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde_json::{json, Value};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    time,
+};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+type Framing = Framed<TcpStream, LengthDelimitedCodec>;
+
+async fn send(framed: &mut Framing, value: Value) {
+    let frame = serde_json::to_vec(&value).unwrap();
+    framed.send(Bytes::from(frame)).await.unwrap();
+}
+
+async fn recv(framed: &mut Framing) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+/// Echoes messages, and pings the client when asked to.
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        if msg == "ping me" {
+            channels.response_sender.ping().unwrap();
+        } else {
+            channels.respond(&msg).await.unwrap();
+        }
+    }
+}
+
+#[derive(Default)]
+struct PongCounter(AtomicUsize);
+
+impl ConnectionObserver<usize> for PongCounter {
+    fn on_pong(&self, _id: &usize) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+struct EchoServer {
+    ids: SequentialIdAllocator,
+    pongs: Arc<PongCounter>,
+}
+
+impl Server for EchoServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    fn observer(&self) -> Option<Arc<dyn ConnectionObserver<usize>>> {
+        Some(self.pongs.clone())
+    }
+}
+
+#[tokio::test]
+async fn server_answers_pings_without_handlers() {
+    let pongs = Arc::new(PongCounter::default());
+    let server = EchoServer {
+        ids: SequentialIdAllocator::new(),
+        pongs: pongs.clone(),
+    };
+    let running = server.launch("127.0.0.1:0").await.unwrap();
+    let stream = TcpStream::connect(running.local_addr()).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+
+    // The handler would have echoed it
+    send(&mut framed, json!({ "scot": "ping" })).await;
+    assert_eq!(recv(&mut framed).await, json!({ "scot": "pong" }));
+    send(&mut framed, json!("hello")).await;
+    assert_eq!(recv(&mut framed).await, json!("hello"));
+
+    send(&mut framed, json!("ping me")).await;
+    assert_eq!(recv(&mut framed).await, json!({ "scot": "ping" }));
+    send(&mut framed, json!({ "scot": "pong" })).await;
+    while pongs.0.load(Ordering::SeqCst) == 0 {
+        time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+static PONGS: AtomicUsize = AtomicUsize::new(0);
+
+struct CountingHandler;
+
+#[async_trait]
+impl client::MessageHandler for CountingHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, _response_channel: &mut ValueSender) {
+        HANDLED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    async fn on_pong() {
+        PONGS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+struct QuietClient;
+
+impl Client for QuietClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = CountingHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+}
+
+#[tokio::test]
+async fn client_answers_pings_without_handlers() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        QuietClient.start_with_stream(stream).await
+    });
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    send(&mut framed, json!({ "scot": "ping" })).await;
+    assert_eq!(recv(&mut framed).await, json!({ "scot": "pong" }));
+
+    send(&mut framed, json!({ "scot": "pong" })).await;
+    send(&mut framed, json!("hello")).await;
+    while HANDLED.load(Ordering::SeqCst) == 0 {
+        time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+    assert_eq!(PONGS.load(Ordering::SeqCst), 1);
+}
+
+/// Echoes any message, whatever it looks like.
+struct ValueEchoHandler;
+
+#[async_trait]
+impl MessageHandler for ValueEchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct ValueEchoServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for ValueEchoServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = ValueEchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+static ECHOED: AtomicUsize = AtomicUsize::new(0);
+
+struct PingShapedHandler;
+
+#[async_trait]
+impl client::MessageHandler for PingShapedHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(msg: Value, _response_channel: &mut ValueSender) {
+        assert_eq!(msg, json!({ "scot": "ping" }));
+        ECHOED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+struct PingShapedInput {
+    sent: bool,
+}
+
+#[async_trait]
+impl InputHandler for PingShapedInput {
+    async fn next_input(&mut self, message_channel: &mut ValueSender) {
+        if self.sent {
+            future::pending::<()>().await;
+        }
+        self.sent = true;
+        message_channel
+            .send_message(&json!({ "scot": "ping" }))
+            .await
+            .unwrap();
+    }
+}
+
+struct PingShapedClient;
+
+impl Client for PingShapedClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = PingShapedHandler;
+    type InputHandler = PingShapedInput;
+
+    fn input_handler(&self) -> PingShapedInput {
+        PingShapedInput { sent: false }
+    }
+}
+
+#[tokio::test]
+async fn messages_shaped_like_control_frames_reach_handlers() {
+    let server = ValueEchoServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    let running = server.launch("127.0.0.1:0").await.unwrap();
+    let addr = running.local_addr();
+    tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        PingShapedClient.start_with_stream(stream).await
+    });
+
+    time::timeout(Duration::from_secs(5), async {
+        while ECHOED.load(Ordering::SeqCst) == 0 {
+            time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,126 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// Big enough that a handful of them back up the connection.
+const BULK_LEN: usize = 2 * 1024 * 1024;
+const BULK_COUNT: usize = 10;
+
+struct BulkHandler;
+
+#[async_trait]
+impl MessageHandler for BulkHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    /// Send a lot of bulk data, followed by something urgent.
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        let bulk = "x".repeat(BULK_LEN);
+        match msg.as_str() {
+            "respond" => {
+                for _ in 0..BULK_COUNT {
+                    channels.respond(&bulk).await.unwrap();
+                }
+                channels.respond_priority("kick").unwrap();
+            }
+            "broadcast" => {
+                for _ in 0..BULK_COUNT {
+                    channels.try_broadcast(&bulk, Recipients::Everyone).unwrap();
+                }
+                channels
+                    .broadcast_priority("going down", Recipients::Everyone)
+                    .unwrap();
+            }
+            _ => channels.respond("joined").await.unwrap(),
+        }
+    }
+}
+
+struct BulkServer;
+
+impl Server for BulkServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = BulkHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+}
+
+type Client = Framed<TcpStream, LengthDelimitedCodec>;
+
+async fn join(addr: std::net::SocketAddr) -> Client {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    send(&mut framed, "join").await;
+    framed.next().await.unwrap().unwrap();
+    framed
+}
+
+async fn send(client: &mut Client, msg: &str) {
+    let frame = serde_json::to_vec(msg).unwrap();
+    client.send(Bytes::from(frame)).await.unwrap();
+}
+
+/// Read until `urgent` arrives, returning how much bulk data came first,
+/// then read the rest of the bulk data.
+async fn position_of(client: &mut Client, urgent: &str) -> usize {
+    let mut bulk_before = 0;
+    loop {
+        let frame = client.next().await.unwrap().unwrap();
+        if frame.len() > BULK_LEN {
+            bulk_before += 1;
+        } else {
+            assert_eq!(
+                serde_json::from_slice::<Value>(&frame).unwrap(),
+                json!(urgent)
+            );
+            break;
+        }
+    }
+    for _ in bulk_before..BULK_COUNT {
+        let frame = client.next().await.unwrap().unwrap();
+        assert!(frame.len() > BULK_LEN);
+    }
+    bulk_before
+}
+
+#[tokio::test]
+async fn priority_responses_jump_the_queue() {
+    let (listener, addr) = BulkServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { BulkServer.start_with_listener(&listener).await });
+
+    let mut client = join(addr).await;
+    send(&mut client, "respond").await;
+    assert!(position_of(&mut client, "kick").await < BULK_COUNT);
+}
+
+#[tokio::test]
+async fn priority_broadcasts_jump_the_queue() {
+    let (listener, addr) = BulkServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { BulkServer.start_with_listener(&listener).await });
+
+    let mut sender = join(addr).await;
+    let mut receiver = join(addr).await;
+    send(&mut sender, "broadcast").await;
+    assert!(position_of(&mut receiver, "going down").await < BULK_COUNT);
+    assert!(position_of(&mut sender, "going down").await < BULK_COUNT);
+}
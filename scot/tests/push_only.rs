@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use futures::prelude::*;
+use scot::{
+    server::{NoOpHandler, Recipients, SequentialIdAllocator},
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::{net::TcpStream, time};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// Pushes prices to its clients, and doesn't listen to them.
+struct Ticker {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for Ticker {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = NoOpHandler<Value, usize, SequentialIdAllocator>;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+#[tokio::test]
+async fn push_only_server_ignores_clients_until_they_leave() {
+    let ticker = Ticker {
+        ids: SequentialIdAllocator::new(),
+    };
+    let running = ticker.launch("127.0.0.1:0").await.unwrap();
+    let stream = TcpStream::connect(running.local_addr()).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+
+    // Nothing answers what the client sends
+    framed
+        .send(Bytes::from("{\"buy\":\"ACME\"}"))
+        .await
+        .unwrap();
+    while running.connection_count() == 0 {
+        time::sleep(Duration::from_millis(5)).await;
+    }
+    let price = json!({ "ACME": 42 });
+    let pushed = running
+        .connections()
+        .send_to_recipients(&Recipients::Everyone, &price)
+        .await
+        .unwrap();
+    assert_eq!(pushed, 1);
+    let frame = framed.next().await.unwrap().unwrap();
+    assert_eq!(serde_json::from_slice::<Value>(&frame).unwrap(), price);
+
+    // The server still notices the client leaving
+    drop(framed);
+    while running.connection_count() > 0 {
+        time::sleep(Duration::from_millis(5)).await;
+    }
+}
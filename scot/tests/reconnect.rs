@@ -0,0 +1,100 @@
+use std::{ops::ControlFlow, time::Duration};
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{InputHandler, MessageHandler, RetryPolicy},
+    types::ValueSender,
+    Client,
+};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct ByeHandler;
+
+#[async_trait]
+impl MessageHandler for ByeHandler {
+    type ServerMessage = String;
+
+    async fn handle_server_message(_msg: String, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        msg: String,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        if msg == "bye" {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+}
+
+/// Says hello once, and nothing after that.
+struct HelloOnce {
+    said_hello: bool,
+}
+
+#[async_trait]
+impl InputHandler for HelloOnce {
+    async fn next_input(&mut self, message_channel: &mut ValueSender) {
+        if self.said_hello {
+            future::pending::<()>().await;
+        }
+        self.said_hello = true;
+        message_channel.send(json!("hello")).await.unwrap();
+    }
+}
+
+struct RejoiningClient;
+
+#[async_trait]
+impl Client for RejoiningClient {
+    type ServerMessage = String;
+    type ServerMessageHandler = ByeHandler;
+    type InputHandler = HelloOnce;
+
+    fn input_handler(&self) -> HelloOnce {
+        HelloOnce { said_hello: false }
+    }
+
+    async fn on_reconnected(&self, sender: &mut ValueSender) {
+        sender.send(json!("rejoin")).await.unwrap();
+    }
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn reconnects_and_restores_session() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let policy = RetryPolicy::default().initial_delay(Duration::from_millis(10));
+    let client =
+        tokio::spawn(async move { RejoiningClient.start_reconnecting(&addr, policy).await });
+
+    // The first connection is dropped once the client said hello
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(recv(&mut framed).await, json!("hello"));
+    drop(framed);
+
+    // The client comes back and restores its session, keeping its input
+    // handler, which has already said hello
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(recv(&mut framed).await, json!("rejoin"));
+
+    // Disconnecting on purpose doesn't reconnect
+    framed.send(Bytes::from("\"bye\"")).await.unwrap();
+    assert_eq!(recv(&mut framed).await, json!({ "scot": "goodbye" }));
+    drop(framed);
+    client.await.unwrap().unwrap();
+}
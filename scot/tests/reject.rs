@@ -0,0 +1,121 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use parking_lot::Mutex;
+use scot::{
+    server::{MessageHandler, RejectReason, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// Lets a single client in at a time.
+#[derive(Default)]
+struct Room {
+    next_id: usize,
+    occupant: Option<usize>,
+}
+
+impl State for Room {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.occupant = Some(self.next_id);
+        self.next_id
+    }
+
+    fn try_on_join(&mut self) -> Result<usize, RejectReason> {
+        if self.occupant.is_some() {
+            Err(RejectReason::with_message(Value::from("full")))
+        } else {
+            Ok(self.on_join())
+        }
+    }
+
+    fn on_leave(&mut self, _id: &usize) {
+        self.occupant = None;
+    }
+}
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = Arc<Mutex<Room>>;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Room>>,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct RoomServer {
+    state: Arc<Mutex<Room>>,
+}
+
+impl Server for RoomServer {
+    type State = Arc<Mutex<Room>>;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Room>> {
+        self.state.clone()
+    }
+}
+
+async fn connect(addr: std::net::SocketAddr) -> Framed<TcpStream, LengthDelimitedCodec> {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    Framed::new(stream, LengthDelimitedCodec::new())
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Option<Value> {
+    let frame = framed.next().await?.unwrap();
+    Some(serde_json::from_slice(&frame).unwrap())
+}
+
+#[tokio::test]
+async fn rejected_clients_are_told_and_disconnected() {
+    let state: Arc<Mutex<Room>> = Arc::default();
+    let (listener, addr) = RoomServer::bind("127.0.0.1:0").await.unwrap();
+    let server = RoomServer {
+        state: state.clone(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let mut first = connect(addr).await;
+    first.send(Bytes::from("\"hi\"")).await.unwrap();
+    assert_eq!(recv(&mut first).await, Some(Value::from("hi")));
+
+    let mut second = connect(addr).await;
+    assert_eq!(recv(&mut second).await, Some(Value::from("full")));
+    assert_eq!(recv(&mut second).await, None);
+
+    // Rejected clients never joined, so they don't free up the room
+    assert_eq!(state.lock().occupant, Some(1));
+
+    drop(first);
+    for _ in 0..50 {
+        if state.lock().occupant.is_none() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let mut third = connect(addr).await;
+    third.send(Bytes::from("\"hello\"")).await.unwrap();
+    assert_eq!(recv(&mut third).await, Some(Value::from("hello")));
+}
@@ -0,0 +1,89 @@
+use std::{collections::HashMap, time::Duration};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, SequentialIdAllocator},
+    types::{BroadcastError, ServerMessageChannels},
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct AnnounceHandler;
+
+#[async_trait]
+impl MessageHandler for AnnounceHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        if msg == "announce" {
+            channels
+                .respond_and_broadcast("sent", "news", Recipients::Everyone)
+                .await
+                .unwrap();
+        } else {
+            // Maps with non-string keys can't be serialized to JSON
+            let unserializable = HashMap::from([((1, 2), 3)]);
+            let result = channels
+                .respond_and_broadcast("sent", &unserializable, Recipients::Everyone)
+                .await;
+            assert!(matches!(result, Err(BroadcastError::Serialize(_))));
+            channels.respond("failed").await.unwrap();
+        }
+    }
+}
+
+struct AnnouncingServer;
+
+impl Server for AnnouncingServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = AnnounceHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+
+    fn ordered_broadcasts(&self) -> bool {
+        true
+    }
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn response_arrives_before_broadcast() {
+    let (listener, addr) = AnnouncingServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { AnnouncingServer.start_with_listener(&listener).await });
+    let sender = TcpStream::connect(addr).await.unwrap();
+    let mut sender = Framed::new(sender, LengthDelimitedCodec::new());
+    let bystander = TcpStream::connect(addr).await.unwrap();
+    let mut bystander = Framed::new(bystander, LengthDelimitedCodec::new());
+    // Let the bystander join before anything is broadcast
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // Nothing is sent if either message can't be serialized
+    sender.send(Bytes::from("\"fail\"")).await.unwrap();
+    assert_eq!(recv(&mut sender).await, json!("failed"));
+
+    sender.send(Bytes::from("\"announce\"")).await.unwrap();
+    assert_eq!(recv(&mut sender).await, json!("sent"));
+    assert_eq!(recv(&mut sender).await, json!("news"));
+    assert_eq!(recv(&mut bystander).await, json!("news"));
+}
@@ -0,0 +1,97 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator, ServerRunner},
+    types::ServerMessageChannels,
+    Server,
+};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct CountHandler;
+
+#[async_trait]
+impl MessageHandler for CountHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg.len()).await.unwrap();
+    }
+}
+
+struct EchoServer;
+
+impl Server for EchoServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+}
+
+struct CountServer;
+
+impl Server for CountServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = CountHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+}
+
+fn pick(name: &str) -> Box<dyn ServerRunner> {
+    match name {
+        "echo" => Box::new(EchoServer),
+        _ => Box::new(CountServer),
+    }
+}
+
+#[tokio::test]
+async fn servers_of_different_types_run_behind_trait_objects() {
+    let mut replies = Vec::new();
+    for name in ["echo", "count"] {
+        let server = pick(name);
+        let (listener, addr) = EchoServer::bind("127.0.0.1:0").await.unwrap();
+        tokio::spawn(async move { server.run_with_listener(&listener).await });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+        framed.send(Bytes::from("\"hello\"")).await.unwrap();
+        replies.push(framed.next().await.unwrap().unwrap());
+    }
+    assert_eq!(replies, [&b"\"hello\""[..], &b"5"[..]]);
+}
@@ -0,0 +1,106 @@
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// Who's connected, written on join and leave and read by every message.
+#[derive(Default)]
+struct Roster {
+    next_id: usize,
+    members: Vec<usize>,
+}
+
+impl State for Roster {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.members.push(id);
+        id
+    }
+
+    fn on_leave(&mut self, id: &usize) {
+        self.members.retain(|member| member != id);
+    }
+}
+
+struct MembersHandler;
+
+#[async_trait]
+impl MessageHandler for MembersHandler {
+    type ClientMessage = ();
+    type ClientID = usize;
+    type State = Arc<RwLock<Roster>>;
+
+    async fn handle_client_message(
+        _msg: (),
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        state: &mut Arc<RwLock<Roster>>,
+    ) {
+        let members = serde_json::to_value(&state.read().unwrap().members).unwrap();
+        channels.response_sender.send(members).await.unwrap();
+    }
+}
+
+struct RosterServer {
+    roster: Arc<RwLock<Roster>>,
+}
+
+impl Server for RosterServer {
+    type State = Arc<RwLock<Roster>>;
+    type ClientID = usize;
+    type ClientMessage = ();
+    type ClientMessageHandler = MembersHandler;
+
+    fn get_state(&self) -> Arc<RwLock<Roster>> {
+        self.roster.clone()
+    }
+}
+
+async fn members(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    framed.send(Bytes::from("null")).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn rwlock_state_is_shared_between_connections() {
+    let roster = Arc::new(RwLock::new(Roster::default()));
+    let (listener, addr) = RosterServer::bind("127.0.0.1:0").await.unwrap();
+    let server = RosterServer {
+        roster: roster.clone(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let mut first = Framed::new(
+        TcpStream::connect(addr).await.unwrap(),
+        LengthDelimitedCodec::new(),
+    );
+    assert_eq!(members(&mut first).await, serde_json::json!([0]));
+
+    let mut second = Framed::new(
+        TcpStream::connect(addr).await.unwrap(),
+        LengthDelimitedCodec::new(),
+    );
+    assert_eq!(members(&mut second).await, serde_json::json!([0, 1]));
+    assert_eq!(members(&mut first).await, serde_json::json!([0, 1]));
+
+    drop(first);
+    while roster.read().unwrap().members != [1] {
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(members(&mut second).await, serde_json::json!([1]));
+}
@@ -0,0 +1,225 @@
+use std::{
+    io,
+    ops::ControlFlow,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{self, InputHandler},
+    sealed::{Cipher, Sealed},
+    server::{self, SequentialIdAllocator},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// Not a real cipher, but enough to tell whether the key is right.
+struct Xor(u8);
+
+impl Cipher for Xor {
+    fn encrypt(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(plaintext.iter().map(|byte| byte ^ self.0).collect())
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+        self.encrypt(ciphertext)
+    }
+}
+
+/// What the server can read of what clients send it.
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Join,
+    Direct { to: usize, body: Sealed },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Delivered<B> {
+    from: usize,
+    body: B,
+}
+
+struct RelayHandler;
+
+#[async_trait]
+impl server::MessageHandler for RelayHandler {
+    type ClientMessage = Request;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    /// Pass direct messages on, without being able to read them.
+    async fn handle_client_message(
+        msg: Request,
+        id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        match msg {
+            Request::Join => channels.respond(id).await.unwrap(),
+            Request::Direct { to, body } => {
+                let delivered = Delivered { from: *id, body };
+                channels.send_to(&to, &delivered).await.unwrap();
+            }
+        }
+    }
+}
+
+struct RelayServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for RelayServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Request;
+    type ClientMessageHandler = RelayHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+type Framing = Framed<TcpStream, LengthDelimitedCodec>;
+
+async fn send(framed: &mut Framing, msg: &impl Serialize) {
+    let frame = serde_json::to_vec(msg).unwrap();
+    framed.send(Bytes::from(frame)).await.unwrap();
+}
+
+async fn recv(framed: &mut Framing) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+async fn join(addr: std::net::SocketAddr) -> (Framing, usize) {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    send(&mut framed, &Request::Join).await;
+    let id = recv(&mut framed).await.as_u64().unwrap();
+    (framed, usize::try_from(id).unwrap())
+}
+
+#[tokio::test]
+async fn server_relays_sealed_bodies_untouched() {
+    let (listener, addr) = RelayServer::bind("127.0.0.1:0").await.unwrap();
+    let server = RelayServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let (mut alice, alice_id) = join(addr).await;
+    let (mut bob, bob_id) = join(addr).await;
+    let body = Sealed::seal("meet at noon", &Xor(42)).unwrap();
+    let direct = Request::Direct {
+        to: bob_id,
+        body: body.clone(),
+    };
+    send(&mut alice, &direct).await;
+
+    let delivered: Delivered<Sealed> = serde_json::from_value(recv(&mut bob).await).unwrap();
+    assert_eq!(delivered.from, alice_id);
+    assert_eq!(delivered.body, body);
+    let opened: String = delivered.body.open(&Xor(42)).unwrap();
+    assert_eq!(opened, "meet at noon");
+}
+
+static BAD_MESSAGES: AtomicUsize = AtomicUsize::new(0);
+
+struct ReadingHandler;
+
+#[async_trait]
+impl client::MessageHandler for ReadingHandler {
+    type ServerMessage = Delivered<String>;
+
+    /// Leave once the secret arrives.
+    async fn handle_server_message(_msg: Delivered<String>, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        msg: Delivered<String>,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        if msg.body == "psst" {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+
+    async fn handle_bad_message(_err: anyhow::Error) {
+        BAD_MESSAGES.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+struct ReadingClient;
+
+impl Client for ReadingClient {
+    type ServerMessage = Delivered<String>;
+    type ServerMessageHandler = ReadingHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+
+    fn cipher(&self) -> Option<Arc<dyn Cipher>> {
+        Some(Arc::new(Xor(42)))
+    }
+}
+
+#[tokio::test]
+async fn client_opens_sealed_bodies() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        ReadingClient.start_with_stream(stream).await
+    });
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+
+    // Sealed with the wrong key, so it doesn't decrypt to JSON
+    let wrong = Sealed::seal("psst", &Xor(7)).unwrap();
+    send(
+        &mut framed,
+        &Delivered {
+            from: 1,
+            body: wrong,
+        },
+    )
+    .await;
+    let right = Sealed::seal("psst", &Xor(42)).unwrap();
+    send(
+        &mut framed,
+        &Delivered {
+            from: 1,
+            body: right,
+        },
+    )
+    .await;
+
+    // The client read the secret, so says goodbye
+    assert_eq!(recv(&mut framed).await, json!({ "scot": "goodbye" }));
+    send(&mut framed, &json!({ "scot": "goodbye" })).await;
+    client.await.unwrap().unwrap();
+    assert_eq!(BAD_MESSAGES.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,74 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{IdAllocator, MessageHandler, SeededUuidAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+use uuid::Uuid;
+
+struct WhoAmIHandler;
+
+#[async_trait]
+impl MessageHandler for WhoAmIHandler {
+    type ClientMessage = ();
+    type ClientID = Uuid;
+    type State = SeededUuidAllocator;
+
+    async fn handle_client_message(
+        _msg: (),
+        id: &Uuid,
+        channels: &mut ServerMessageChannels<Uuid>,
+        _state: &mut SeededUuidAllocator,
+    ) {
+        channels.respond(id).await.unwrap();
+    }
+}
+
+struct UuidServer {
+    ids: SeededUuidAllocator,
+}
+
+impl Server for UuidServer {
+    type State = SeededUuidAllocator;
+    type ClientID = Uuid;
+    type ClientMessage = ();
+    type ClientMessageHandler = WhoAmIHandler;
+
+    fn get_state(&self) -> SeededUuidAllocator {
+        self.ids.clone()
+    }
+}
+
+async fn who_am_i(addr: std::net::SocketAddr) -> Uuid {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("null")).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    let id: Value = serde_json::from_slice(&frame).unwrap();
+    serde_json::from_value(id).unwrap()
+}
+
+#[tokio::test]
+async fn clients_get_the_same_ids_every_run() {
+    let server = UuidServer {
+        ids: SeededUuidAllocator::new(42),
+    };
+    let running = server.launch("127.0.0.1:0").await.unwrap();
+
+    let expected = SeededUuidAllocator::new(42);
+    for _ in 0..3 {
+        let id = who_am_i(running.local_addr()).await;
+        assert_eq!(id, expected.next_id());
+        assert_eq!(id.get_version_num(), 4);
+    }
+
+    let other = SeededUuidAllocator::new(43);
+    assert_ne!(other.next_id(), SeededUuidAllocator::new(42).next_id());
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, SequentialIdAllocator},
+    types::{BroadcastError, ServerMessageChannels},
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct BadPayloadHandler;
+
+#[async_trait]
+impl MessageHandler for BadPayloadHandler {
+    type ClientMessage = ();
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    /// Try to send a payload that can't be represented as JSON, then report
+    /// how that went.
+    async fn handle_client_message(
+        _msg: (),
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        // JSON object keys must be strings
+        let payload: HashMap<Vec<u8>, u8> = HashMap::from([(vec![1, 2], 3)]);
+
+        let responded = channels.respond(&payload).await;
+        let broadcast = channels.try_broadcast(&payload, Recipients::Everyone);
+        let report = json!({
+            "respond": responded.map_err(|e| format!("{:?}", e.kind())).err(),
+            "broadcast": matches!(broadcast, Err(BroadcastError::Serialize(_))),
+        });
+        channels.respond(&report).await.unwrap();
+    }
+}
+
+struct BadPayloadServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for BadPayloadServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = ();
+    type ClientMessageHandler = BadPayloadHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+#[tokio::test]
+async fn unserializable_messages_error_instead_of_panicking() {
+    let (listener, addr) = BadPayloadServer::bind("127.0.0.1:0").await.unwrap();
+    let server = BadPayloadServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("null")).await.unwrap();
+
+    // Nothing was sent for the bad payload, so the report comes first
+    let frame = framed.next().await.unwrap().unwrap();
+    let report: Value = serde_json::from_slice(&frame).unwrap();
+    assert_eq!(
+        report,
+        json!({ "respond": "InvalidData", "broadcast": true })
+    );
+}
@@ -0,0 +1,96 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde::{Serialize, Serializer};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+static SERIALIZED: AtomicUsize = AtomicUsize::new(0);
+
+/// A message counting how often it's serialized.
+struct Counted;
+
+impl Serialize for Counted {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SERIALIZED.fetch_add(1, Ordering::SeqCst);
+        serializer.serialize_str("counted")
+    }
+}
+
+struct BroadcastHandler;
+
+#[async_trait]
+impl MessageHandler for BroadcastHandler {
+    type ClientMessage = bool;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        broadcast: bool,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        if broadcast {
+            channels
+                .try_broadcast(&Counted, Recipients::Everyone)
+                .unwrap();
+        } else {
+            channels.respond(&"pong").await.unwrap();
+        }
+    }
+}
+
+struct BroadcastServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for BroadcastServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = bool;
+    type ClientMessageHandler = BroadcastHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    fn json_pretty(&self) -> bool {
+        true
+    }
+}
+
+#[tokio::test]
+async fn broadcasts_are_serialized_once() {
+    let (listener, addr) = BroadcastServer::bind("127.0.0.1:0").await.unwrap();
+    let server = BroadcastServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let mut clients = Vec::new();
+    for _ in 0..3 {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut client = Framed::new(stream, LengthDelimitedCodec::new());
+        // Make sure the client has joined before broadcasting
+        client.send(Bytes::from("false")).await.unwrap();
+        client.next().await.unwrap().unwrap();
+        clients.push(client);
+    }
+    clients[0].send(Bytes::from("true")).await.unwrap();
+
+    for client in &mut clients {
+        let frame = client.next().await.unwrap().unwrap();
+        assert_eq!(&frame[..], b"\"counted\"");
+    }
+    assert_eq!(SERIALIZED.load(Ordering::SeqCst), 1);
+}
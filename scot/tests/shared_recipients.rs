@@ -0,0 +1,38 @@
+use std::{collections::HashSet, sync::Arc};
+
+use scot::{server::Recipients, types::TryBroadcast};
+use tokio::sync::broadcast;
+use tokio_util::bytes::Bytes;
+
+#[test]
+fn shared_recipients_are_not_copied_per_connection() {
+    let (sender, mut first): (broadcast::Sender<(Bytes, Recipients<usize>)>, _) =
+        broadcast::channel(16);
+    let mut second = sender.subscribe();
+    let ids: Arc<[usize]> = (0..10_000).collect();
+    sender
+        .try_broadcast("hello", Recipients::shared(ids.clone()))
+        .unwrap();
+
+    let (_, first) = first.try_recv().unwrap();
+    let (_, second) = second.try_recv().unwrap();
+    for recipients in [&first, &second] {
+        let Recipients::SharedRecipients { recipients } = recipients else {
+            panic!("expected shared recipients, got {recipients:?}");
+        };
+        assert!(Arc::ptr_eq(recipients, &ids));
+    }
+    assert!(first.contains(&9_999, &HashSet::new()));
+    assert!(!first.contains(&10_000, &HashSet::new()));
+}
+
+#[test]
+fn shared_recipients_round_trip() {
+    let recipients = Recipients::shared(vec![1, 2, 3]);
+    let json = serde_json::to_string(&recipients).unwrap();
+    assert_eq!(json, r#"{"SharedRecipients":{"recipients":[1,2,3]}}"#);
+    assert_eq!(
+        serde_json::from_str::<Recipients<usize>>(&json).unwrap(),
+        recipients
+    );
+}
@@ -0,0 +1,117 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+    sync::CancellationToken,
+};
+
+#[derive(Default)]
+struct Roster {
+    next_id: usize,
+    online: Vec<usize>,
+}
+
+impl State for Roster {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.online.push(self.next_id);
+        self.next_id
+    }
+
+    fn on_leave(&mut self, id: &usize) {
+        self.online.retain(|x| x != id);
+    }
+}
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = Arc<Mutex<Roster>>;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Roster>>,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct EchoServer {
+    roster: Arc<Mutex<Roster>>,
+}
+
+impl Server for EchoServer {
+    type State = Arc<Mutex<Roster>>;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Roster>> {
+        self.roster.clone()
+    }
+
+    fn shutdown_timeout(&self) -> Option<Duration> {
+        Some(Duration::from_secs(5))
+    }
+}
+
+#[tokio::test]
+async fn shutdown_ends_every_connection_before_returning() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let roster = Arc::new(Mutex::new(Roster::default()));
+    let server = EchoServer {
+        roster: roster.clone(),
+    };
+    let shutdown = CancellationToken::new();
+    let running = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move { server.start_with_shutdown(&listener, shutdown).await }
+    });
+
+    let mut clients = Vec::new();
+    for _ in 0..3 {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+        // Make sure the client has joined
+        framed.send(Bytes::from("\"hello\"")).await.unwrap();
+        let frame = framed.next().await.unwrap().unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&frame).unwrap(),
+            json!("hello")
+        );
+        clients.push(framed);
+    }
+    assert_eq!(roster.lock().unwrap().online.len(), 3);
+
+    shutdown.cancel();
+    running.await.unwrap().unwrap();
+
+    // Every connection has been handled leaving and closed by the time the
+    // server returns
+    assert!(roster.lock().unwrap().online.is_empty());
+    for framed in &mut clients {
+        assert!(framed.next().await.is_none());
+    }
+    assert!(TcpStream::connect(addr).await.is_err());
+}
@@ -0,0 +1,86 @@
+use std::ops::ControlFlow;
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{InputHandler, MessageHandler},
+    types::ValueSender,
+    Client,
+};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct KickHandler;
+
+#[async_trait]
+impl MessageHandler for KickHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        _msg: Value,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        ControlFlow::Break(())
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+struct ButtonClient;
+
+impl Client for ButtonClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = KickHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn sink_sends_from_outside_the_input_handler() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (sink, run) = ButtonClient.start_stream_with_sink(stream);
+    let client = tokio::spawn(run);
+
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+
+    // Clones can send from other tasks, e.g. button clicks
+    let clicked = sink.clone();
+    tokio::spawn(async move { clicked.send(&json!("click")).await.unwrap() })
+        .await
+        .unwrap();
+    sink.send("timer").await.unwrap();
+    assert_eq!(recv(&mut framed).await, json!("click"));
+    assert_eq!(recv(&mut framed).await, json!("timer"));
+
+    // Once disconnected, sending fails
+    framed.send(Bytes::from("\"kick\"")).await.unwrap();
+    let goodbye = framed.next().await.unwrap().unwrap();
+    framed.send(goodbye.freeze()).await.unwrap();
+    client.await.unwrap();
+    assert!(sink.is_closed());
+    assert!(sink.send("late").await.is_err());
+}
@@ -0,0 +1,170 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::{channel::mpsc, prelude::*};
+use scot::{
+    client::{self, InputHandler},
+    server::{ConnectionObserver, MessageHandler, SequentialIdAllocator},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Block,
+    Sleep,
+}
+
+struct Recorder {
+    slow: Mutex<mpsc::UnboundedSender<Duration>>,
+}
+
+impl ConnectionObserver<usize> for Recorder {
+    fn on_slow_handler(&self, _id: &usize, elapsed: Duration) {
+        self.slow.lock().unwrap().unbounded_send(elapsed).unwrap();
+    }
+}
+
+struct SlowHandler;
+
+#[async_trait]
+impl MessageHandler for SlowHandler {
+    type ClientMessage = Request;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Request,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        match msg {
+            Request::Block => std::thread::sleep(Duration::from_millis(100)),
+            Request::Sleep => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+        channels.response_sender.send(Value::Null).await.unwrap();
+    }
+}
+
+struct SlowServer {
+    ids: SequentialIdAllocator,
+    observer: Arc<Recorder>,
+}
+
+impl Server for SlowServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Request;
+    type ClientMessageHandler = SlowHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    fn observer(&self) -> Option<Arc<dyn ConnectionObserver<usize>>> {
+        Some(self.observer.clone())
+    }
+
+    fn slow_handler_warn(&self) -> Option<Duration> {
+        Some(Duration::from_millis(50))
+    }
+}
+
+#[tokio::test]
+async fn blocking_handlers_are_reported() {
+    let (sender, mut slow) = mpsc::unbounded();
+    let (listener, addr) = SlowServer::bind("127.0.0.1:0").await.unwrap();
+    let server = SlowServer {
+        ids: SequentialIdAllocator::new(),
+        observer: Arc::new(Recorder {
+            slow: Mutex::new(sender),
+        }),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+
+    // Awaiting doesn't count, however long it takes
+    framed.send(Bytes::from("\"Sleep\"")).await.unwrap();
+    framed.next().await.unwrap().unwrap();
+    assert!(slow.try_recv().is_err());
+
+    framed.send(Bytes::from("\"Block\"")).await.unwrap();
+    framed.next().await.unwrap().unwrap();
+    let elapsed = slow.next().await.unwrap();
+    assert!(elapsed >= Duration::from_millis(100));
+}
+
+/// How long each blocking call of the client took.
+static CLIENT_SLOW: Mutex<Vec<Duration>> = Mutex::new(Vec::new());
+
+struct RecordingHandler;
+
+#[async_trait]
+impl client::MessageHandler for RecordingHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, _sender: &mut ValueSender) {}
+
+    fn on_slow_handler(elapsed: Duration) {
+        CLIENT_SLOW.lock().unwrap().push(elapsed);
+    }
+}
+
+/// Blocks the first time it's asked for input, then waits forever.
+struct BlockingInput {
+    blocked: bool,
+}
+
+#[async_trait]
+impl InputHandler for BlockingInput {
+    async fn next_input(&mut self, _sender: &mut ValueSender) {
+        if !self.blocked {
+            self.blocked = true;
+            std::thread::sleep(Duration::from_millis(100));
+            return;
+        }
+        future::pending().await
+    }
+}
+
+struct BlockingClient;
+
+impl Client for BlockingClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = RecordingHandler;
+    type InputHandler = BlockingInput;
+
+    fn input_handler(&self) -> BlockingInput {
+        BlockingInput { blocked: false }
+    }
+
+    fn slow_handler_warn(&self) -> Option<Duration> {
+        Some(Duration::from_millis(50))
+    }
+}
+
+#[tokio::test]
+async fn blocking_input_is_reported_to_the_client() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { BlockingClient.start(&addr.to_string()).await });
+    let _connection = listener.accept().await.unwrap();
+
+    while CLIENT_SLOW.lock().unwrap().is_empty() {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert!(CLIENT_SLOW.lock().unwrap()[0] >= Duration::from_millis(100));
+}
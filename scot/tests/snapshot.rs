@@ -0,0 +1,165 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// A shared log of everything that's been said so far.
+#[derive(Default)]
+struct Log {
+    next_id: usize,
+    lines: Vec<String>,
+}
+
+impl State for Log {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+struct LogHandler;
+
+#[async_trait]
+impl MessageHandler for LogHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = Arc<Mutex<Log>>;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        state: &mut Arc<Mutex<Log>>,
+    ) {
+        state.lock().unwrap().lines.push(msg.clone());
+        channels
+            .try_broadcast(msg.as_str(), Recipients::Everyone)
+            .unwrap();
+    }
+}
+
+struct LogServer {
+    state: Arc<Mutex<Log>>,
+}
+
+#[async_trait]
+impl Server for LogServer {
+    type State = Arc<Mutex<Log>>;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = LogHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Log>> {
+        self.state.clone()
+    }
+
+    async fn on_join_snapshot(
+        &self,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        state: &mut Arc<Mutex<Log>>,
+    ) {
+        let lines = state.lock().unwrap().lines.clone();
+        channels
+            .response_sender
+            .send(Value::from(lines))
+            .await
+            .unwrap();
+    }
+}
+
+async fn receive(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn new_clients_get_a_snapshot_first() {
+    let (listener, addr) = LogServer::bind("127.0.0.1:0").await.unwrap();
+    let server = LogServer {
+        state: Arc::default(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut first = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(receive(&mut first).await, serde_json::json!([]));
+
+    first.send(Bytes::from("\"hello\"")).await.unwrap();
+    assert_eq!(receive(&mut first).await, Value::from("hello"));
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut second = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(receive(&mut second).await, serde_json::json!(["hello"]));
+
+    first.send(Bytes::from("\"again\"")).await.unwrap();
+    assert_eq!(receive(&mut second).await, Value::from("again"));
+}
+
+/// Sends a snapshot far bigger than fits in the queue and socket buffers,
+/// one line at a time.
+struct HugeLogServer {
+    state: Arc<Mutex<Log>>,
+}
+
+#[async_trait]
+impl Server for HugeLogServer {
+    type State = Arc<Mutex<Log>>;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = LogHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Log>> {
+        self.state.clone()
+    }
+
+    async fn on_join_snapshot(
+        &self,
+        id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut Arc<Mutex<Log>>,
+    ) {
+        // Only the first client is sent the huge snapshot
+        if *id > 1 {
+            return;
+        }
+        let line = "x".repeat(64 * 1024);
+        for _ in 0..256 {
+            if channels.respond(&line).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn client_not_reading_its_snapshot_holds_up_no_one_else() {
+    let server = HugeLogServer {
+        state: Arc::default(),
+    };
+    let running = server.launch("127.0.0.1:0").await.unwrap();
+
+    // Never reads anything
+    let _stuck = TcpStream::connect(running.local_addr()).await.unwrap();
+    while running.connection_count() == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    let stream = TcpStream::connect(running.local_addr()).await.unwrap();
+    let mut other = Framed::new(stream, LengthDelimitedCodec::new());
+    other.send(Bytes::from("\"hello\"")).await.unwrap();
+    assert_eq!(receive(&mut other).await, Value::from("hello"));
+}
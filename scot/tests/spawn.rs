@@ -0,0 +1,89 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use async_trait::async_trait;
+use futures::{future::BoxFuture, prelude::*};
+use scot::{
+    server::{MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::{net::TcpStream, task::LocalSet};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct EchoHandler;
+
+#[async_trait]
+impl MessageHandler for EchoHandler {
+    type ClientMessage = String;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: String,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels
+            .response_sender
+            .send(Value::from(msg))
+            .await
+            .unwrap();
+    }
+}
+
+struct LocalServer {
+    ids: SequentialIdAllocator,
+    spawned: Arc<AtomicUsize>,
+}
+
+impl Server for LocalServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = String;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    fn spawn_connection(&self, task: BoxFuture<'static, ()>) {
+        self.spawned.fetch_add(1, Ordering::SeqCst);
+        tokio::task::spawn_local(task);
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn connections_can_run_on_a_local_set() {
+    let spawned = Arc::new(AtomicUsize::new(0));
+    let (listener, addr) = LocalServer::bind("127.0.0.1:0").await.unwrap();
+    let server = LocalServer {
+        ids: SequentialIdAllocator::new(),
+        spawned: spawned.clone(),
+    };
+
+    let local = LocalSet::new();
+    local.spawn_local(async move { server.start_with_listener(&listener).await });
+    local
+        .run_until(async {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+            framed.send(Bytes::from("\"hello\"")).await.unwrap();
+            let frame = framed.next().await.unwrap().unwrap();
+            assert_eq!(
+                serde_json::from_slice::<Value>(&frame).unwrap(),
+                Value::from("hello")
+            );
+        })
+        .await;
+
+    // One connection task and one writer task
+    assert_eq!(spawned.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,100 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// A per-connection state that isn't shared between connections, so
+/// anything `on_join` does is only visible if the same instance is used for
+/// the message loop.
+struct Session {
+    next_id: Arc<AtomicUsize>,
+    id: Option<usize>,
+}
+
+impl State for Session {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.id = Some(id);
+        id
+    }
+}
+
+struct WhoAmIHandler;
+
+#[async_trait]
+impl MessageHandler for WhoAmIHandler {
+    type ClientMessage = ();
+    type ClientID = usize;
+    type State = Session;
+
+    async fn handle_client_message(
+        _msg: (),
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        state: &mut Session,
+    ) {
+        let reply = serde_json::to_value(state.id).unwrap();
+        channels.response_sender.send(reply).await.unwrap();
+    }
+}
+
+struct SessionServer {
+    next_id: Arc<AtomicUsize>,
+    get_state_calls: Arc<AtomicUsize>,
+}
+
+impl Server for SessionServer {
+    type State = Session;
+    type ClientID = usize;
+    type ClientMessage = ();
+    type ClientMessageHandler = WhoAmIHandler;
+
+    fn get_state(&self) -> Session {
+        self.get_state_calls.fetch_add(1, Ordering::SeqCst);
+        Session {
+            next_id: self.next_id.clone(),
+            id: None,
+        }
+    }
+}
+
+async fn who_am_i(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    framed.send(Bytes::from("null")).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn on_join_and_message_loop_share_one_state() {
+    let get_state_calls = Arc::new(AtomicUsize::new(0));
+    let (listener, addr) = SessionServer::bind("127.0.0.1:0").await.unwrap();
+    let server = SessionServer {
+        next_id: Arc::default(),
+        get_state_calls: get_state_calls.clone(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    for expected_id in 0..2 {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+        assert_eq!(who_am_i(&mut framed).await, Value::from(expected_id));
+    }
+
+    assert_eq!(get_state_calls.load(Ordering::SeqCst), 2);
+}
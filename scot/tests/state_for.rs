@@ -0,0 +1,164 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use tokio::{net::TcpStream, sync::Semaphore, time};
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+/// A per-connection state remembering who it was made for.
+struct Peer {
+    next_id: Arc<AtomicUsize>,
+    addr: Option<SocketAddr>,
+}
+
+impl State for Peer {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id.fetch_add(1, Ordering::SeqCst)
+    }
+}
+
+struct WhereAmIHandler;
+
+#[async_trait]
+impl MessageHandler for WhereAmIHandler {
+    type ClientMessage = ();
+    type ClientID = usize;
+    type State = Peer;
+
+    async fn handle_client_message(
+        _msg: (),
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        state: &mut Peer,
+    ) {
+        let reply = serde_json::to_value(state.addr).unwrap();
+        channels.response_sender.send(reply).await.unwrap();
+    }
+}
+
+struct PeerServer {
+    next_id: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Server for PeerServer {
+    type State = Peer;
+    type ClientID = usize;
+    type ClientMessage = ();
+    type ClientMessageHandler = WhereAmIHandler;
+
+    fn get_state(&self) -> Peer {
+        Peer {
+            next_id: self.next_id.clone(),
+            addr: None,
+        }
+    }
+
+    async fn get_state_for(&self, addr: SocketAddr) -> Peer {
+        Peer {
+            addr: Some(addr),
+            ..self.get_state()
+        }
+    }
+}
+
+#[tokio::test]
+async fn state_is_built_for_the_connection() {
+    let (listener, addr) = PeerServer::bind("127.0.0.1:0").await.unwrap();
+    let server = PeerServer {
+        next_id: Arc::new(AtomicUsize::new(0)),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    for _ in 0..2 {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let local_addr = stream.local_addr().unwrap();
+        let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+        framed.send(Bytes::from("null")).await.unwrap();
+        let frame = framed.next().await.unwrap().unwrap();
+        let seen: Option<SocketAddr> = serde_json::from_slice(&frame).unwrap();
+        assert_eq!(seen, Some(local_addr));
+    }
+}
+
+/// Holds up setting up the first connection until the test lets it go.
+struct StuckServer {
+    next_id: Arc<AtomicUsize>,
+    setups: Arc<AtomicUsize>,
+    gate: Arc<Semaphore>,
+}
+
+#[async_trait]
+impl Server for StuckServer {
+    type State = Peer;
+    type ClientID = usize;
+    type ClientMessage = ();
+    type ClientMessageHandler = WhereAmIHandler;
+
+    fn get_state(&self) -> Peer {
+        Peer {
+            next_id: self.next_id.clone(),
+            addr: None,
+        }
+    }
+
+    async fn get_state_for(&self, addr: SocketAddr) -> Peer {
+        if self.setups.fetch_add(1, Ordering::SeqCst) == 0 {
+            self.gate.acquire().await.unwrap().forget();
+        }
+        Peer {
+            addr: Some(addr),
+            ..self.get_state()
+        }
+    }
+}
+
+async fn where_am_i(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Option<SocketAddr> {
+    framed.send(Bytes::from("null")).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn slow_state_only_holds_up_its_own_connection() {
+    let gate = Arc::new(Semaphore::new(0));
+    let setups = Arc::new(AtomicUsize::new(0));
+    let server = StuckServer {
+        next_id: Arc::new(AtomicUsize::new(0)),
+        setups: setups.clone(),
+        gate: gate.clone(),
+    };
+    let running = server.launch("127.0.0.1:0").await.unwrap();
+
+    let stuck = TcpStream::connect(running.local_addr()).await.unwrap();
+    let stuck_addr = stuck.local_addr().unwrap();
+    let mut stuck = Framed::new(stuck, LengthDelimitedCodec::new());
+    while setups.load(Ordering::SeqCst) == 0 {
+        time::sleep(Duration::from_millis(5)).await;
+    }
+
+    let other = TcpStream::connect(running.local_addr()).await.unwrap();
+    let other_addr = other.local_addr().unwrap();
+    let mut other = Framed::new(other, LengthDelimitedCodec::new());
+    assert_eq!(where_am_i(&mut other).await, Some(other_addr));
+
+    gate.add_permits(1);
+    assert_eq!(where_am_i(&mut stuck).await, Some(stuck_addr));
+}
@@ -0,0 +1,99 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::{channel::mpsc, prelude::*};
+use scot::{
+    server::{ConnectionObserver, ConnectionStats, MessageHandler, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct Recorder {
+    closed: Mutex<mpsc::UnboundedSender<(usize, u64, u64)>>,
+}
+
+impl ConnectionObserver<usize> for Recorder {
+    fn on_close(&self, id: &usize, stats: &ConnectionStats) {
+        let closed = (*id, stats.bytes_read(), stats.bytes_written());
+        self.closed.lock().unwrap().unbounded_send(closed).unwrap();
+    }
+}
+
+struct StatsHandler;
+
+#[async_trait]
+impl MessageHandler for StatsHandler {
+    type ClientMessage = ();
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        _msg: (),
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        let own = channels.stats.bytes_read();
+        let total = channels.connections.stats().bytes_read();
+        channels.respond(&[own, total]).await.unwrap();
+    }
+}
+
+struct StatsServer {
+    ids: SequentialIdAllocator,
+    observer: Arc<Recorder>,
+}
+
+impl Server for StatsServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = ();
+    type ClientMessageHandler = StatsHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+
+    fn observer(&self) -> Option<Arc<dyn ConnectionObserver<usize>>> {
+        Some(self.observer.clone())
+    }
+}
+
+async fn ask(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    framed.send(Bytes::from("null")).await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+#[tokio::test]
+async fn bytes_are_counted_per_connection_and_in_total() {
+    let (sender, mut closed) = mpsc::unbounded();
+    let (listener, addr) = StatsServer::bind("127.0.0.1:0").await.unwrap();
+    let server = StatsServer {
+        ids: SequentialIdAllocator::new(),
+        observer: Arc::new(Recorder {
+            closed: Mutex::new(sender),
+        }),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    // Each request is the 4 bytes of `null`
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut first = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(ask(&mut first).await, json!([4, 4]));
+    assert_eq!(ask(&mut first).await, json!([8, 8]));
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut second = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(ask(&mut second).await, json!([4, 12]));
+
+    // Both answers to the first client were the 5 bytes of `[n,n]`
+    drop(first);
+    assert_eq!(closed.next().await.unwrap(), (0, 8, 10));
+}
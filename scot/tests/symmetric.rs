@@ -0,0 +1,119 @@
+use std::{
+    ops::ControlFlow,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use async_trait::async_trait;
+use futures::future;
+use scot::{
+    client,
+    server::{self, SequentialIdAllocator},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde::{Deserialize, Serialize};
+
+/// Sent both ways: each side answers a ping with a pong.
+#[derive(Serialize, Deserialize)]
+enum Message {
+    Ping(u32),
+    Pong(u32),
+}
+
+static SERVER_GOT_PONG: AtomicU32 = AtomicU32::new(0);
+
+struct ServerHandler;
+
+#[async_trait]
+impl server::MessageHandler for ServerHandler {
+    type ClientMessage = Message;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Message,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        match msg {
+            Message::Ping(n) => {
+                channels.respond(&Message::Pong(n)).await.unwrap();
+                channels.respond(&Message::Ping(n + 1)).await.unwrap();
+            }
+            Message::Pong(n) => SERVER_GOT_PONG.store(n, Ordering::SeqCst),
+        }
+    }
+}
+
+struct PeerServer;
+
+impl Server for PeerServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Message;
+    type ClientMessageHandler = ServerHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+}
+
+struct ClientHandler;
+
+#[async_trait]
+impl client::MessageHandler for ClientHandler {
+    type ServerMessage = Message;
+
+    async fn handle_server_message(_msg: Message, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        msg: Message,
+        response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        match msg {
+            Message::Ping(n) => {
+                response_channel
+                    .send_message(&Message::Pong(n))
+                    .await
+                    .unwrap();
+                ControlFlow::Break(())
+            }
+            Message::Pong(_) => ControlFlow::Continue(()),
+        }
+    }
+}
+
+struct FirstPing;
+
+#[async_trait]
+impl client::InputHandler for FirstPing {
+    async fn next_input(&mut self, message_channel: &mut ValueSender) {
+        message_channel
+            .send_message(&Message::Ping(1))
+            .await
+            .unwrap();
+        future::pending::<()>().await;
+    }
+}
+
+struct PeerClient;
+
+impl Client for PeerClient {
+    type ServerMessage = Message;
+    type ServerMessageHandler = ClientHandler;
+    type InputHandler = FirstPing;
+
+    fn input_handler(&self) -> FirstPing {
+        FirstPing
+    }
+}
+
+#[tokio::test]
+async fn one_enum_is_sent_both_ways() {
+    let (listener, addr) = PeerServer::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { PeerServer.start_with_listener(&listener).await });
+
+    PeerClient.start(&addr.to_string()).await.unwrap();
+    assert_eq!(SERVER_GOT_PONG.load(Ordering::SeqCst), 2);
+}
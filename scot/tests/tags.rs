@@ -0,0 +1,150 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use scot::{
+    server::{MessageHandler, Recipients, SequentialIdAllocator},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Tag(String),
+    Untag(String),
+    Announce { tag: String, text: String },
+    Ping,
+}
+
+struct TagHandler;
+
+#[async_trait]
+impl MessageHandler for TagHandler {
+    type ClientMessage = Request;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Request,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        let reply = match msg {
+            Request::Tag(tag) => {
+                channels.set_tag(&tag);
+                "tagged"
+            }
+            Request::Untag(tag) => {
+                channels.clear_tag(&tag);
+                "untagged"
+            }
+            Request::Announce { tag, text } => {
+                let recipients = Recipients::Tagged { tag };
+                channels.try_broadcast(&text, recipients).unwrap();
+                "announced"
+            }
+            Request::Ping => "pong",
+        };
+        channels
+            .response_sender
+            .send(Value::from(reply))
+            .await
+            .unwrap();
+    }
+}
+
+struct TagServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for TagServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Request;
+    type ClientMessageHandler = TagHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+struct TestClient(Framed<TcpStream, LengthDelimitedCodec>);
+
+impl TestClient {
+    async fn connect(addr: std::net::SocketAddr) -> TestClient {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        TestClient(Framed::new(stream, LengthDelimitedCodec::new()))
+    }
+
+    async fn send(&mut self, request: Request) {
+        let frame = serde_json::to_vec(&request).unwrap();
+        self.0.send(Bytes::from(frame)).await.unwrap();
+    }
+
+    async fn receive(&mut self) -> Value {
+        let frame = self.0.next().await.unwrap().unwrap();
+        serde_json::from_slice(&frame).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn tagged_broadcasts_only_reach_tagged_clients() {
+    let (listener, addr) = TagServer::bind("127.0.0.1:0").await.unwrap();
+    let server = TagServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let mut admin = TestClient::connect(addr).await;
+    admin.send(Request::Tag("admin".to_string())).await;
+    assert_eq!(admin.receive().await, Value::from("tagged"));
+
+    let mut user = TestClient::connect(addr).await;
+    user.send(Request::Tag("user".to_string())).await;
+    assert_eq!(user.receive().await, Value::from("tagged"));
+
+    user.send(Request::Announce {
+        tag: "admin".to_string(),
+        text: "hello admins".to_string(),
+    })
+    .await;
+    assert_eq!(user.receive().await, Value::from("announced"));
+    assert_eq!(admin.receive().await, Value::from("hello admins"));
+
+    user.send(Request::Ping).await;
+    assert_eq!(user.receive().await, Value::from("pong"));
+}
+
+#[tokio::test]
+async fn cleared_tags_stop_routing() {
+    let (listener, addr) = TagServer::bind("127.0.0.1:0").await.unwrap();
+    let server = TagServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    let mut member = TestClient::connect(addr).await;
+    member.send(Request::Tag("room5".to_string())).await;
+    assert_eq!(member.receive().await, Value::from("tagged"));
+    member.send(Request::Untag("room5".to_string())).await;
+    assert_eq!(member.receive().await, Value::from("untagged"));
+
+    let mut speaker = TestClient::connect(addr).await;
+    speaker
+        .send(Request::Announce {
+            tag: "room5".to_string(),
+            text: "anyone here?".to_string(),
+        })
+        .await;
+    assert_eq!(speaker.receive().await, Value::from("announced"));
+
+    // The announcement was skipped, so the next thing is the reply
+    member.send(Request::Ping).await;
+    assert_eq!(member.receive().await, Value::from("pong"));
+}
@@ -0,0 +1,133 @@
+use std::{ops::ControlFlow, sync::Mutex};
+
+use async_trait::async_trait;
+use futures::{channel::oneshot, future};
+use scot::{
+    client::{self, InputHandler},
+    server::{self, Recipients, SequentialIdAllocator},
+    testing::{connect_in_memory, TestServer},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Echo(String),
+    Shout(String),
+}
+
+struct ChatHandler;
+
+#[async_trait]
+impl server::MessageHandler for ChatHandler {
+    type ClientMessage = Request;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Request,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        match msg {
+            Request::Echo(text) => channels.respond(&text).await.unwrap(),
+            Request::Shout(text) => channels.try_broadcast(&text, Recipients::Everyone).unwrap(),
+        }
+    }
+}
+
+struct ChatServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for ChatServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Request;
+    type ClientMessageHandler = ChatHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+fn chat_server() -> ChatServer {
+    ChatServer {
+        ids: SequentialIdAllocator::new(),
+    }
+}
+
+#[tokio::test]
+async fn raw_clients_talk_to_the_server_in_memory() {
+    let server = TestServer::new(chat_server());
+    let mut first = server.connect().await.unwrap();
+    let mut second = server.connect().await.unwrap();
+    assert_eq!(server.connections().count(), 2);
+
+    first.send(&Request::Echo("hi".into())).await.unwrap();
+    assert_eq!(first.recv::<String>().await.unwrap(), Some("hi".into()));
+
+    second.send(&Request::Shout("hey".into())).await.unwrap();
+    assert_eq!(first.recv::<String>().await.unwrap(), Some("hey".into()));
+    assert_eq!(second.recv::<String>().await.unwrap(), Some("hey".into()));
+}
+
+static GREETING: Mutex<Option<oneshot::Sender<String>>> = Mutex::new(None);
+
+struct GreetingHandler;
+
+#[async_trait]
+impl client::MessageHandler for GreetingHandler {
+    type ServerMessage = String;
+
+    async fn handle_server_message(_msg: String, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(
+        msg: String,
+        _response_channel: &mut ValueSender,
+    ) -> ControlFlow<()> {
+        if let Some(greeting) = GREETING.lock().unwrap().take() {
+            greeting.send(msg).unwrap();
+        }
+        ControlFlow::Break(())
+    }
+}
+
+struct SayHello;
+
+#[async_trait]
+impl InputHandler for SayHello {
+    async fn next_input(&mut self, message_channel: &mut ValueSender) {
+        message_channel
+            .send_message(&Request::Echo("hello".into()))
+            .await
+            .unwrap();
+        future::pending::<()>().await;
+    }
+}
+
+struct GreetingClient;
+
+impl Client for GreetingClient {
+    type ServerMessage = String;
+    type ServerMessageHandler = GreetingHandler;
+    type InputHandler = SayHello;
+
+    fn input_handler(&self) -> SayHello {
+        SayHello
+    }
+}
+
+#[tokio::test]
+async fn client_connects_in_memory() {
+    let (sender, receiver) = oneshot::channel();
+    *GREETING.lock().unwrap() = Some(sender);
+
+    let (_server, client) = connect_in_memory(chat_server(), GreetingClient)
+        .await
+        .unwrap();
+    assert_eq!(receiver.await.unwrap(), "hello");
+    client.await.unwrap().unwrap();
+}
@@ -0,0 +1,22 @@
+use scot::{
+    server::Recipients,
+    types::{BroadcastError, TryBroadcast},
+};
+use tokio::sync::broadcast;
+use tokio_util::bytes::Bytes;
+
+#[test]
+fn broadcasting_after_everyone_left_fails_without_panicking() {
+    let (sender, receiver): (broadcast::Sender<(Bytes, Recipients<usize>)>, _) =
+        broadcast::channel(16);
+    sender.try_broadcast("hello", Recipients::Everyone).unwrap();
+
+    // The last client leaves between choosing recipients and broadcasting
+    drop(receiver);
+    let result = sender.try_broadcast("hello", Recipients::everyone_but(&1, [1, 2]));
+    assert!(matches!(result, Err(BroadcastError::NoReceivers)));
+
+    // Broadcasting to no one at all isn't an error
+    let result = sender.try_broadcast("hello", Recipients::everyone_but(&1, [1]));
+    assert!(result.is_ok());
+}
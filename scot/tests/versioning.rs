@@ -0,0 +1,189 @@
+use std::{
+    ops::ControlFlow,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{self, InputHandler},
+    server::{self, SequentialIdAllocator},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+struct EchoHandler;
+
+#[async_trait]
+impl server::MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+/// Speaks version 2, and disconnects clients speaking anything else.
+struct StrictServer;
+
+impl Server for StrictServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+
+    fn protocol_version(&self) -> u32 {
+        2
+    }
+}
+
+/// Speaks version 2, but still understands version 1.
+struct LenientServer;
+
+impl Server for LenientServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        SequentialIdAllocator::new()
+    }
+
+    fn protocol_version(&self) -> u32 {
+        2
+    }
+
+    fn on_version_mismatch(
+        client_version: Option<u32>,
+        _state: &mut SequentialIdAllocator,
+    ) -> bool {
+        client_version == Some(1)
+    }
+}
+
+async fn start<S: Server + Send + Sync>(server: S) -> std::net::SocketAddr {
+    let (listener, addr) = S::bind("127.0.0.1:0").await.unwrap();
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+    addr
+}
+
+async fn send(framed: &mut Framed<TcpStream, LengthDelimitedCodec>, value: Value) {
+    let frame = serde_json::to_vec(&value).unwrap();
+    framed.send(Bytes::from(frame)).await.unwrap();
+}
+
+async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Value {
+    let frame = framed.next().await.unwrap().unwrap();
+    serde_json::from_slice(&frame).unwrap()
+}
+
+async fn connect(addr: std::net::SocketAddr) -> Framed<TcpStream, LengthDelimitedCodec> {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    assert_eq!(
+        recv(&mut framed).await,
+        json!({ "scot": "version", "version": 2 })
+    );
+    framed
+}
+
+#[tokio::test]
+async fn matching_versions_are_served() {
+    let mut framed = connect(start(StrictServer).await).await;
+    send(&mut framed, json!({ "scot": "version", "version": 2 })).await;
+    send(&mut framed, json!("hello")).await;
+    assert_eq!(recv(&mut framed).await, json!("hello"));
+}
+
+#[tokio::test]
+async fn mismatched_versions_are_disconnected() {
+    let mut framed = connect(start(StrictServer).await).await;
+    send(&mut framed, json!({ "scot": "version", "version": 1 })).await;
+    send(&mut framed, json!("hello")).await;
+    assert!(framed.next().await.is_none());
+}
+
+#[tokio::test]
+async fn undeclared_versions_are_disconnected() {
+    let mut framed = connect(start(StrictServer).await).await;
+    send(&mut framed, json!("hello")).await;
+    assert!(framed.next().await.is_none());
+}
+
+#[tokio::test]
+async fn servers_can_accept_other_versions() {
+    let mut framed = connect(start(LenientServer).await).await;
+    send(&mut framed, json!({ "scot": "version", "version": 1 })).await;
+    send(&mut framed, json!("hello")).await;
+    assert_eq!(recv(&mut framed).await, json!("hello"));
+}
+
+static MISMATCHED: AtomicBool = AtomicBool::new(false);
+
+struct VersionHandler;
+
+#[async_trait]
+impl client::MessageHandler for VersionHandler {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, _response_channel: &mut ValueSender) {}
+
+    async fn on_version_mismatch(server_version: Option<u32>) -> ControlFlow<()> {
+        assert_eq!(server_version, Some(2));
+        MISMATCHED.store(true, Ordering::SeqCst);
+        ControlFlow::Break(())
+    }
+}
+
+struct NoInput;
+
+#[async_trait]
+impl InputHandler for NoInput {
+    async fn next_input(&mut self, _message_channel: &mut ValueSender) {
+        future::pending::<()>().await;
+    }
+}
+
+/// Speaks version 1.
+struct OldClient;
+
+impl Client for OldClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = VersionHandler;
+    type InputHandler = NoInput;
+
+    fn input_handler(&self) -> NoInput {
+        NoInput
+    }
+
+    fn protocol_version(&self) -> u32 {
+        1
+    }
+}
+
+#[tokio::test]
+async fn clients_disconnect_from_mismatched_servers() {
+    let addr = start(LenientServer).await;
+    let stream = TcpStream::connect(addr).await.unwrap();
+    // The server would keep serving it, but the client gives up
+    OldClient.start_with_stream(stream).await.unwrap();
+    assert!(MISMATCHED.load(Ordering::SeqCst));
+}
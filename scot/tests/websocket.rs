@@ -0,0 +1,127 @@
+use std::{
+    ops::ControlFlow,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use async_trait::async_trait;
+use futures::{future, prelude::*};
+use scot::{
+    client::{self, InputHandler},
+    server::{self, SequentialIdAllocator},
+    types::{ServerMessageChannels, ValueSender},
+    Client, Server,
+};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+struct EchoHandler;
+
+#[async_trait]
+impl server::MessageHandler for EchoHandler {
+    type ClientMessage = Value;
+    type ClientID = usize;
+    type State = SequentialIdAllocator;
+
+    async fn handle_client_message(
+        msg: Value,
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        _state: &mut SequentialIdAllocator,
+    ) {
+        channels.respond(&msg).await.unwrap();
+    }
+}
+
+struct EchoServer {
+    ids: SequentialIdAllocator,
+}
+
+impl Server for EchoServer {
+    type State = SequentialIdAllocator;
+    type ClientID = usize;
+    type ClientMessage = Value;
+    type ClientMessageHandler = EchoHandler;
+
+    fn get_state(&self) -> SequentialIdAllocator {
+        self.ids.clone()
+    }
+}
+
+async fn spawn_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = EchoServer {
+        ids: SequentialIdAllocator::new(),
+    };
+    tokio::spawn(async move { server.start_ws_with_listener(&listener).await });
+    format!("ws://{addr}")
+}
+
+#[tokio::test]
+async fn browser_style_clients_exchange_one_message_per_ws_message() {
+    let url = spawn_server().await;
+    let (mut ws, _response) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+    // Both text and binary messages are accepted, replies are sent as text
+    ws.send(Message::text(r#"{"hello":"text"}"#)).await.unwrap();
+    ws.send(Message::binary(&b"[1,2,3]"[..])).await.unwrap();
+    for expected in [json!({ "hello": "text" }), json!([1, 2, 3])] {
+        let Message::Text(text) = ws.next().await.unwrap().unwrap() else {
+            panic!("expected a text message");
+        };
+        let value: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value, expected);
+    }
+}
+
+static ECHOED: AtomicBool = AtomicBool::new(false);
+
+struct LeaveOnEcho;
+
+#[async_trait]
+impl client::MessageHandler for LeaveOnEcho {
+    type ServerMessage = Value;
+
+    async fn handle_server_message(_msg: Value, _response_channel: &mut ValueSender) {}
+
+    async fn on_server_message(msg: Value, _response_channel: &mut ValueSender) -> ControlFlow<()> {
+        assert_eq!(msg, json!("ping"));
+        ECHOED.store(true, Ordering::SeqCst);
+        ControlFlow::Break(())
+    }
+}
+
+struct PingOnce {
+    sent: bool,
+}
+
+#[async_trait]
+impl InputHandler for PingOnce {
+    async fn next_input(&mut self, message_channel: &mut ValueSender) {
+        if self.sent {
+            future::pending::<()>().await;
+        }
+        self.sent = true;
+        message_channel.send(json!("ping")).await.unwrap();
+    }
+}
+
+struct PingClient;
+
+impl Client for PingClient {
+    type ServerMessage = Value;
+    type ServerMessageHandler = LeaveOnEcho;
+    type InputHandler = PingOnce;
+
+    fn input_handler(&self) -> PingOnce {
+        PingOnce { sent: false }
+    }
+}
+
+#[tokio::test]
+async fn native_clients_connect_over_websockets() {
+    let url = spawn_server().await;
+    PingClient.start_ws(&url).await.unwrap();
+    assert!(ECHOED.load(Ordering::SeqCst));
+}
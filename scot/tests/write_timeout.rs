@@ -0,0 +1,106 @@
+use std::{io, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use parking_lot::Mutex;
+use scot::{
+    server::{MessageHandler, State},
+    types::ServerMessageChannels,
+    Server,
+};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_util::{
+    bytes::Bytes,
+    codec::{Framed, LengthDelimitedCodec},
+};
+
+#[derive(Default)]
+struct Record {
+    next_id: usize,
+    send_error: Option<io::ErrorKind>,
+    left: Vec<usize>,
+}
+
+impl State for Record {
+    type ClientID = usize;
+
+    fn on_join(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn on_leave(&mut self, id: &usize) {
+        self.left.push(*id);
+    }
+}
+
+struct FloodHandler;
+
+#[async_trait]
+impl MessageHandler for FloodHandler {
+    type ClientMessage = ();
+    type ClientID = usize;
+    type State = Arc<Mutex<Record>>;
+
+    /// Send to the client until sending fails.
+    async fn handle_client_message(
+        _msg: (),
+        _id: &usize,
+        channels: &mut ServerMessageChannels<usize>,
+        state: &mut Arc<Mutex<Record>>,
+    ) {
+        let chunk = Value::from("x".repeat(64 * 1024));
+        loop {
+            if let Err(e) = channels.response_sender.send(chunk.clone()).await {
+                state.lock().send_error = Some(e.kind());
+                return;
+            }
+        }
+    }
+}
+
+struct FloodServer {
+    state: Arc<Mutex<Record>>,
+}
+
+impl Server for FloodServer {
+    type State = Arc<Mutex<Record>>;
+    type ClientID = usize;
+    type ClientMessage = ();
+    type ClientMessageHandler = FloodHandler;
+
+    fn get_state(&self) -> Arc<Mutex<Record>> {
+        self.state.clone()
+    }
+
+    fn write_timeout(&self) -> Option<Duration> {
+        Some(Duration::from_millis(100))
+    }
+}
+
+#[tokio::test]
+async fn client_that_stops_reading_is_disconnected() {
+    let state: Arc<Mutex<Record>> = Arc::default();
+    let (listener, addr) = FloodServer::bind("127.0.0.1:0").await.unwrap();
+    let server = FloodServer {
+        state: state.clone(),
+    };
+    tokio::spawn(async move { server.start_with_listener(&listener).await });
+
+    // Ask for a flood of messages, then never read any of them
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from("null")).await.unwrap();
+
+    for _ in 0..100 {
+        if !state.lock().left.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    let state = state.lock();
+    assert_eq!(state.left, vec![1]);
+    assert!(state.send_error.is_some());
+}